@@ -0,0 +1,222 @@
+use anchor_lang::prelude::*;
+
+use crate::{Charity, ConfigAccount, CustomError, VotingRound};
+
+/// Account allowed to attest charity wallets, appointed by the admin.
+#[account]
+pub struct Verifier {
+    pub authority: Pubkey,
+    pub active: bool,
+    pub bump: u8,
+}
+
+/// Attestation of a charity wallet by a verifier. Rounds requiring attestations only
+/// accept and pay charities whose wallet has an unrevoked, unexpired attestation.
+#[account]
+pub struct Attestation {
+    pub wallet: Pubkey,
+    pub verifier: Pubkey,
+    pub documents_hash: [u8; 32], // hash of the documents checked by the verifier
+    pub issued_at: i64,
+    pub expires_at: i64,
+    pub revoked: bool,
+    pub bump: u8,
+}
+
+impl Attestation {
+    pub fn is_valid(&self, now: i64) -> bool {
+        !self.revoked && now < self.expires_at
+    }
+}
+
+#[event]
+pub struct VerifierUpdatedEvent {
+    pub authority: Pubkey,
+    pub active: bool,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct AttestationIssuedEvent {
+    pub wallet: Pubkey,
+    pub verifier: Pubkey,
+    pub documents_hash: [u8; 32],
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct AttestationRevokedEvent {
+    pub wallet: Pubkey,
+    pub verifier: Pubkey,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(authority: Pubkey)]
+pub struct SetVerifier<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + std::mem::size_of::<Verifier>(),
+        seeds = [b"verifier".as_ref(), authority.as_ref()],
+        bump
+    )]
+    pub verifier: Account<'info, Verifier>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct IssueAttestation<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    /// Only required when the signer is not the admin.
+    #[account(
+        seeds = [b"verifier".as_ref(), authority.key().as_ref()],
+        bump = verifier.bump,
+        constraint = verifier.active @ CustomError::VerifierInactive,
+    )]
+    pub verifier: Option<Account<'info, Verifier>>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + std::mem::size_of::<Attestation>(),
+        seeds = [b"attestation".as_ref(), wallet.as_ref()],
+        bump
+    )]
+    pub attestation: Account<'info, Attestation>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct RevokeAttestation<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds = [b"verifier".as_ref(), authority.key().as_ref()],
+        bump = verifier.bump,
+        constraint = verifier.active @ CustomError::VerifierInactive,
+    )]
+    pub verifier: Account<'info, Verifier>,
+    #[account(
+        mut,
+        seeds = [b"attestation".as_ref(), wallet.as_ref()],
+        bump = attestation.bump,
+    )]
+    pub attestation: Account<'info, Attestation>,
+    pub authority: Signer<'info>,
+}
+
+/// Checks the charity can take part in the round: rounds requiring attestations need a
+/// valid attestation of the charity wallet.
+pub fn require_attested(
+    voting_round: &VotingRound,
+    charity: &Charity,
+    attestation: Option<&Attestation>,
+    now: i64,
+) -> Result<()> {
+    if !voting_round.require_attestation {
+        return Ok(());
+    }
+    let attestation = attestation.ok_or(CustomError::CharityNotAttested)?;
+    require!(
+        attestation.wallet == charity.wallet && attestation.is_valid(now),
+        CustomError::CharityNotAttested
+    );
+    Ok(())
+}
+
+/// Whether a winner of a round requiring attestations lost its attestation, a revoked or
+/// expired one. Needs the attestation account, which the winner had to join the round.
+pub fn is_unattested(
+    voting_round: &VotingRound,
+    attestation: Option<&Account<Attestation>>,
+    now: i64,
+) -> bool {
+    voting_round.require_attestation && attestation.is_some_and(|a| !a.is_valid(now))
+}
+
+/// Appoints or removes a verifier.
+pub fn set_verifier(ctx: Context<SetVerifier>, authority: Pubkey, active: bool) -> Result<()> {
+    let verifier = &mut ctx.accounts.verifier;
+    verifier.authority = authority;
+    verifier.active = active;
+    verifier.bump = ctx.bumps.verifier;
+    emit!(VerifierUpdatedEvent {
+        authority,
+        active,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    Ok(())
+}
+
+/// Attests a charity wallet until `expires_at`. Issuing again renews the attestation,
+/// also after a revocation, which only the verifier that issued it or the admin can do.
+pub fn issue_attestation(
+    ctx: Context<IssueAttestation>,
+    wallet: Pubkey,
+    documents_hash: [u8; 32],
+    expires_at: i64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        wallet != Pubkey::default() && expires_at > now,
+        CustomError::InvalidAttestation
+    );
+    let authority = ctx.accounts.authority.key();
+    let is_admin = authority == ctx.accounts.config_account.config.admin;
+    require!(
+        is_admin || ctx.accounts.verifier.is_some(),
+        CustomError::VerifierInactive
+    );
+    let attestation = &mut ctx.accounts.attestation;
+    require!(
+        is_admin || attestation.verifier == Pubkey::default() || attestation.verifier == authority,
+        CustomError::AttestationVerifierMismatch
+    );
+    attestation.wallet = wallet;
+    attestation.verifier = authority;
+    attestation.documents_hash = documents_hash;
+    attestation.issued_at = now;
+    attestation.expires_at = expires_at;
+    attestation.revoked = false;
+    attestation.bump = ctx.bumps.attestation;
+    emit!(AttestationIssuedEvent {
+        wallet,
+        verifier: attestation.verifier,
+        documents_hash,
+        expires_at,
+    });
+    Ok(())
+}
+
+/// Revokes the attestation of a charity wallet, blocking its further payouts in rounds
+/// requiring attestations.
+pub fn revoke_attestation(ctx: Context<RevokeAttestation>, wallet: Pubkey) -> Result<()> {
+    ctx.accounts.attestation.revoked = true;
+    emit!(AttestationRevokedEvent {
+        wallet,
+        verifier: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    Ok(())
+}
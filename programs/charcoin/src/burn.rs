@@ -2,7 +2,196 @@ use crate::{ConfigAccount, CustomError};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock::Clock;
 use anchor_spl::token_interface::{Mint, TokenAccount};
-use anchor_spl::token_2022::{Burn, burn, Token2022 as Token};
+use anchor_spl::token_interface::{Burn, burn, TokenInterface, TransferChecked};
+use crate::transfer::transfer_checked_with_hook;
+
+/// Number of burns kept in the burn ledger history.
+pub const BURN_LEDGER_CAPACITY: usize = 64;
+
+/// Where burned CHAR comes from.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BurnSource {
+    #[default]
+    MarketingShare, // death wallet share of the marketing fees
+    Buyback,        // CHAR bought back or sent to the death wallet
+    Voluntary,      // holders burning their own CHAR
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct BurnEntry {
+    pub amount: u64,
+    pub source: BurnSource,
+    pub timestamp: i64,
+}
+
+/// History of the burns. The latest `BURN_LEDGER_CAPACITY` burns are kept in a ring
+/// buffer, the entry of burn number `n` is at `n % BURN_LEDGER_CAPACITY`. Totals per
+/// source cover every burn since the ledger was created.
+#[account]
+pub struct BurnLedger {
+    pub burn_count: u64, // number of burns recorded, also the number of the next burn
+    pub total_by_source: [u64; 3], // indexed by `BurnSource`
+    pub entries: [BurnEntry; BURN_LEDGER_CAPACITY],
+    pub bump: u8,
+}
+
+impl BurnLedger {
+    /// Adds a burn to the history and the totals.
+    pub fn record(&mut self, source: BurnSource, amount: u64, now: i64) -> Result<()> {
+        let total = &mut self.total_by_source[source as usize];
+        *total = total.checked_add(amount).ok_or(CustomError::MathError)?;
+        self.entries[(self.burn_count % BURN_LEDGER_CAPACITY as u64) as usize] = BurnEntry {
+            amount,
+            source,
+            timestamp: now,
+        };
+        self.burn_count += 1;
+        Ok(())
+    }
+}
+
+/// Voluntary burns of one holder, for the burn leaderboard.
+#[account]
+pub struct BurnerStats {
+    pub burner: Pubkey,
+    pub total_burned: u64,
+    pub burn_count: u64,
+    pub last_burn_at: i64,
+    pub bump: u8,
+}
+
+#[event]
+pub struct VoluntaryBurnEvent {
+    pub burner: Pubkey,
+    pub amount: u64,
+    pub burner_total: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBurnLedger<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<BurnLedger>(),
+        seeds = [b"burn_ledger".as_ref()],
+        bump
+    )]
+    pub burn_ledger: Box<Account<'info, BurnLedger>>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BurnVoluntary<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"burn_ledger".as_ref()],
+        bump = burn_ledger.bump,
+    )]
+    pub burn_ledger: Box<Account<'info, BurnLedger>>,
+    #[account(
+        init_if_needed,
+        payer = burner,
+        space = 8 + std::mem::size_of::<BurnerStats>(),
+        seeds = [b"burner_stats".as_ref(), burner.key().as_ref()],
+        bump
+    )]
+    pub burner_stats: Account<'info, BurnerStats>,
+    #[account(mut,
+        constraint = burner_token_account.owner == burner.key(),
+        constraint = burner_token_account.mint == config_account.config.char_token_mint
+    )]
+    pub burner_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut,
+    constraint = mint.key() == config_account.config.char_token_mint,
+    constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub burner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Max keeper tip, in basis points of the death wallet balance.
+pub const MAX_KEEPER_TIP_BPS: u16 = 100;
+
+/// When the death wallet can be burned. The death wallet is the `death_wallet` PDA,
+/// so anyone can trigger the burn once its balance reaches `min_balance` or
+/// `min_interval` has passed since the previous burn, and get a tip for it.
+#[account]
+pub struct BuybackConfig {
+    pub min_balance: u64,
+    pub min_interval: i64, // seconds between two burns below `min_balance`
+    pub keeper_tip_bps: u16,
+    pub last_buyback_at: i64,
+    pub death_wallet_bump: u8,
+    pub bump: u8,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBuybackConfig<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<BuybackConfig>(),
+        seeds = [b"buyback_config".as_ref()],
+        bump
+    )]
+    pub buyback_config: Account<'info, BuybackConfig>,
+    /// CHECK: PDA owning the death wallet token account.
+    #[account(
+        seeds = [b"death_wallet".as_ref()],
+        bump
+    )]
+    pub death_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBuybackConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"buyback_config".as_ref()],
+        bump = buyback_config.bump,
+    )]
+    pub buyback_config: Account<'info, BuybackConfig>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
 
 #[derive(Accounts)]
 pub struct ExecuteBuyback<'info> {
@@ -12,56 +201,229 @@ pub struct ExecuteBuyback<'info> {
         bump
     )]
     pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"buyback_config".as_ref()],
+        bump = buyback_config.bump,
+    )]
+    pub buyback_config: Account<'info, BuybackConfig>,
     #[account(mut,
-    constraint = mint.key() == config_account.config.char_token_mint)]
+    constraint = mint.key() == config_account.config.char_token_mint,
+    constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
     pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA owning the death wallet token account.
+    #[account(
+        seeds = [b"death_wallet".as_ref()],
+        bump = buyback_config.death_wallet_bump
+    )]
+    pub death_wallet: UncheckedAccount<'info>,
     #[account(mut,
-            constraint = burn_wallet_ata.owner.key() ==  config_account.config.death_wallet,
+            constraint = burn_wallet_ata.owner.key() ==  death_wallet.key(),
             constraint = burn_wallet_ata.mint.key() ==  config_account.config.char_token_mint
     )]
     pub burn_wallet_ata: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
-        constraint = config_account.config.death_wallet == burn_authority.key() 
+        seeds = [b"burn_ledger".as_ref()],
+        bump = burn_ledger.bump,
+    )]
+    pub burn_ledger: Box<Account<'info, BurnLedger>>,
+    /// Token account receiving the keeper tip.
+    #[account(mut,
+        constraint = keeper_token_account.mint == config_account.config.char_token_mint
     )]
-    pub burn_authority: Signer<'info>,
+    pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub keeper: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
+fn check_keeper_tip(keeper_tip_bps: u16) -> Result<()> {
+    require!(
+        keeper_tip_bps <= MAX_KEEPER_TIP_BPS,
+        CustomError::InvalidKeeperTip
+    );
+    Ok(())
+}
+
+/// Creates the buyback config and makes the `death_wallet` PDA the death wallet of the config.
+/// The program cannot move CHAR left in the previous death wallet: its owner has to send it
+/// to the token account of the PDA to have it burned, `DeathWalletChangedEvent` names both.
+pub fn initialize_buyback_config(
+    ctx: Context<InitializeBuybackConfig>,
+    min_balance: u64,
+    min_interval: i64,
+    keeper_tip_bps: u16,
+) -> Result<()> {
+    check_keeper_tip(keeper_tip_bps)?;
+    let buyback_config = &mut ctx.accounts.buyback_config;
+    buyback_config.min_balance = min_balance;
+    buyback_config.min_interval = min_interval;
+    buyback_config.keeper_tip_bps = keeper_tip_bps;
+    buyback_config.death_wallet_bump = ctx.bumps.death_wallet;
+    buyback_config.bump = ctx.bumps.buyback_config;
+    let config = &mut ctx.accounts.config_account.config;
+    let old_death_wallet = config.death_wallet;
+    config.death_wallet = ctx.accounts.death_wallet.key();
+    emit!(DeathWalletChangedEvent {
+        old_death_wallet,
+        new_death_wallet: config.death_wallet,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    Ok(())
+}
+
+/// Changes the burn thresholds and the keeper tip.
+pub fn update_buyback_config(
+    ctx: Context<UpdateBuybackConfig>,
+    min_balance: u64,
+    min_interval: i64,
+    keeper_tip_bps: u16,
+) -> Result<()> {
+    check_keeper_tip(keeper_tip_bps)?;
+    let buyback_config = &mut ctx.accounts.buyback_config;
+    buyback_config.min_balance = min_balance;
+    buyback_config.min_interval = min_interval;
+    buyback_config.keeper_tip_bps = keeper_tip_bps;
+    Ok(())
+}
+
+// Permissionless, meant to be run by keepers: burns the death wallet once its
+// balance or the time since the previous burn is high enough, minus the keeper tip.
+pub fn execute_buyback<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ExecuteBuyback<'info>>,
+) -> Result<()> {
+    let balance = ctx.accounts.burn_wallet_ata.amount;
+    require!(balance > 0, CustomError::NoTokensToBuyback);
+    let now = Clock::get()?.unix_timestamp;
+    let buyback_config = &ctx.accounts.buyback_config;
+    require!(
+        balance >= buyback_config.min_balance
+            || now - buyback_config.last_buyback_at >= buyback_config.min_interval,
+        CustomError::BuybackNotDue
+    );
+    let tip = (balance as u128 * buyback_config.keeper_tip_bps as u128 / 10_000) as u64;
+    let tokens_to_buy = balance - tip;
+
+    let death_wallet_seeds: &[&[u8]] = &[b"death_wallet", &[buyback_config.death_wallet_bump]];
+    let signer = &[death_wallet_seeds];
+    if tip > 0 {
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.burn_wallet_ata.to_account_info(),
+                    to: ctx.accounts.keeper_token_account.to_account_info(),
+                    authority: ctx.accounts.death_wallet.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+                signer,
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            tip,
+            ctx.accounts.mint.decimals,
+        )?;
+    }
 
-// this function will be run in the backend inside a cron job
-pub fn execute_buyback(ctx: Context<ExecuteBuyback>) -> Result<()> {
-    let tokens_to_buy = ctx.accounts.burn_wallet_ata.amount;
-    require!(tokens_to_buy > 0, CustomError::NoTokensToBuyback);
     // Burn tokens from the burn_wallet.
-    let burn_ctx = CpiContext::new(
+    let burn_ctx = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
         Burn {
             mint: ctx.accounts.mint.to_account_info(),
             from: ctx.accounts.burn_wallet_ata.to_account_info(),
-            authority: ctx.accounts.burn_authority.to_account_info(),
+            authority: ctx.accounts.death_wallet.to_account_info(),
         },
+        signer,
     );
     burn(burn_ctx, tokens_to_buy)?;
 
     // Update the burn tracker.
     let tracker = &mut ctx.accounts.config_account.config;
     tracker.total_burned += tokens_to_buy;
-
-    // Cache the current timestamp.
-    let current_time = Clock::get()?.unix_timestamp as u64;
+    ctx.accounts.buyback_config.last_buyback_at = now;
+    ctx.accounts
+        .burn_ledger
+        .record(BurnSource::Buyback, tokens_to_buy, now)?;
 
     // Emit an event logging the buyback and burn details.
     emit!(BuybackBurnEvent {
         tokens_bought: tokens_to_buy,
         new_total_burned: tracker.total_burned,
-        timestamp: current_time,
+        timestamp: now as u64,
     });
+    emit!(KeeperTipPaidEvent {
+        keeper: ctx.accounts.keeper.key(),
+        tip,
+        timestamp: now as u64,
+    });
+
+    Ok(())
+}
+
+/// Creates the burn ledger.
+pub fn initialize_burn_ledger(ctx: Context<InitializeBurnLedger>) -> Result<()> {
+    ctx.accounts.burn_ledger.bump = ctx.bumps.burn_ledger;
+    Ok(())
+}
+
+/// Burns CHAR of the signer and credits it to their burner stats.
+pub fn burn_voluntary(ctx: Context<BurnVoluntary>, amount: u64) -> Result<()> {
+    require!(amount > 0, CustomError::InvalidArg);
+    burn(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Burn {
+                mint: ctx.accounts.mint.to_account_info(),
+                from: ctx.accounts.burner_token_account.to_account_info(),
+                authority: ctx.accounts.burner.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let tracker = &mut ctx.accounts.config_account.config;
+    tracker.total_burned = tracker
+        .total_burned
+        .checked_add(amount)
+        .ok_or(CustomError::MathError)?;
+    ctx.accounts
+        .burn_ledger
+        .record(BurnSource::Voluntary, amount, now)?;
+
+    let burner_stats = &mut ctx.accounts.burner_stats;
+    burner_stats.burner = ctx.accounts.burner.key();
+    burner_stats.total_burned = burner_stats
+        .total_burned
+        .checked_add(amount)
+        .ok_or(CustomError::MathError)?;
+    burner_stats.burn_count += 1;
+    burner_stats.last_burn_at = now;
+    burner_stats.bump = ctx.bumps.burner_stats;
 
+    emit!(VoluntaryBurnEvent {
+        burner: burner_stats.burner,
+        amount,
+        burner_total: burner_stats.total_burned,
+        timestamp: now as u64,
+    });
     Ok(())
 }
 
+#[event]
+pub struct DeathWalletChangedEvent {
+    pub old_death_wallet: Pubkey,
+    pub new_death_wallet: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct KeeperTipPaidEvent {
+    pub keeper: Pubkey,
+    pub tip: u64,
+    pub timestamp: u64,
+}
+
 #[event]
 pub struct BuybackBurnEvent {
     pub tokens_bought: u64,
@@ -0,0 +1,237 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::distribution::share;
+use crate::{ConfigAccount, CustomError, DistributionConfig, DistributionSplits};
+
+/// Max number of points of the target supply curve.
+pub const MAX_TARGET_POINTS: usize = 8;
+
+/// Target supply at a point in time.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug)]
+pub struct TargetPoint {
+    pub timestamp: i64,
+    pub target_supply: u64,
+}
+
+/// Supply-responsive burn share of the marketing fees. The target supply is linearly
+/// interpolated between the points of the curve. Above target the burn share goes from
+/// `min_burn` up to `max_burn`, reached when supply is `full_burn_excess` per-mille above
+/// target. Within `taper_excess` per-mille of the target the share tapers linearly to
+/// zero, so it does not jump to `min_burn` right above target. At or below target nothing
+/// is burned. The marketing wallets share the rest in the ratio of their splits. When
+/// disabled the fixed splits apply.
+#[account]
+pub struct BurnPolicy {
+    pub enabled: bool,
+    pub min_burn: u16,         // per-mille of the marketing share
+    pub max_burn: u16,         // per-mille of the marketing share
+    pub full_burn_excess: u16, // per-mille of excess supply where `max_burn` applies
+    pub taper_excess: u16,     // per-mille of excess supply below which the share tapers to zero
+    pub curve: [TargetPoint; MAX_TARGET_POINTS],
+    pub point_count: u8,
+    pub bump: u8,
+}
+
+impl BurnPolicy {
+    /// Target supply of the curve at `now`.
+    pub fn target_supply(&self, now: i64) -> u64 {
+        let points = &self.curve[..self.point_count as usize];
+        let (Some(first), Some(last)) = (points.first(), points.last()) else {
+            return 0;
+        };
+        if now <= first.timestamp {
+            return first.target_supply;
+        }
+        if now >= last.timestamp {
+            return last.target_supply;
+        }
+        let index = points.iter().position(|p| p.timestamp > now).unwrap_or(0);
+        let (from, to) = (points[index - 1], points[index]);
+        let elapsed = (now - from.timestamp) as i128;
+        let length = (to.timestamp - from.timestamp) as i128;
+        let delta = to.target_supply as i128 - from.target_supply as i128;
+        (from.target_supply as i128 + delta * elapsed / length) as u64
+    }
+
+    /// Burn share, per-mille of the marketing share, for the current supply.
+    pub fn burn_share(&self, supply: u64, now: i64) -> u16 {
+        let target = self.target_supply(now);
+        if supply <= target {
+            return 0;
+        }
+        let excess = if target == 0 {
+            u128::MAX
+        } else {
+            (supply - target) as u128 * 1000 / target as u128
+        };
+        let full = self.full_burn_excess as u128;
+        let range = (self.max_burn - self.min_burn) as u128;
+        let share = self.min_burn as u128 + range * excess.min(full) / full;
+        let taper = self.taper_excess as u128;
+        if excess < taper {
+            return (share * excess / taper) as u16;
+        }
+        share as u16
+    }
+}
+
+/// Splits the marketing share into marketing wallet 1, marketing wallet 2 and burn amounts.
+pub fn split_marketing(
+    total: u64,
+    splits: &DistributionSplits,
+    burn_policy: &BurnPolicy,
+    supply: u64,
+    now: i64,
+) -> Result<(u64, u64, u64)> {
+    if !burn_policy.enabled {
+        return Ok((
+            share(total, splits.marketing_wallet_1)?,
+            share(total, splits.marketing_wallet_2)?,
+            share(total, splits.burn)?,
+        ));
+    }
+    let burn = share(total, burn_policy.burn_share(supply, now))?;
+    let wallets = total - burn;
+    let wallet_shares = splits.marketing_wallet_1 as u128 + splits.marketing_wallet_2 as u128;
+    let wallet_1 = (wallets as u128 * splits.marketing_wallet_1 as u128)
+        .checked_div(wallet_shares)
+        .unwrap_or(0) as u64;
+    let wallet_2 = (wallets as u128 * splits.marketing_wallet_2 as u128)
+        .checked_div(wallet_shares)
+        .unwrap_or(0) as u64;
+    Ok((wallet_1, wallet_2, burn))
+}
+
+#[event]
+pub struct BurnPolicyUpdatedEvent {
+    pub enabled: bool,
+    pub min_burn: u16,
+    pub max_burn: u16,
+    pub full_burn_excess: u16,
+    pub taper_excess: u16,
+    pub curve: Vec<TargetPoint>,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBurnPolicy<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<BurnPolicy>(),
+        seeds = [b"burn_policy".as_ref()],
+        bump
+    )]
+    pub burn_policy: Account<'info, BurnPolicy>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetBurnPolicy<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"burn_policy".as_ref()],
+        bump = burn_policy.bump,
+    )]
+    pub burn_policy: Account<'info, BurnPolicy>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetBurnShare<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds = [b"burn_policy".as_ref()],
+        bump = burn_policy.bump,
+    )]
+    pub burn_policy: Account<'info, BurnPolicy>,
+    #[account(
+        seeds = [b"distribution_config".as_ref()],
+        bump = distribution_config.bump,
+    )]
+    pub distribution_config: Account<'info, DistributionConfig>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+}
+
+/// Creates the burn policy, disabled.
+pub fn initialize_burn_policy(ctx: Context<InitializeBurnPolicy>) -> Result<()> {
+    ctx.accounts.burn_policy.bump = ctx.bumps.burn_policy;
+    Ok(())
+}
+
+/// Replaces the burn bounds and the target supply curve, or disables the policy.
+pub fn set_burn_policy(
+    ctx: Context<SetBurnPolicy>,
+    enabled: bool,
+    min_burn: u16,
+    max_burn: u16,
+    full_burn_excess: u16,
+    taper_excess: u16,
+    curve: Vec<TargetPoint>,
+) -> Result<()> {
+    require!(
+        min_burn <= max_burn
+            && max_burn <= 1000
+            && full_burn_excess > 0
+            && taper_excess > 0
+            && taper_excess <= full_burn_excess
+            && curve.len() <= MAX_TARGET_POINTS
+            && (!enabled || !curve.is_empty())
+            && curve.windows(2).all(|w| w[0].timestamp < w[1].timestamp),
+        CustomError::InvalidBurnPolicy
+    );
+    let burn_policy = &mut ctx.accounts.burn_policy;
+    burn_policy.enabled = enabled;
+    burn_policy.min_burn = min_burn;
+    burn_policy.max_burn = max_burn;
+    burn_policy.full_burn_excess = full_burn_excess;
+    burn_policy.taper_excess = taper_excess;
+    burn_policy.curve = [TargetPoint::default(); MAX_TARGET_POINTS];
+    burn_policy.curve[..curve.len()].copy_from_slice(&curve);
+    burn_policy.point_count = curve.len() as u8;
+
+    emit!(BurnPolicyUpdatedEvent {
+        enabled,
+        min_burn,
+        max_burn,
+        full_burn_excess,
+        taper_excess,
+        curve,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    Ok(())
+}
+
+/// Returns the burn share, per-mille of the marketing share, for the current supply.
+/// The fixed burn split of the distribution config when the policy is disabled.
+pub fn get_burn_share(ctx: Context<GetBurnShare>) -> Result<u16> {
+    let burn_policy = &ctx.accounts.burn_policy;
+    if !burn_policy.enabled {
+        return Ok(ctx.accounts.distribution_config.splits.burn);
+    }
+    Ok(burn_policy.burn_share(ctx.accounts.mint.supply, Clock::get()?.unix_timestamp))
+}
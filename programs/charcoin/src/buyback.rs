@@ -0,0 +1,538 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_spl::token_interface::{
+    burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    BurnLedger, BurnSource, BuybackBurnEvent, ConfigAccount, CustomError, OutflowLimiter, Treasury,
+    LIMIT_INCREASE_DELAY,
+};
+
+/// AMM used by the buyback. Any program implementing the swap interface can be
+/// configured: an anchor `swap(amount_in, min_amount_out)` instruction taking
+/// pool, user source, user destination, pool source vault, pool destination vault,
+/// source mint, destination mint, user, source token program and destination
+/// token program, followed by the transfer hook accounts.
+///
+/// The AMM never gets the treasury accounts: the quote asset is moved to a swap
+/// account of the buyback authority, which signs the swap and receives the CHAR.
+#[account]
+pub struct AmmConfig {
+    pub amm_program: Pubkey,
+    pub pool: Pubkey,        // constant product pool of CHAR and the quote mint
+    pub quote_mint: Pubkey,  // asset spent by the buyback, e.g. wrapped SOL or USDC
+    pub quote_vault: Pubkey, // treasury account holding the quote asset
+    pub quote_vault_bump: u8,
+    pub bump: u8,
+    // AMM program and pool waiting for the timelock
+    pub pending_amm_program: Pubkey,
+    pub pending_pool: Pubkey,
+    pub pending_apply_after: i64, // 0 when nothing is pending
+}
+
+#[event]
+pub struct AmmConfigProposedEvent {
+    pub amm_program: Pubkey,
+    pub pool: Pubkey,
+    pub apply_after: i64,
+}
+
+#[event]
+pub struct AmmConfigUpdatedEvent {
+    pub amm_program: Pubkey,
+    pub pool: Pubkey,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeAmmConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<AmmConfig>(),
+        seeds = [b"amm_config".as_ref()],
+        bump
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Account<'info, Treasury>,
+    /// Treasury vault of the quote asset.
+    #[account(
+        init,
+        payer = admin,
+        token::mint = quote_mint,
+        token::authority = treasury,
+        token::token_program = quote_token_program,
+        seeds = [b"treasury_vault".as_ref(), quote_mint.key().as_ref()],
+        bump
+    )]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(constraint = quote_mint.key() != config_account.config.char_token_mint)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: PDA signing the swaps, owns the swap accounts.
+    #[account(
+        seeds = [b"buyback_authority".as_ref()],
+        bump
+    )]
+    pub buyback_authority: UncheckedAccount<'info>,
+    /// Holds the quote asset of a single swap.
+    #[account(
+        init,
+        payer = admin,
+        token::mint = quote_mint,
+        token::authority = buyback_authority,
+        token::token_program = quote_token_program,
+        seeds = [b"buyback_swap".as_ref(), quote_mint.key().as_ref()],
+        bump
+    )]
+    pub swap_quote_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Receives the CHAR bought by a swap before it is burned.
+    #[account(
+        init,
+        payer = admin,
+        token::mint = mint,
+        token::authority = buyback_authority,
+        token::token_program = token_program,
+        seeds = [b"buyback_swap".as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub swap_char_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAmmConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"amm_config".as_ref()],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeQuoteOutflowLimiter<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds = [b"amm_config".as_ref()],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    /// Limits the quote asset spent by the buyback, in units of the quote mint.
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<OutflowLimiter>(),
+        seeds = [b"outflow_limiter".as_ref(), amm_config.quote_mint.as_ref()],
+        bump
+    )]
+    pub quote_outflow_limiter: Account<'info, OutflowLimiter>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateQuoteOutflowLimiter<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds = [b"amm_config".as_ref()],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Account<'info, AmmConfig>,
+    #[account(
+        mut,
+        seeds = [b"outflow_limiter".as_ref(), amm_config.quote_mint.as_ref()],
+        bump = quote_outflow_limiter.bump,
+    )]
+    pub quote_outflow_limiter: Account<'info, OutflowLimiter>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BuybackSwap<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [b"amm_config".as_ref()],
+        bump = amm_config.bump,
+    )]
+    pub amm_config: Box<Account<'info, AmmConfig>>,
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+    /// Treasury vault the quote asset is taken from.
+    #[account(mut, address = amm_config.quote_vault)]
+    pub quote_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: PDA signing the swap, owns the swap accounts.
+    #[account(
+        seeds = [b"buyback_authority".as_ref()],
+        bump
+    )]
+    pub buyback_authority: UncheckedAccount<'info>,
+    /// The only quote account the AMM gets, holds `amount_in` during the swap.
+    #[account(
+        mut,
+        seeds = [b"buyback_swap".as_ref(), quote_mint.key().as_ref()],
+        bump,
+    )]
+    pub swap_quote_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Receives the bought CHAR before it is burned.
+    #[account(
+        mut,
+        seeds = [b"buyback_swap".as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub swap_char_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"burn_ledger".as_ref()],
+        bump = burn_ledger.bump,
+    )]
+    pub burn_ledger: Box<Account<'info, BurnLedger>>,
+    /// Outflow limiter of the quote asset, the CHAR limiter does not count it.
+    #[account(
+        mut,
+        seeds = [b"outflow_limiter".as_ref(), amm_config.quote_mint.as_ref()],
+        bump = quote_outflow_limiter.bump,
+    )]
+    pub quote_outflow_limiter: Box<Account<'info, OutflowLimiter>>,
+    /// CHECK: pool of the configured AMM, validated by the AMM.
+    #[account(address = amm_config.pool)]
+    pub pool: UncheckedAccount<'info>,
+    /// CHECK: quote vault of the pool, validated by the AMM.
+    #[account(mut)]
+    pub pool_quote_vault: UncheckedAccount<'info>,
+    /// CHECK: CHAR vault of the pool, validated by the AMM.
+    #[account(mut)]
+    pub pool_char_vault: UncheckedAccount<'info>,
+    /// CHECK: the configured AMM program.
+    #[account(executable, address = amm_config.amm_program)]
+    pub amm_program: UncheckedAccount<'info>,
+    #[account(address = amm_config.quote_mint,
+        constraint = *quote_mint.to_account_info().owner == quote_token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub quote_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(mut,
+        constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Authority allowed to trigger treasury releases.
+    #[account(
+        constraint = config_account.config.treasury_authority == treasury_authority.key()
+    )]
+    pub treasury_authority: Signer<'info>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Configures the AMM used by the buyback and creates the quote vault of the treasury.
+pub fn initialize_amm_config(
+    ctx: Context<InitializeAmmConfig>,
+    amm_program: Pubkey,
+    pool: Pubkey,
+) -> Result<()> {
+    let amm_config = &mut ctx.accounts.amm_config;
+    amm_config.amm_program = amm_program;
+    amm_config.pool = pool;
+    amm_config.quote_mint = ctx.accounts.quote_mint.key();
+    amm_config.quote_vault = ctx.accounts.quote_vault.key();
+    amm_config.quote_vault_bump = ctx.bumps.quote_vault;
+    amm_config.bump = ctx.bumps.amm_config;
+    Ok(())
+}
+
+/// Proposes another AMM program or pool of the same quote mint. Like raised
+/// outflow limits, it can only be applied after `LIMIT_INCREASE_DELAY`.
+pub fn propose_amm_config(
+    ctx: Context<UpdateAmmConfig>,
+    amm_program: Pubkey,
+    pool: Pubkey,
+) -> Result<()> {
+    let apply_after = Clock::get()?.unix_timestamp + LIMIT_INCREASE_DELAY;
+    let amm_config = &mut ctx.accounts.amm_config;
+    amm_config.pending_amm_program = amm_program;
+    amm_config.pending_pool = pool;
+    amm_config.pending_apply_after = apply_after;
+    emit!(AmmConfigProposedEvent {
+        amm_program,
+        pool,
+        apply_after,
+    });
+    Ok(())
+}
+
+/// Points the buyback to the proposed AMM program and pool once the timelock has passed.
+pub fn apply_amm_config(ctx: Context<UpdateAmmConfig>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let amm_config = &mut ctx.accounts.amm_config;
+    require!(
+        amm_config.pending_apply_after != 0,
+        CustomError::NoPendingAmmConfig
+    );
+    require!(
+        now >= amm_config.pending_apply_after,
+        CustomError::AmmConfigTimelocked
+    );
+    amm_config.amm_program = amm_config.pending_amm_program;
+    amm_config.pool = amm_config.pending_pool;
+    amm_config.pending_apply_after = 0;
+    emit!(AmmConfigUpdatedEvent {
+        amm_program: amm_config.amm_program,
+        pool: amm_config.pool,
+        timestamp: now as u64,
+    });
+    Ok(())
+}
+
+/// Creates the outflow limiter of the quote asset spent by the buyback.
+pub fn initialize_quote_outflow_limiter(
+    ctx: Context<InitializeQuoteOutflowLimiter>,
+    daily_limit: u64,
+    monthly_limit: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let bump = ctx.bumps.quote_outflow_limiter;
+    ctx.accounts
+        .quote_outflow_limiter
+        .set_up(daily_limit, monthly_limit, bump, now)
+}
+
+/// Changes the quote outflow limits, with the same timelock on raised limits
+/// as the CHAR outflow limits.
+pub fn propose_quote_outflow_limits(
+    ctx: Context<UpdateQuoteOutflowLimiter>,
+    daily_limit: u64,
+    monthly_limit: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts
+        .quote_outflow_limiter
+        .propose_limits(daily_limit, monthly_limit, now)
+}
+
+/// Applies the raised quote outflow limits once the timelock has passed.
+pub fn apply_quote_outflow_limits(ctx: Context<UpdateQuoteOutflowLimiter>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.quote_outflow_limiter.apply_limits(now)
+}
+
+/// Anchor discriminator of the `swap` instruction of the AMM.
+fn swap_discriminator() -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(b"global:swap").to_bytes()[..8]);
+    discriminator
+}
+
+/// Whether the AMM left a swap account as it was given: same owner, no delegate
+/// and no close authority.
+fn is_untouched(account: &TokenAccount, authority: &Pubkey) -> bool {
+    account.owner == *authority && account.delegate.is_none() && account.close_authority.is_none()
+}
+
+/// Buys CHAR with `amount_in` of the quote asset through the configured AMM and
+/// burns everything bought. Fails when less than `min_amount_out` CHAR arrives
+/// in the swap account, after the CHAR transfer fee. What the AMM spends counts
+/// as an outflow of the quote asset; the rest goes back to the quote vault.
+pub fn buyback_swap<'info>(
+    ctx: Context<'_, '_, 'info, 'info, BuybackSwap<'info>>,
+    amount_in: u64,
+    min_amount_out: u64,
+) -> Result<()> {
+    require!(amount_in > 0, CustomError::InvalidArg);
+    let now = Clock::get()?.unix_timestamp;
+
+    // Move the quote asset of this swap out of the treasury vault
+    let treasury_seeds: &[&[u8]] = &[b"treasury", &[ctx.accounts.treasury.bump]];
+    transfer_checked(
+        CpiContext::new_with_signer(
+            ctx.accounts.quote_token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.quote_vault.to_account_info(),
+                to: ctx.accounts.swap_quote_account.to_account_info(),
+                authority: ctx.accounts.treasury.to_account_info(),
+                mint: ctx.accounts.quote_mint.to_account_info(),
+            },
+            &[treasury_seeds],
+        ),
+        amount_in,
+        ctx.accounts.quote_mint.decimals,
+    )?;
+    ctx.accounts.swap_quote_account.reload()?;
+    let quote_before = ctx.accounts.swap_quote_account.amount;
+    let balance_before = ctx.accounts.swap_char_account.amount;
+
+    let mut data = swap_discriminator().to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+    let a = &ctx.accounts;
+    let mut accounts = vec![
+        AccountMeta::new_readonly(a.pool.key(), false),
+        AccountMeta::new(a.swap_quote_account.key(), false),
+        AccountMeta::new(a.swap_char_account.key(), false),
+        AccountMeta::new(a.pool_quote_vault.key(), false),
+        AccountMeta::new(a.pool_char_vault.key(), false),
+        AccountMeta::new_readonly(a.quote_mint.key(), false),
+        AccountMeta::new_readonly(a.mint.key(), false),
+        AccountMeta::new_readonly(a.buyback_authority.key(), true),
+        AccountMeta::new_readonly(a.quote_token_program.key(), false),
+        AccountMeta::new_readonly(a.token_program.key(), false),
+    ];
+    let mut account_infos = vec![
+        a.pool.to_account_info(),
+        a.swap_quote_account.to_account_info(),
+        a.swap_char_account.to_account_info(),
+        a.pool_quote_vault.to_account_info(),
+        a.pool_char_vault.to_account_info(),
+        a.quote_mint.to_account_info(),
+        a.mint.to_account_info(),
+        a.buyback_authority.to_account_info(),
+        a.quote_token_program.to_account_info(),
+        a.token_program.to_account_info(),
+    ];
+    // Transfer hook accounts of CHAR
+    for account in ctx.remaining_accounts {
+        accounts.push(if account.is_writable {
+            AccountMeta::new(account.key(), false)
+        } else {
+            AccountMeta::new_readonly(account.key(), false)
+        });
+        account_infos.push(account.clone());
+    }
+    account_infos.push(a.amm_program.to_account_info());
+
+    let authority_seeds: &[&[u8]] = &[b"buyback_authority", &[ctx.bumps.buyback_authority]];
+    let signer = &[authority_seeds];
+    invoke_signed(
+        &Instruction {
+            program_id: a.amm_program.key(),
+            accounts,
+            data,
+        },
+        &account_infos,
+        signer,
+    )?;
+
+    // The AMM is not trusted: it may not spend more than `amount_in` nor keep
+    // any right on the swap accounts
+    ctx.accounts.swap_quote_account.reload()?;
+    ctx.accounts.swap_char_account.reload()?;
+    let authority = ctx.accounts.buyback_authority.key();
+    let spent = quote_before.saturating_sub(ctx.accounts.swap_quote_account.amount);
+    require!(
+        spent <= amount_in
+            && is_untouched(&ctx.accounts.swap_quote_account, &authority)
+            && is_untouched(&ctx.accounts.swap_char_account, &authority),
+        CustomError::SwapAccountTampered
+    );
+    ctx.accounts
+        .quote_outflow_limiter
+        .record_outflow(spent, now)?;
+
+    // Slippage guard on what actually arrived
+    let bought = ctx
+        .accounts
+        .swap_char_account
+        .amount
+        .checked_sub(balance_before)
+        .ok_or(CustomError::MathError)?;
+    require!(bought >= min_amount_out, CustomError::SlippageExceeded);
+
+    burn(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Burn {
+                mint: ctx.accounts.mint.to_account_info(),
+                from: ctx.accounts.swap_char_account.to_account_info(),
+                authority: ctx.accounts.buyback_authority.to_account_info(),
+            },
+            signer,
+        ),
+        bought,
+    )?;
+
+    // Return what the AMM did not spend
+    let unspent = ctx.accounts.swap_quote_account.amount;
+    if unspent > 0 {
+        transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.quote_token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.swap_quote_account.to_account_info(),
+                    to: ctx.accounts.quote_vault.to_account_info(),
+                    authority: ctx.accounts.buyback_authority.to_account_info(),
+                    mint: ctx.accounts.quote_mint.to_account_info(),
+                },
+                signer,
+            ),
+            unspent,
+            ctx.accounts.quote_mint.decimals,
+        )?;
+    }
+
+    let tracker = &mut ctx.accounts.config_account.config;
+    tracker.total_burned = tracker
+        .total_burned
+        .checked_add(bought)
+        .ok_or(CustomError::MathError)?;
+    ctx.accounts
+        .burn_ledger
+        .record(BurnSource::Buyback, bought, now)?;
+
+    emit!(BuybackBurnEvent {
+        tokens_bought: bought,
+        new_total_burned: tracker.total_burned,
+        timestamp: now as u64,
+    });
+    msg!("Bought back and burned {} CHAR for {}", bought, spent);
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use anchor_lang::prelude::*;
+
+use crate::{ConfigAccount, CustomError};
+
+/// Split tree of the collected fees. Every value is per-mille of its parent
+/// and every level adds up to 1000.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DistributionSplits {
+    // Fees
+    pub staking: u16,            // staking rewards
+    pub donation_ecosystem: u16, // reward system + donation system
+    pub marketing: u16,          // buyback & marketing
+    // Donation ecosystem
+    pub reward_system: u16,
+    pub donation_system: u16,
+    // Reward system
+    pub monthly_rewards: u16,
+    pub annual_rewards: u16,
+    // Monthly and annual rewards
+    pub top_tier: u16,
+    pub charity_lottery: u16,
+    // Donation system
+    pub monthly_donations: u16,
+    pub annual_donations: u16,
+    pub char_fund: u16,
+    // Monthly and annual donations
+    pub one_time_causes: u16,
+    pub infinite_impact_causes: u16,
+    // Buyback & marketing
+    pub marketing_wallet_1: u16,
+    pub marketing_wallet_2: u16,
+    pub burn: u16,
+}
+
+impl DistributionSplits {
+    /// Splits from the CHAR Coin schema.
+    pub const DEFAULT: DistributionSplits = DistributionSplits {
+        staking: 150,
+        donation_ecosystem: 750,
+        marketing: 100,
+        reward_system: 200,
+        donation_system: 800,
+        monthly_rewards: 500,
+        annual_rewards: 500,
+        top_tier: 500,
+        charity_lottery: 500,
+        monthly_donations: 800,
+        annual_donations: 100,
+        char_fund: 100,
+        one_time_causes: 500,
+        infinite_impact_causes: 500,
+        marketing_wallet_1: 425,
+        marketing_wallet_2: 425,
+        burn: 150,
+    };
+
+    /// Checks that every level of the tree adds up to 1000.
+    pub fn validate(&self) -> Result<()> {
+        let levels: [&[u16]; 7] = [
+            &[self.staking, self.donation_ecosystem, self.marketing],
+            &[self.reward_system, self.donation_system],
+            &[self.monthly_rewards, self.annual_rewards],
+            &[self.top_tier, self.charity_lottery],
+            &[self.monthly_donations, self.annual_donations, self.char_fund],
+            &[self.one_time_causes, self.infinite_impact_causes],
+            &[self.marketing_wallet_1, self.marketing_wallet_2, self.burn],
+        ];
+        for level in levels {
+            let sum: u32 = level.iter().map(|share| *share as u32).sum();
+            require!(sum == 1000, CustomError::InvalidDistributionSplit);
+        }
+        Ok(())
+    }
+}
+
+/// Per-mille share of an amount.
+pub fn share(amount: u64, per_mille: u16) -> Result<u64> {
+    Ok((amount as u128)
+        .checked_mul(per_mille as u128)
+        .ok_or(CustomError::MathError)?
+        .checked_div(1000)
+        .ok_or(CustomError::MathError)? as u64)
+}
+
+#[account]
+pub struct DistributionConfig {
+    pub splits: DistributionSplits,
+    pub bump: u8,
+}
+
+#[event]
+pub struct DistributionConfigUpdatedEvent {
+    pub before: DistributionSplits,
+    pub after: DistributionSplits,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeDistributionConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<DistributionConfig>(),
+        seeds = [b"distribution_config".as_ref()],
+        bump
+    )]
+    pub distribution_config: Account<'info, DistributionConfig>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDistributionConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"distribution_config".as_ref()],
+        bump = distribution_config.bump,
+    )]
+    pub distribution_config: Account<'info, DistributionConfig>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Creates the distribution config with the default splits.
+pub fn initialize_distribution_config(ctx: Context<InitializeDistributionConfig>) -> Result<()> {
+    let distribution_config = &mut ctx.accounts.distribution_config;
+    distribution_config.splits = DistributionSplits::DEFAULT;
+    distribution_config.bump = ctx.bumps.distribution_config;
+    Ok(())
+}
+
+/// Replaces the whole split tree.
+pub fn update_distribution_config(
+    ctx: Context<UpdateDistributionConfig>,
+    splits: DistributionSplits,
+) -> Result<()> {
+    splits.validate()?;
+
+    let distribution_config = &mut ctx.accounts.distribution_config;
+    let before = distribution_config.splits;
+    distribution_config.splits = splits;
+
+    emit!(DistributionConfigUpdatedEvent {
+        before,
+        after: splits,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    Ok(())
+}
@@ -1,12 +1,47 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock::Clock;
 
-use crate::{ConfigAccount, CustomError, UserStakeInfo};
+use crate::{Config, ConfigAccount, CustomError, MilestonePlan, UserStakeInfo};
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Debug)]
 pub enum CharityStatus {
     Active,
     Finalized,
+    Cancelled, // removed by the admin
+    Vetoed,    // removed by a governance vote
+}
+
+/// Donation classification of a charity, each funded by its donation wallet of the config.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub enum CharityCategory {
+    MonthlyOneTimeCauses,
+    MonthlyInfiniteImpactCauses,
+    AnnualOneTimeCauses,
+    AnnualInfiniteImpactCauses,
+}
+
+impl CharityCategory {
+    /// Donation wallet of the config funding the category.
+    pub fn funding_wallet<'a>(&self, config: &'a mut Config) -> &'a mut Pubkey {
+        match self {
+            CharityCategory::MonthlyOneTimeCauses => &mut config.monthly_one_time_causes_wallet,
+            CharityCategory::MonthlyInfiniteImpactCauses => {
+                &mut config.monthly_infinite_impact_causes_wallet
+            }
+            CharityCategory::AnnualOneTimeCauses => &mut config.annual_one_time_causes_wallet,
+            CharityCategory::AnnualInfiniteImpactCauses => {
+                &mut config.annual_infinite_impact_causes_wallet
+            }
+        }
+    }
+
+    /// One-time causes are paid in milestone tranches, never at once.
+    pub fn is_one_time(&self) -> bool {
+        matches!(
+            self,
+            CharityCategory::MonthlyOneTimeCauses | CharityCategory::AnnualOneTimeCauses
+        )
+    }
 }
 
 #[account]
@@ -18,7 +53,192 @@ pub struct Charity {
     pub start_time: u64,     // Voting start time (unix timestamp)
     pub end_time: u64,       // Voting end time (unix timestamp)
     pub status: CharityStatus,
+    pub removal_reason: u8, // Reason code of a cancellation or veto
     pub admin: Pubkey, // Admin's public key for managing the charity
+    pub round: Pubkey, // Voting round the charity competes in, default if none
+    pub paid_out: bool, // Whether the round payout was made
+    pub category: CharityCategory,
+    pub description_uri: String,
+    pub website_uri: String,
+    pub image_uri: String,
+    pub registration_hash: [u8; 32], // Hash of the registration or tax id
+    pub country_code: [u8; 2],       // ISO 3166-1 alpha-2 country code
+    pub content_hash: [u8; 32],      // Hash of the off-chain profile content
+}
+
+/// Max length of a charity title in bytes.
+pub const MAX_CHARITY_TITLE_LEN: usize = 64;
+/// Size of the charity accounts registered before rounds, removal and profiles were added.
+pub const LEGACY_CHARITY_SPACE: usize = 8 + 8 + 4 + 64 + 32 + 8 + 8 + 8 + 2 + 32;
+
+/// Layout of the charity accounts of size `LEGACY_CHARITY_SPACE`.
+#[derive(AnchorDeserialize)]
+struct LegacyCharity {
+    id: u64,
+    title: String,
+    wallet: Pubkey,
+    total_votes: u64,
+    start_time: u64,
+    end_time: u64,
+    status: CharityStatus, // Active or Finalized, same variant indexes
+    admin: Pubkey,
+}
+/// Max length of a charity profile URI in bytes.
+pub const MAX_CHARITY_URI_LEN: usize = 200;
+
+/// Profile of a charity, set by the charity admin.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CharityProfile {
+    pub title: String,
+    pub description_uri: String,
+    pub website_uri: String,
+    pub image_uri: String,
+    pub registration_hash: [u8; 32],
+    pub country_code: [u8; 2],
+    pub content_hash: [u8; 32],
+}
+
+impl CharityProfile {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.title.len() <= MAX_CHARITY_TITLE_LEN,
+            CustomError::CharityTitleTooLong
+        );
+        require!(
+            [&self.description_uri, &self.website_uri, &self.image_uri]
+                .iter()
+                .all(|uri| uri.len() <= MAX_CHARITY_URI_LEN),
+            CustomError::CharityUriTooLong
+        );
+        require!(
+            self.country_code.iter().all(|c| c.is_ascii_uppercase()),
+            CustomError::InvalidCountryCode
+        );
+        Ok(())
+    }
+}
+
+impl Charity {
+    /// Sets up a new charity without voting window.
+    pub fn register(
+        &mut self,
+        id: u64,
+        title: String,
+        wallet: Pubkey,
+        category: CharityCategory,
+        admin: Pubkey,
+    ) -> Result<()> {
+        self.id = id;
+        self.title = title;
+        self.wallet = wallet;
+        self.total_votes = 0;
+        self.status = CharityStatus::Active;
+        self.removal_reason = 0;
+        self.admin = admin;
+        self.round = Pubkey::default();
+        self.paid_out = false;
+        self.category = category;
+        emit!(CharityProfileUpdatedEvent::new(self)?);
+        Ok(())
+    }
+
+    /// Whether the charity was cancelled or vetoed. Its votes no longer count and it
+    /// cannot be paid.
+    pub fn is_removed(&self) -> bool {
+        matches!(self.status, CharityStatus::Cancelled | CharityStatus::Vetoed)
+    }
+
+    /// Whether the charity can still be cancelled or vetoed: not removed yet, and not
+    /// paid or paid in milestones that are not all released.
+    pub fn is_removable(&self, milestone_plan: Option<&MilestonePlan>) -> bool {
+        !self.is_removed()
+            && (!self.paid_out || milestone_plan.is_some_and(|plan| plan.has_unreleased()))
+    }
+
+    /// Cancels or vetoes the charity with a reason code. Unreleased milestones of the
+    /// charity, or its unpaid round share, can then be reclaimed.
+    pub fn remove(
+        &mut self,
+        status: CharityStatus,
+        reason: u8,
+        milestone_plan: Option<&MilestonePlan>,
+    ) -> Result<()> {
+        require!(
+            self.is_removable(milestone_plan),
+            CustomError::CharityNotRemovable
+        );
+        self.status = status.clone();
+        self.removal_reason = reason;
+        emit!(CharityRemovedEvent {
+            charity_id: self.id,
+            status,
+            reason,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        });
+        Ok(())
+    }
+
+    /// Account size for the given title and profile URIs.
+    pub fn space(title: &str, description_uri: &str, website_uri: &str, image_uri: &str) -> usize {
+        8 +                             // discriminator
+        8 +                             // id
+        4 + title.len() +               // title (4 bytes for length prefix + actual string)
+        32 +                            // wallet
+        8 +                             // total_votes
+        8 +                             // start_time
+        8 +                             // end_time
+        2 +                             // status (Active,Finalized,Cancelled,Vetoed)
+        1 +                             // removal_reason
+        32 +                            // admin
+        32 +                            // round
+        1 +                             // paid_out
+        1 +                             // category
+        4 + description_uri.len() +     // description_uri
+        4 + website_uri.len() +         // website_uri
+        4 + image_uri.len() +           // image_uri
+        32 +                            // registration_hash
+        2 +                             // country_code
+        32                              // content_hash
+    }
+}
+
+#[event]
+pub struct CharityProfileUpdatedEvent {
+    pub charity_id: u64,
+    pub admin: Pubkey,
+    pub title: String,
+    pub description_uri: String,
+    pub website_uri: String,
+    pub image_uri: String,
+    pub registration_hash: [u8; 32],
+    pub country_code: [u8; 2],
+    pub content_hash: [u8; 32],
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CharityRemovedEvent {
+    pub charity_id: u64,
+    pub status: CharityStatus,
+    pub reason: u8,
+    pub timestamp: u64,
+}
+
+impl CharityProfileUpdatedEvent {
+    fn new(charity: &Charity) -> Result<Self> {
+        Ok(Self {
+            charity_id: charity.id,
+            admin: charity.admin,
+            title: charity.title.clone(),
+            description_uri: charity.description_uri.clone(),
+            website_uri: charity.website_uri.clone(),
+            image_uri: charity.image_uri.clone(),
+            registration_hash: charity.registration_hash,
+            country_code: charity.country_code,
+            content_hash: charity.content_hash,
+            timestamp: Clock::get()?.unix_timestamp as u64,
+        })
+    }
 }
 
 #[account]
@@ -36,6 +256,7 @@ pub fn register_charity(
     ctx: Context<RegisterCharity>,
     title: String,
     wallet: Pubkey,
+    category: CharityCategory,
     start_time: u64,
     end_time: u64,
 ) -> Result<()> {
@@ -48,16 +269,18 @@ pub fn register_charity(
     if wallet == Pubkey::default() {
         return Err(CustomError::InvalidArg.into());
     }
+    require!(title.len() <= MAX_CHARITY_TITLE_LEN, CustomError::CharityTitleTooLong);
 
     let charity = &mut ctx.accounts.charity;
-    charity.id = ctx.accounts.config_account.config.next_charity_id;
-    charity.title = title;
-    charity.wallet = wallet;
-    charity.total_votes = 0;
+    charity.register(
+        ctx.accounts.config_account.config.next_charity_id,
+        title,
+        wallet,
+        category,
+        ctx.accounts.registrar.key(),
+    )?;
     charity.start_time = start_time;
     charity.end_time = end_time;
-    charity.status = CharityStatus::Active;
-    charity.admin = ctx.accounts.registrar.key();
     msg!("Charity '{}' registered. Charity id '{}", charity.title,ctx.accounts.config_account.config.next_charity_id);
     ctx.accounts.config_account.config.next_charity_id += 1;
     Ok(())
@@ -73,6 +296,7 @@ pub fn cast_vote(ctx: Context<CastVote>, _charity_id: u64) -> Result<()> {
     let user = &mut ctx.accounts.user;
 
     require!(user.voting_power > 0, CustomError::VotingNotEligible);
+    require!(!charity.is_removed(), CustomError::CharityRemoved);
     
     let amount_staked = user.total_amount;
     if vote_record.voted {
@@ -142,7 +366,100 @@ pub fn finalize_charity_vote(ctx: Context<FinalizeCharityVote>, _charity_id: u64
     Ok(())
 }
 
+/// Cancels a charity, e.g. one found to be fraudulent during its vote. Its votes are
+/// excluded from the round tally.
+pub fn cancel_charity(ctx: Context<CancelCharity>, _charity_id: u64, reason: u8) -> Result<()> {
+    ctx.accounts.charity.remove(
+        CharityStatus::Cancelled,
+        reason,
+        ctx.accounts.milestone_plan.as_deref(),
+    )?;
+    msg!("Charity '{}' cancelled", ctx.accounts.charity.title);
+    Ok(())
+}
+
+/// Moves a charity account of the legacy layout to the current one. The account is
+/// resized, the admin pays the extra rent and picks the category the charity is funded by.
+pub fn migrate_charity(
+    ctx: Context<MigrateCharity>,
+    _charity_id: u64,
+    category: CharityCategory,
+) -> Result<()> {
+    let info = ctx.accounts.charity.to_account_info();
+    require!(
+        info.owner == &crate::ID && info.data_len() == LEGACY_CHARITY_SPACE,
+        CustomError::CharityNotLegacy
+    );
+    let legacy = {
+        let data = info.try_borrow_data()?;
+        require!(
+            data[..8] == *Charity::DISCRIMINATOR,
+            CustomError::CharityNotLegacy
+        );
+        LegacyCharity::deserialize(&mut &data[8..])?
+    };
+    let charity = Charity {
+        id: legacy.id,
+        title: legacy.title,
+        wallet: legacy.wallet,
+        total_votes: legacy.total_votes,
+        start_time: legacy.start_time,
+        end_time: legacy.end_time,
+        status: legacy.status,
+        removal_reason: 0,
+        admin: legacy.admin,
+        round: Pubkey::default(),
+        paid_out: false,
+        category,
+        description_uri: String::new(),
+        website_uri: String::new(),
+        image_uri: String::new(),
+        registration_hash: [0; 32],
+        country_code: [0; 2],
+        content_hash: [0; 32],
+    };
+
+    let space = Charity::space(&charity.title, "", "", "");
+    let rent = Rent::get()?.minimum_balance(space).saturating_sub(info.lamports());
+    if rent > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.admin.to_account_info(),
+                    to: info.clone(),
+                },
+            ),
+            rent,
+        )?;
+    }
+    info.resize(space)?;
+    charity.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    emit!(CharityProfileUpdatedEvent::new(&charity)?);
+    Ok(())
+}
+
+/// Replaces the profile of a charity, the account is resized to the new profile.
+pub fn update_charity_profile(
+    ctx: Context<UpdateCharityProfile>,
+    _charity_id: u64,
+    profile: CharityProfile,
+) -> Result<()> {
+    profile.validate()?;
+    let charity = &mut ctx.accounts.charity;
+    charity.title = profile.title;
+    charity.description_uri = profile.description_uri;
+    charity.website_uri = profile.website_uri;
+    charity.image_uri = profile.image_uri;
+    charity.registration_hash = profile.registration_hash;
+    charity.country_code = profile.country_code;
+    charity.content_hash = profile.content_hash;
+    emit!(CharityProfileUpdatedEvent::new(charity)?);
+    Ok(())
+}
+
 #[derive(Accounts)]
+#[instruction(title: String)]
 pub struct RegisterCharity<'info> {
     #[account(
         mut,
@@ -155,16 +472,7 @@ pub struct RegisterCharity<'info> {
         payer = admin,
         seeds=[b"charity".as_ref(),config_account.config.next_charity_id.to_le_bytes().as_ref()],
         bump, 
-        space = 
-            8 +                     // discriminator
-            8 +                     // id
-            4 + 64 +                // title (4 bytes for length prefix + actual string 64 bytes)
-            32 +                    // wallet
-            8 +                     // total_votes
-            8 +                     // start_time
-            8 +                     // end_time
-            2 +                     // status (Active,Finalized)
-            32                      // admin
+        space = Charity::space(&title, "", "", "")
     )]
     pub charity: Account<'info, Charity>,
     /// CHECK: registrar is the public key of admin of charity, CHAR admin can specify the registrar of charity.
@@ -239,3 +547,75 @@ pub struct FinalizeCharityVote<'info> {
     )]
     pub admin: Signer<'info>,
 }
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64)]
+pub struct CancelCharity<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Account<'info, Charity>,
+    /// Only required when the charity has a milestone plan.
+    #[account(
+        seeds = [b"milestone_plan".as_ref(), charity.key().as_ref()],
+        bump = milestone_plan.bump,
+    )]
+    pub milestone_plan: Option<Account<'info, MilestonePlan>>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64)]
+pub struct MigrateCharity<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    /// CHECK: legacy charity account, too short to deserialize as `Charity`.
+    #[account(
+        mut,
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64, profile: CharityProfile)]
+pub struct UpdateCharityProfile<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = charity.admin == charity_admin.key(),
+        realloc = Charity::space(&profile.title, &profile.description_uri, &profile.website_uri, &profile.image_uri),
+        realloc::payer = charity_admin,
+        realloc::zero = false,
+    )]
+    pub charity: Account<'info, Charity>,
+    #[account(mut)]
+    pub charity_admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
@@ -46,4 +46,166 @@ pub enum CustomError {
     MathError,
     #[msg("You Dont Have Enough Voting Power")]
     YouDontHaveEnoughVotingPower,
+    #[msg("Referral program is disabled")]
+    ReferralProgramDisabled,
+    #[msg("Referrer can only be set on the first stake")]
+    ReferralOnlyOnFirstStake,
+    #[msg("Users cannot refer themselves")]
+    SelfReferral,
+    #[msg("Invalid referrer")]
+    InvalidReferrer,
+    #[msg("Referral accounts are missing")]
+    ReferrerAccountsMissing,
+    #[msg("All extra reward slots are used")]
+    ExtraRewardSlotsFull,
+    #[msg("Extra reward mint already added")]
+    ExtraRewardAlreadyAdded,
+    #[msg("Extra reward has not ended yet")]
+    ExtraRewardNotEnded,
+    #[msg("Extra reward accounts are missing")]
+    ExtraRewardAccountsMissing,
+    #[msg("Invalid extra reward account")]
+    InvalidExtraRewardAccount,
+    #[msg("Token program does not own the mint")]
+    TokenProgramMismatch,
+    #[msg("No fees collected since the last release")]
+    NoFeesToRelease,
+    #[msg("Treasury token account is empty")]
+    TreasuryEmpty,
+    #[msg("Distribution splits must add up to 1000 at every level")]
+    InvalidDistributionSplit,
+    #[msg("Release streams must be at the same point to settle a fee epoch")]
+    FeeReleaseOutOfSync,
+    #[msg("Treasury balance too low, harvest the withheld fees first")]
+    TreasuryBalanceTooLow,
+    #[msg("Release schedule has not started yet")]
+    ReleaseScheduleNotStarted,
+    #[msg("Already released in the current period")]
+    ReleasePeriodNotElapsed,
+    #[msg("Invalid release period")]
+    InvalidReleasePeriod,
+    #[msg("Invalid release classification")]
+    InvalidClassification,
+    #[msg("Treasury outflow limit exceeded")]
+    OutflowLimitExceeded,
+    #[msg("Daily outflow limit can't be above the monthly limit")]
+    InvalidOutflowLimits,
+    #[msg("No outflow limits are pending")]
+    NoPendingOutflowLimits,
+    #[msg("Raised outflow limits are still timelocked")]
+    OutflowLimitsTimelocked,
+    #[msg("Account is not a marketing wallet")]
+    InvalidMarketingWallet,
+    #[msg("Vesting duration must be positive")]
+    InvalidVestingDuration,
+    #[msg("Nothing vested to withdraw")]
+    NothingVested,
+    #[msg("Buyback received less than the minimum amount")]
+    SlippageExceeded,
+    #[msg("Death wallet balance and interval are below the buyback thresholds")]
+    BuybackNotDue,
+    #[msg("Keeper tip is too high")]
+    InvalidKeeperTip,
+    #[msg("Too many wallets excluded from the circulating supply")]
+    TooManySupplyExclusions,
+    #[msg("Token account is not held by an excluded wallet or is counted twice")]
+    InvalidSupplyExclusion,
+    #[msg("Invalid burn bounds or target supply curve")]
+    InvalidBurnPolicy,
+    #[msg("Invalid voting window or winner count")]
+    InvalidRoundConfig,
+    #[msg("Voting round is closed")]
+    RoundClosed,
+    #[msg("Voting round has the maximum number of charities")]
+    RoundFull,
+    #[msg("Charity is already in a round, finalized or has votes")]
+    CharityNotEligibleForRound,
+    #[msg("Charity accounts do not match the charities of the round")]
+    InvalidRoundCharity,
+    #[msg("Voting round is not finalized")]
+    RoundNotFinalized,
+    #[msg("Charity is not a winner of the round")]
+    CharityNotWinner,
+    #[msg("Charity was already paid")]
+    CharityAlreadyPaid,
+    #[msg("Governance windows, thresholds, quorum and durations must be positive")]
+    InvalidGovernanceConfig,
+    #[msg("Milestones must add up to the round payout and have deadlines within the max duration")]
+    InvalidMilestones,
+    #[msg("Invalid milestone index")]
+    InvalidMilestoneIndex,
+    #[msg("Milestone proof was already submitted")]
+    MilestoneNotPending,
+    #[msg("Milestone deadline has passed")]
+    MilestoneDeadlinePassed,
+    #[msg("Milestone has no proof awaiting a decision")]
+    MilestoneNotSubmitted,
+    #[msg("Objection window of the milestone is closed")]
+    ObjectionWindowClosed,
+    #[msg("Milestone is not approved or still in its objection window")]
+    MilestoneNotReleasable,
+    #[msg("Milestone cannot be reclaimed")]
+    MilestoneNotReclaimable,
+    #[msg("Charity category does not match the round")]
+    CharityCategoryMismatch,
+    #[msg("Charity title is too long")]
+    CharityTitleTooLong,
+    #[msg("Charity profile URI is too long")]
+    CharityUriTooLong,
+    #[msg("Country code must be two uppercase letters")]
+    InvalidCountryCode,
+    #[msg("Invalid nomination bond, endorsement threshold or period")]
+    InvalidNominationConfig,
+    #[msg("Nomination phase is closed")]
+    NominationClosed,
+    #[msg("Nomination phase has not ended")]
+    NominationPhaseNotEnded,
+    #[msg("Nomination was already resolved")]
+    NominationAlreadyResolved,
+    #[msg("Nomination did not reach the endorsement threshold")]
+    EndorsementThresholdNotReached,
+    #[msg("Nomination reached the endorsement threshold")]
+    EndorsementThresholdReached,
+    #[msg("Verifier is not active")]
+    VerifierInactive,
+    #[msg("Invalid attestation wallet or expiry")]
+    InvalidAttestation,
+    #[msg("Charity has no valid attestation")]
+    CharityNotAttested,
+    #[msg("Charity was cancelled or vetoed")]
+    CharityRemoved,
+    #[msg("Charity is already removed or paid in full")]
+    CharityNotRemovable,
+    #[msg("Claims of the removed extra reward campaign must be settled first")]
+    ExtraRewardNotSettled,
+    #[msg("Period anchor cannot move past the last release")]
+    ReleaseAnchorAfterLastRelease,
+    #[msg("No AMM config change is pending")]
+    NoPendingAmmConfig,
+    #[msg("AMM config change is still timelocked")]
+    AmmConfigTimelocked,
+    #[msg("AMM spent more than the swap amount or changed a swap account")]
+    SwapAccountTampered,
+    #[msg("Charity account is not in the legacy layout")]
+    CharityNotLegacy,
+    #[msg("Round allocation exceeds the uncommitted balance of the donation vault")]
+    AllocationExceedsVault,
+    #[msg("Veto voting window has ended")]
+    VetoVotingClosed,
+    #[msg("Only the unpaid share of a removed round winner can be reclaimed")]
+    CharityPayoutNotReclaimable,
+    #[msg("One-time causes are paid through a milestone plan")]
+    MilestonePlanRequired,
+    #[msg("Votes on this milestone proof are closed")]
+    MilestoneVotingClosed,
+    #[msg("The stake is locked until the governance votes it counted in close")]
+    StakeLockedByVote,
+    #[msg("Fees are settled by fee epoch, the per stream releases are closed")]
+    FeesSettledByEpoch,
+    #[msg("The admin can still accept the nomination")]
+    NominationAcceptancePending,
+    #[msg("Only the verifier that issued the attestation or the admin can renew it")]
+    AttestationVerifierMismatch,
+    #[msg("Staking info is not in the legacy layout")]
+    UserStakeInfoNotLegacy,
 }
@@ -0,0 +1,495 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{ConfigAccount, CustomError, StakingPool, UserStakesEntry};
+
+/// Maximum number of partner reward mints a staking pool can distribute next to CHAR.
+pub const MAX_EXTRA_REWARD_MINTS: usize = 3;
+/// Precision of `ExtraReward::emission_rate`.
+pub const EMISSION_RATE_PRECISION: u128 = 1_000_000_000;
+const ONE_DAY_IN_SECONDS: u128 = 86400;
+
+#[event]
+pub struct ExtraRewardAddedEvent {
+    pub index: u8,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub emission_rate: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+#[event]
+pub struct ExtraRewardRemovedEvent {
+    pub index: u8,
+    pub mint: Pubkey,
+    pub start_time: u64,
+    pub ended_extra_reward: Pubkey,
+}
+
+#[event]
+pub struct ExtraRewardClaimedEvent {
+    pub user: Pubkey,
+    pub stake_id: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Partner token co-sponsoring the staking pool.
+#[derive(Clone, Copy, Debug, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct ExtraReward {
+    pub mint: Pubkey, // default when the slot is empty
+    pub vault: Pubkey,
+    // reward token units paid per staked CHAR unit per day, scaled by EMISSION_RATE_PRECISION
+    pub emission_rate: u64,
+    pub start_time: u64,
+    pub end_time: u64, // 0 = no end
+    pub total_issued: u64,
+}
+
+impl ExtraReward {
+    pub fn is_active(&self) -> bool {
+        self.mint != Pubkey::default()
+    }
+
+    /// Total amount a stake has earned from this reward since it was started.
+    pub fn earned(&self, amount: u64, staked_at: u64, stake_end: u64) -> Result<u64> {
+        let from = staked_at.max(self.start_time);
+        let to = if self.end_time == 0 {
+            stake_end
+        } else {
+            stake_end.min(self.end_time)
+        };
+        let elapsed = to.saturating_sub(from) as u128;
+        let earned = (amount as u128)
+            .checked_mul(self.emission_rate as u128)
+            .and_then(|x| x.checked_mul(elapsed))
+            .ok_or(CustomError::MathError)?
+            / (EMISSION_RATE_PRECISION * ONE_DAY_IN_SECONDS);
+        u64::try_from(earned).map_err(|_| CustomError::MathError.into())
+    }
+}
+
+/// Per stake accounting of an extra reward.
+#[derive(Clone, Copy, Debug, Default, AnchorSerialize, AnchorDeserialize)]
+pub struct ExtraRewardCheckpoint {
+    pub mint: Pubkey,
+    pub start_time: u64, // identifies the reward campaign the claimed amount belongs to
+    pub claimed: u64,
+}
+
+/// Campaign removed from its pool slot. Stakes claim what they earned before its end from here.
+#[account]
+pub struct EndedExtraReward {
+    pub staking_pool: Pubkey,
+    pub index: u8, // pool slot the campaign was removed from
+    pub reward: ExtraReward,
+    pub bump: u8,
+}
+
+/// Marks the ended campaign reward of a stake as settled.
+#[account]
+pub struct ExtraRewardReceipt {
+    pub amount: u64,
+}
+
+/// Pays every extra reward a stake has earned.
+/// `remaining_accounts` must hold
+/// `[reward_mint, reward_vault, user_reward_token_account, reward_token_program]`
+/// for each active extra reward, in slot order.
+pub fn claim_extra_rewards<'info>(
+    staking_pool: &mut Account<'info, StakingPool>,
+    user_stake: &mut UserStakesEntry,
+    user_authority: Pubkey,
+    stake_end: u64,
+    remaining_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let token_mint = staking_pool.token_mint;
+    let bump = [staking_pool.bump];
+    let pool_seeds = &[b"staking_pool".as_ref(), token_mint.as_ref(), &bump];
+    let signer = &[&pool_seeds[..]];
+
+    let mut accounts = remaining_accounts.chunks(4);
+    for index in 0..MAX_EXTRA_REWARD_MINTS {
+        let reward = staking_pool.extra_rewards[index];
+        if !reward.is_active() {
+            continue;
+        }
+        let [mint_info, vault_info, destination_info, token_program] = accounts
+            .next()
+            .ok_or(CustomError::ExtraRewardAccountsMissing)?
+        else {
+            return Err(CustomError::ExtraRewardAccountsMissing.into());
+        };
+        require!(
+            mint_info.key() == reward.mint && vault_info.key() == reward.vault,
+            CustomError::InvalidExtraRewardAccount
+        );
+        // partner mints can live on either the SPL Token or the Token-2022 program
+        require!(
+            mint_info.owner == token_program.key,
+            CustomError::TokenProgramMismatch
+        );
+        let mint = InterfaceAccount::<Mint>::try_from(mint_info)?;
+        let vault = InterfaceAccount::<TokenAccount>::try_from(vault_info)?;
+        let destination = InterfaceAccount::<TokenAccount>::try_from(destination_info)?;
+        require!(
+            destination.mint == reward.mint && destination.owner == user_authority,
+            CustomError::InvalidExtraRewardAccount
+        );
+
+        // reset the checkpoint when the slot holds a new reward campaign, the claims of
+        // a removed campaign must be settled with `claim_ended_extra_reward` first
+        let checkpoint = &mut user_stake.extra_rewards[index];
+        if checkpoint.mint != reward.mint || checkpoint.start_time != reward.start_time {
+            require!(checkpoint.claimed == 0, CustomError::ExtraRewardNotSettled);
+            *checkpoint = ExtraRewardCheckpoint {
+                mint: reward.mint,
+                start_time: reward.start_time,
+                claimed: 0,
+            };
+        }
+
+        let earned = reward.earned(user_stake.amount, user_stake.staked_at, stake_end)?;
+        // pay what the vault can cover, the rest stays claimable
+        let amount = earned.saturating_sub(checkpoint.claimed).min(vault.amount);
+        if amount == 0 {
+            continue;
+        }
+
+        let cpi_accounts = TransferChecked {
+            from: vault.to_account_info(),
+            to: destination.to_account_info(),
+            authority: staking_pool.to_account_info(),
+            mint: mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer);
+        transfer_checked(cpi_ctx, amount, mint.decimals)?;
+
+        checkpoint.claimed += amount;
+        staking_pool.extra_rewards[index].total_issued += amount;
+        emit!(ExtraRewardClaimedEvent {
+            user: user_authority,
+            stake_id: user_stake.stake_id,
+            mint: reward.mint,
+            amount,
+        });
+    }
+    Ok(())
+}
+
+pub fn add_extra_reward(
+    ctx: Context<AddExtraReward>,
+    emission_rate: u64,
+    start_time: u64,
+    end_time: u64,
+) -> Result<()> {
+    let staking_pool = &mut ctx.accounts.staking_pool;
+    let reward_mint = ctx.accounts.reward_mint.key();
+    require!(emission_rate > 0, CustomError::InvalidArg);
+    require!(
+        !staking_pool.extra_rewards.iter().any(|x| x.mint == reward_mint),
+        CustomError::ExtraRewardAlreadyAdded
+    );
+    let index = staking_pool
+        .extra_rewards
+        .iter()
+        .position(|x| !x.is_active())
+        .ok_or(CustomError::ExtraRewardSlotsFull)?;
+
+    let start_time = start_time.max(Clock::get()?.unix_timestamp as u64);
+    require!(
+        end_time == 0 || end_time > start_time,
+        CustomError::InvalidArg
+    );
+    staking_pool.extra_rewards[index] = ExtraReward {
+        mint: reward_mint,
+        vault: ctx.accounts.reward_vault.key(),
+        emission_rate,
+        start_time,
+        end_time,
+        total_issued: 0,
+    };
+
+    emit!(ExtraRewardAddedEvent {
+        index: index as u8,
+        mint: reward_mint,
+        vault: ctx.accounts.reward_vault.key(),
+        emission_rate,
+        start_time,
+        end_time,
+    });
+    Ok(())
+}
+
+/// Ends an extra reward campaign. Stakes keep what they earned until `end_time`.
+pub fn end_extra_reward(ctx: Context<UpdateExtraReward>, index: u8, end_time: u64) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp as u64;
+    let reward = ctx
+        .accounts
+        .staking_pool
+        .extra_rewards
+        .get_mut(index as usize)
+        .ok_or(CustomError::InvalidArg)?;
+    require!(reward.is_active(), CustomError::InvalidArg);
+    require!(
+        end_time >= now && (reward.end_time == 0 || end_time < reward.end_time),
+        CustomError::InvalidArg
+    );
+    reward.end_time = end_time;
+    Ok(())
+}
+
+/// Frees the slot of an extra reward campaign that has ended.
+/// The campaign moves to an `EndedExtraReward` account so stakes keep what they earned.
+pub fn remove_extra_reward(ctx: Context<RemoveExtraReward>, index: u8) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp as u64;
+    let staking_pool_key = ctx.accounts.staking_pool.key();
+    let reward = ctx
+        .accounts
+        .staking_pool
+        .extra_rewards
+        .get_mut(index as usize)
+        .ok_or(CustomError::InvalidArg)?;
+    require!(
+        reward.end_time != 0 && now > reward.end_time,
+        CustomError::ExtraRewardNotEnded
+    );
+    require!(
+        ctx.accounts.reward_mint.key() == reward.mint,
+        CustomError::InvalidExtraRewardAccount
+    );
+
+    let ended_extra_reward = &mut ctx.accounts.ended_extra_reward;
+    ended_extra_reward.staking_pool = staking_pool_key;
+    ended_extra_reward.index = index;
+    ended_extra_reward.reward = *reward;
+    ended_extra_reward.bump = ctx.bumps.ended_extra_reward;
+    *reward = ExtraReward::default();
+
+    emit!(ExtraRewardRemovedEvent {
+        index,
+        mint: ended_extra_reward.reward.mint,
+        start_time: ended_extra_reward.reward.start_time,
+        ended_extra_reward: ended_extra_reward.key(),
+    });
+    Ok(())
+}
+
+/// Settles what a stake earned from a removed extra reward campaign. Each stake settles once.
+pub fn claim_ended_extra_reward(ctx: Context<ClaimEndedExtraReward>, _stake_id: u64) -> Result<()> {
+    let reward = ctx.accounts.ended_extra_reward.reward;
+    let index = ctx.accounts.ended_extra_reward.index as usize;
+    let user_stake = &mut ctx.accounts.user_stake;
+    let stake_end = if user_stake.unstaked_at == 0 {
+        Clock::get()?.unix_timestamp as u64
+    } else {
+        user_stake.unstaked_at
+    };
+
+    // the checkpoint only holds claims of the campaign while it still refers to it,
+    // any other checkpoint was reset with nothing claimed from this campaign
+    let earned = reward.earned(user_stake.amount, user_stake.staked_at, stake_end)?;
+    let checkpoint = &mut user_stake.extra_rewards[index];
+    let mut claimed = 0;
+    if checkpoint.mint == reward.mint && checkpoint.start_time == reward.start_time {
+        claimed = checkpoint.claimed;
+        *checkpoint = ExtraRewardCheckpoint::default();
+    }
+    let amount = earned.saturating_sub(claimed);
+    require!(
+        ctx.accounts.reward_vault.amount >= amount,
+        CustomError::StakingRewardInsufficientBalance
+    );
+    ctx.accounts.receipt.amount = amount;
+
+    if amount > 0 {
+        let token_mint = ctx.accounts.staking_pool.token_mint;
+        let bump = [ctx.accounts.staking_pool.bump];
+        let pool_seeds = &[b"staking_pool".as_ref(), token_mint.as_ref(), &bump];
+        let signer = &[&pool_seeds[..]];
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.user_reward_token_account.to_account_info(),
+            authority: ctx.accounts.staking_pool.to_account_info(),
+            mint: ctx.accounts.reward_mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        transfer_checked(cpi_ctx, amount, ctx.accounts.reward_mint.decimals)?;
+        ctx.accounts.ended_extra_reward.reward.total_issued += amount;
+    }
+
+    emit!(ExtraRewardClaimedEvent {
+        user: ctx.accounts.user_authority.key(),
+        stake_id: user_stake.stake_id,
+        mint: reward.mint,
+        amount,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AddExtraReward<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"staking_pool".as_ref(), staking_pool.token_mint.as_ref()],
+        bump = staking_pool.bump,
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+    #[account(
+        constraint = reward_mint.key() != staking_pool.token_mint @ CustomError::InvalidArg,
+        constraint = *reward_mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    /// Vault holding the partner tokens, anyone can fund it.
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"reward_vault".as_ref(), staking_pool.key().as_ref(), reward_mint.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = staking_pool,
+        token::token_program = token_program,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateExtraReward<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"staking_pool".as_ref(), staking_pool.token_mint.as_ref()],
+        bump = staking_pool.bump,
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveExtraReward<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"staking_pool".as_ref(), staking_pool.token_mint.as_ref()],
+        bump = staking_pool.bump,
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<EndedExtraReward>(),
+        seeds = [
+            b"ended_extra_reward".as_ref(),
+            staking_pool.key().as_ref(),
+            reward_mint.key().as_ref(),
+            ended_start_time(&staking_pool, &reward_mint.key()).to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub ended_extra_reward: Account<'info, EndedExtraReward>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Start time of the campaign of `mint`, seeds the account it is removed to.
+fn ended_start_time(staking_pool: &StakingPool, mint: &Pubkey) -> u64 {
+    staking_pool
+        .extra_rewards
+        .iter()
+        .find(|x| x.mint == *mint)
+        .map(|x| x.start_time)
+        .unwrap_or(0)
+}
+
+#[derive(Accounts)]
+#[instruction(stake_id:u64)]
+pub struct ClaimEndedExtraReward<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds = [b"staking_pool".as_ref(), staking_pool.token_mint.as_ref()],
+        bump = staking_pool.bump,
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+    #[account(
+        mut,
+        constraint = ended_extra_reward.staking_pool == staking_pool.key() @ CustomError::InvalidExtraRewardAccount
+    )]
+    pub ended_extra_reward: Account<'info, EndedExtraReward>,
+    #[account(
+        mut,
+        seeds = [b"user_stake".as_ref(), user_authority.key().as_ref(), stake_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub user_stake: Account<'info, UserStakesEntry>,
+    #[account(
+        init,
+        payer = user_authority,
+        space = 8 + std::mem::size_of::<ExtraRewardReceipt>(),
+        seeds = [b"extra_reward_receipt".as_ref(), ended_extra_reward.key().as_ref(), user_stake.key().as_ref()],
+        bump
+    )]
+    pub receipt: Account<'info, ExtraRewardReceipt>,
+    #[account(mut)]
+    pub user_authority: Signer<'info>,
+    #[account(
+        constraint = reward_mint.key() == ended_extra_reward.reward.mint @ CustomError::InvalidExtraRewardAccount,
+        constraint = *reward_mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        address = ended_extra_reward.reward.vault @ CustomError::InvalidExtraRewardAccount
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_reward_token_account.mint == reward_mint.key() @ CustomError::InvalidExtraRewardAccount,
+        constraint = user_reward_token_account.owner == user_authority.key() @ CustomError::InvalidExtraRewardAccount
+    )]
+    pub user_reward_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,497 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{burn, Burn, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use charcoin_fee_hook::FeeAccumulator;
+
+use crate::burn_policy::split_marketing;
+use crate::distribution::share;
+use crate::transfer::transfer_checked_with_hook;
+use crate::{
+    BurnLedger, BurnPolicy, BurnSource, BuybackBurnEvent, Classification, ConfigAccount, CustomError, DistributionConfig,
+    DistributionSplits, FeeRelease, MarketingVesting, OutflowLimiter, ReleaseSchedule, StakingPool,
+    Treasury,
+};
+
+/// Number of destinations paid by a fee epoch settlement.
+pub const FEE_EPOCH_PAYOUTS: usize = 12;
+
+/// Amount paid to one destination token account.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct EpochPayout {
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+/// Running state of the fee epoch settlements.
+#[account]
+pub struct FeeEpochState {
+    pub epoch_count: u64,   // number of settled epochs, id of the next epoch
+    pub carried_dust: u64,  // rounding dust carried into the next epoch
+    pub total_settled: u64, // total amount paid out by all settlements
+    pub last_settled_at: i64,
+    pub bump: u8,
+}
+
+/// Immutable record of one settlement. Annual shares are paid once per annual period,
+/// in between they are added to the reserves of the release schedule. Payouts are in order: staking rewards,
+/// monthly top tier, monthly charity lottery, annual top tier, annual charity lottery,
+/// monthly one time causes, monthly infinite impact causes, annual one time causes,
+/// annual infinite impact causes, char funds, marketing wallet 1 escrow and marketing wallet 2
+/// escrow. The death wallet share is burned, see `BurnPolicy` for the burn share.
+#[account]
+pub struct FeeEpoch {
+    pub id: u64,
+    pub collected: u64,    // fees collected since the previous settlement
+    pub carried_in: u64,   // dust carried from the previous epoch
+    pub distributed: u64,  // sum of the payouts
+    pub carried_out: u64,  // dust carried into the next epoch
+    pub reserved: u64,     // annual shares added to the annual reserves
+    pub reserve_paid: u64, // annual reserves paid out, included in the payouts
+    pub burned: u64,       // death wallet share burned from the vault
+    pub splits: DistributionSplits,
+    pub payouts: [EpochPayout; FEE_EPOCH_PAYOUTS],
+    pub settled_at: i64,
+    pub bump: u8,
+}
+
+#[event]
+pub struct FeeEpochSettledEvent {
+    pub epoch_id: u64,
+    pub collected: u64,
+    pub distributed: u64,
+    pub burned: u64,
+    pub carried_dust: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct SettleFeeEpoch<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        init_if_needed,
+        payer = treasury_authority,
+        space = 8 + std::mem::size_of::<FeeEpochState>(),
+        seeds = [b"fee_epoch_state".as_ref()],
+        bump
+    )]
+    pub fee_epoch_state: Box<Account<'info, FeeEpochState>>,
+    #[account(
+        init,
+        payer = treasury_authority,
+        space = 8 + std::mem::size_of::<FeeEpoch>(),
+        seeds = [b"fee_epoch".as_ref(), fee_epoch_state.epoch_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub fee_epoch: Box<Account<'info, FeeEpoch>>,
+    /// Fees recorded by the transfer hook.
+    #[account(constraint = fee_accumulator.mint == config_account.config.char_token_mint)]
+    pub fee_accumulator: Box<Account<'info, FeeAccumulator>>,
+    #[account(
+        mut,
+        seeds = [b"fee_release".as_ref()],
+        bump = fee_release.bump,
+    )]
+    pub fee_release: Box<Account<'info, FeeRelease>>,
+    #[account(
+        seeds = [b"distribution_config".as_ref()],
+        bump = distribution_config.bump,
+    )]
+    pub distribution_config: Box<Account<'info, DistributionConfig>>,
+    #[account(
+        mut,
+        seeds = [b"release_schedule".as_ref()],
+        bump = release_schedule.bump,
+    )]
+    pub release_schedule: Box<Account<'info, ReleaseSchedule>>,
+    #[account(
+        mut,
+        seeds = [b"outflow_limiter".as_ref()],
+        bump = outflow_limiter.bump,
+    )]
+    pub outflow_limiter: Box<Account<'info, OutflowLimiter>>,
+    #[account(
+        mut,
+        seeds = [b"burn_ledger".as_ref()],
+        bump = burn_ledger.bump,
+    )]
+    pub burn_ledger: Box<Account<'info, BurnLedger>>,
+    #[account(
+        seeds = [b"burn_policy".as_ref()],
+        bump = burn_policy.bump,
+    )]
+    pub burn_policy: Box<Account<'info, BurnPolicy>>,
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+    ///  Treasury vault holding funds to be distributed.
+    #[account(
+        mut,
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump = treasury.vault_bump,
+    )]
+    pub treasury_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        seeds = [b"staking_pool".as_ref(), mint.key().as_ref()],
+        bump = staking_pool.bump,
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+    #[account(
+        mut,
+        constraint = staking_reward_ata.mint == config_account.config.char_token_mint,
+        constraint = staking_reward_ata.owner == staking_pool.staking_reward_account
+    )]
+    pub staking_reward_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = monthly_top_tier_ata.mint == config_account.config.char_token_mint,
+        constraint = monthly_top_tier_ata.owner == config_account.config.monthly_top_tier_wallet
+    )]
+    pub monthly_top_tier_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = monthly_charity_lottery_ata.mint == config_account.config.char_token_mint,
+        constraint = monthly_charity_lottery_ata.owner == config_account.config.monthly_charity_lottery_wallet
+    )]
+    pub monthly_charity_lottery_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = annual_top_tier_ata.mint == config_account.config.char_token_mint,
+        constraint = annual_top_tier_ata.owner == config_account.config.annual_top_tier_wallet
+    )]
+    pub annual_top_tier_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = annual_charity_lottery_ata.mint == config_account.config.char_token_mint,
+        constraint = annual_charity_lottery_ata.owner == config_account.config.annual_charity_lottery_wallet
+    )]
+    pub annual_charity_lottery_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = monthly_one_time_causes_ata.mint == config_account.config.char_token_mint,
+        constraint = monthly_one_time_causes_ata.owner == config_account.config.monthly_one_time_causes_wallet
+    )]
+    pub monthly_one_time_causes_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = monthly_infinite_impact_causes_ata.mint == config_account.config.char_token_mint,
+        constraint = monthly_infinite_impact_causes_ata.owner == config_account.config.monthly_infinite_impact_causes_wallet
+    )]
+    pub monthly_infinite_impact_causes_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = annual_one_time_causes_ata.mint == config_account.config.char_token_mint,
+        constraint = annual_one_time_causes_ata.owner == config_account.config.annual_one_time_causes_wallet
+    )]
+    pub annual_one_time_causes_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = annual_infinite_impact_causes_ata.mint == config_account.config.char_token_mint,
+        constraint = annual_infinite_impact_causes_ata.owner == config_account.config.annual_infinite_impact_causes_wallet
+    )]
+    pub annual_infinite_impact_causes_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = char_funds_ata.mint == config_account.config.char_token_mint,
+        constraint = char_funds_ata.owner == config_account.config.char_funds
+    )]
+    pub char_funds_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"marketing_vesting".as_ref(), config_account.config.marketing_wallet_1.as_ref()],
+        bump = marketing_vesting_1.bump,
+    )]
+    pub marketing_vesting_1: Box<Account<'info, MarketingVesting>>,
+    #[account(mut, address = marketing_vesting_1.escrow)]
+    pub marketing_escrow_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [b"marketing_vesting".as_ref(), config_account.config.marketing_wallet_2.as_ref()],
+        bump = marketing_vesting_2.bump,
+    )]
+    pub marketing_vesting_2: Box<Account<'info, MarketingVesting>>,
+    #[account(mut, address = marketing_vesting_2.escrow)]
+    pub marketing_escrow_2: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Authority allowed to trigger treasury releases.
+    #[account(
+        mut,
+        constraint = config_account.config.treasury_authority == treasury_authority.key()
+    )]
+    pub treasury_authority: Signer<'info>,
+    #[account(mut,
+        constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Settles every fee collected since the previous settlement in one go: runs the whole
+/// split tree of the distribution config, pays every destination from the treasury vault,
+/// carries the rounding dust into the next epoch and writes an immutable `FeeEpoch` record.
+/// The collected amount comes from the fee accumulator and the release cursors are moved
+/// with it, so a batch of fees can't be released twice. From the first settlement on, the
+/// per stream releases are closed and fees are only settled by epoch.
+pub fn settle_fee_epoch<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SettleFeeEpoch<'info>>,
+) -> Result<()> {
+    let total_fees = ctx.accounts.fee_accumulator.total_fees;
+
+    // Fees can only be settled by epoch once every release stream is at the same point
+    let fee_release = &mut ctx.accounts.fee_release;
+    let released = fee_release.rewards_released;
+    require!(
+        fee_release.donations_released == released
+            && fee_release.staking_released == released
+            && fee_release.marketing_released == released,
+        CustomError::FeeReleaseOutOfSync
+    );
+    let collected = total_fees
+        .checked_sub(released)
+        .ok_or(CustomError::MathError)?;
+    fee_release.rewards_released = total_fees;
+    fee_release.donations_released = total_fees;
+    fee_release.staking_released = total_fees;
+    fee_release.marketing_released = total_fees;
+    fee_release.settled_by_epoch = true;
+
+    let fee_epoch_state = &mut ctx.accounts.fee_epoch_state;
+    let carried_in = fee_epoch_state.carried_dust;
+    let total = collected
+        .checked_add(carried_in)
+        .ok_or(CustomError::MathError)?;
+    require!(total > 0, CustomError::NoFeesToRelease);
+    require!(
+        ctx.accounts.treasury_vault.amount >= total,
+        CustomError::TreasuryBalanceTooLow
+    );
+
+    // Run the split tree
+    let splits = ctx.accounts.distribution_config.splits;
+    let staking = share(total, splits.staking)?;
+    let donation_total = share(total, splits.donation_ecosystem)?;
+    let marketing = share(total, splits.marketing)?;
+
+    let reward_system = share(donation_total, splits.reward_system)?;
+    let monthly_rewards = share(reward_system, splits.monthly_rewards)?;
+    let annual_rewards = share(reward_system, splits.annual_rewards)?;
+
+    let donation_system = share(donation_total, splits.donation_system)?;
+    let monthly_donations = share(donation_system, splits.monthly_donations)?;
+    let annual_donations = share(donation_system, splits.annual_donations)?;
+
+    let annual_reward_shares = [
+        share(annual_rewards, splits.top_tier)?,
+        share(annual_rewards, splits.charity_lottery)?,
+    ];
+    let annual_donation_shares = [
+        share(annual_donations, splits.one_time_causes)?,
+        share(annual_donations, splits.infinite_impact_causes)?,
+    ];
+    let reserved = annual_reward_shares
+        .iter()
+        .chain(annual_donation_shares.iter())
+        .sum::<u64>();
+
+    // Monthly payouts once per monthly period, annual shares wait in the vault for the annual release
+    let now = Clock::get()?.unix_timestamp;
+    let release_schedule = &mut ctx.accounts.release_schedule;
+    release_schedule.record_release(Classification::MonthlyRewards, now)?;
+    release_schedule.record_release(Classification::MonthlyDonations, now)?;
+    let annual_reward_payouts =
+        release_schedule.accrue_annual(Classification::AnnualRewards, annual_reward_shares, now)?;
+    let annual_donation_payouts = release_schedule.accrue_annual(
+        Classification::AnnualDonations,
+        annual_donation_shares,
+        now,
+    )?;
+    let reserve_paid = annual_reward_payouts
+        .iter()
+        .chain(annual_donation_payouts.iter())
+        .sum::<u64>();
+
+    let (marketing_wallet_1, marketing_wallet_2, burned) = split_marketing(
+        marketing,
+        &splits,
+        &ctx.accounts.burn_policy,
+        ctx.accounts.mint.supply,
+        now,
+    )?;
+
+    let a = &ctx.accounts;
+    let payouts: [(AccountInfo<'info>, u64); FEE_EPOCH_PAYOUTS] = [
+        (a.staking_reward_ata.to_account_info(), staking),
+        (
+            a.monthly_top_tier_ata.to_account_info(),
+            share(monthly_rewards, splits.top_tier)?,
+        ),
+        (
+            a.monthly_charity_lottery_ata.to_account_info(),
+            share(monthly_rewards, splits.charity_lottery)?,
+        ),
+        (
+            a.annual_top_tier_ata.to_account_info(),
+            annual_reward_payouts[0],
+        ),
+        (
+            a.annual_charity_lottery_ata.to_account_info(),
+            annual_reward_payouts[1],
+        ),
+        (
+            a.monthly_one_time_causes_ata.to_account_info(),
+            share(monthly_donations, splits.one_time_causes)?,
+        ),
+        (
+            a.monthly_infinite_impact_causes_ata.to_account_info(),
+            share(monthly_donations, splits.infinite_impact_causes)?,
+        ),
+        (
+            a.annual_one_time_causes_ata.to_account_info(),
+            annual_donation_payouts[0],
+        ),
+        (
+            a.annual_infinite_impact_causes_ata.to_account_info(),
+            annual_donation_payouts[1],
+        ),
+        (
+            a.char_funds_ata.to_account_info(),
+            share(donation_system, splits.char_fund)?,
+        ),
+        (
+            a.marketing_escrow_1.to_account_info(),
+            marketing_wallet_1,
+        ),
+        (
+            a.marketing_escrow_2.to_account_info(),
+            marketing_wallet_2,
+        ),
+    ];
+
+    // Every treasury outflow counts against the outflow limits
+    let outflow = payouts.iter().map(|(_, amount)| amount).sum::<u64>() + burned;
+    ctx.accounts.outflow_limiter.record_outflow(outflow, now)?;
+
+    let treasury_seeds: &[&[u8]] = &[b"treasury", &[ctx.accounts.treasury.bump]];
+    let signer = &[treasury_seeds];
+
+    // The death wallet share is burned straight from the treasury vault
+    if burned > 0 {
+        burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.mint.to_account_info(),
+                    from: ctx.accounts.treasury_vault.to_account_info(),
+                    authority: ctx.accounts.treasury.to_account_info(),
+                },
+                signer,
+            ),
+            burned,
+        )?;
+        let tracker = &mut ctx.accounts.config_account.config;
+        tracker.total_burned = tracker
+            .total_burned
+            .checked_add(burned)
+            .ok_or(CustomError::MathError)?;
+        ctx.accounts
+            .burn_ledger
+            .record(BurnSource::MarketingShare, burned, now)?;
+        emit!(BuybackBurnEvent {
+            tokens_bought: burned,
+            new_total_burned: tracker.total_burned,
+            timestamp: now as u64,
+        });
+    }
+
+    let mut records = [EpochPayout::default(); FEE_EPOCH_PAYOUTS];
+    let mut distributed: u64 = 0;
+    for (record, (destination, amount)) in records.iter_mut().zip(payouts) {
+        record.destination = destination.key();
+        record.amount = amount;
+        if amount == 0 {
+            continue;
+        }
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.treasury_vault.to_account_info(),
+                    to: destination,
+                    authority: ctx.accounts.treasury.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+                signer,
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        distributed = distributed
+            .checked_add(amount)
+            .ok_or(CustomError::MathError)?;
+    }
+    // Marketing wallet shares vest in their escrows
+    ctx.accounts.marketing_escrow_1.reload()?;
+    ctx.accounts.marketing_escrow_2.reload()?;
+    ctx.accounts
+        .marketing_vesting_1
+        .deposit_escrow_balance(ctx.accounts.marketing_escrow_1.amount, now)?;
+    ctx.accounts
+        .marketing_vesting_2
+        .deposit_escrow_balance(ctx.accounts.marketing_escrow_2.amount, now)?;
+    // collected + carried in = paid now + burned + added to the annual reserves + dust
+    let carried_out = total
+        .checked_add(reserve_paid)
+        .and_then(|available| available.checked_sub(distributed))
+        .and_then(|left| left.checked_sub(burned))
+        .and_then(|left| left.checked_sub(reserved))
+        .ok_or(CustomError::MathError)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let fee_epoch_state = &mut ctx.accounts.fee_epoch_state;
+    let epoch_id = fee_epoch_state.epoch_count;
+    fee_epoch_state.epoch_count += 1;
+    fee_epoch_state.carried_dust = carried_out;
+    fee_epoch_state.total_settled = fee_epoch_state
+        .total_settled
+        .checked_add(distributed)
+        .ok_or(CustomError::MathError)?;
+    fee_epoch_state.last_settled_at = now;
+    fee_epoch_state.bump = ctx.bumps.fee_epoch_state;
+
+    let fee_epoch = &mut ctx.accounts.fee_epoch;
+    fee_epoch.id = epoch_id;
+    fee_epoch.collected = collected;
+    fee_epoch.carried_in = carried_in;
+    fee_epoch.distributed = distributed;
+    fee_epoch.carried_out = carried_out;
+    fee_epoch.reserved = reserved;
+    fee_epoch.reserve_paid = reserve_paid;
+    fee_epoch.burned = burned;
+    fee_epoch.splits = splits;
+    fee_epoch.payouts = records;
+    fee_epoch.settled_at = now;
+    fee_epoch.bump = ctx.bumps.fee_epoch;
+
+    emit!(FeeEpochSettledEvent {
+        epoch_id,
+        collected,
+        distributed,
+        burned,
+        carried_dust: carried_out,
+        timestamp: now as u64,
+    });
+    msg!(
+        "Settled fee epoch {}: {} distributed, {} carried",
+        epoch_id,
+        distributed,
+        carried_out
+    );
+    Ok(())
+}
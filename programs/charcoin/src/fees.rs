@@ -0,0 +1,108 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::Token2022;
+use anchor_spl::token_2022_extensions::transfer_fee::{
+    harvest_withheld_tokens_to_mint, withdraw_withheld_tokens_from_mint,
+    HarvestWithheldTokensToMint, WithdrawWithheldTokensFromMint,
+};
+use anchor_spl::token_interface::{Mint, TokenAccount};
+
+use crate::{ConfigAccount, Treasury};
+
+#[event]
+pub struct FeesHarvestedEvent {
+    pub accounts_harvested: u32,
+    pub amount: u64,
+    pub treasury_balance: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct HarvestWithheldFees<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(mut,
+    constraint = mint.key() == config_account.config.char_token_mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Account<'info, Treasury>,
+    ///  Treasury vault receiving the transfer fees.
+    #[account(
+        mut,
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump = treasury.vault_bump,
+    )]
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: PDA set as the withdraw withheld authority of the CHAR mint.
+    #[account(
+        seeds = [b"fee_withdraw_authority".as_ref()],
+        bump
+    )]
+    pub withdraw_authority: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+/// Collects the withheld transfer fees of the token accounts passed in `remaining_accounts`
+/// into the mint, then withdraws everything withheld in the mint into the treasury.
+/// Anyone can call it, the fees can only go to the treasury.
+pub fn harvest_withheld_fees<'info>(
+    ctx: Context<'_, '_, 'info, 'info, HarvestWithheldFees<'info>>,
+) -> Result<()> {
+    let sources = ctx.remaining_accounts.to_vec();
+    let accounts_harvested = sources.len() as u32;
+
+    // Move the fees withheld in the token accounts into the mint
+    if !sources.is_empty() {
+        harvest_withheld_tokens_to_mint(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                HarvestWithheldTokensToMint {
+                    token_program_id: ctx.accounts.token_program.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+            ),
+            sources,
+        )?;
+    }
+
+    let treasury_balance_before = ctx.accounts.treasury_vault.amount;
+
+    // Withdraw the fees withheld in the mint into the treasury
+    let seeds: &[&[u8]] = &[
+        b"fee_withdraw_authority",
+        &[ctx.bumps.withdraw_authority],
+    ];
+    let signer = &[seeds];
+    withdraw_withheld_tokens_from_mint(CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        WithdrawWithheldTokensFromMint {
+            token_program_id: ctx.accounts.token_program.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            destination: ctx.accounts.treasury_vault.to_account_info(),
+            authority: ctx.accounts.withdraw_authority.to_account_info(),
+        },
+        signer,
+    ))?;
+
+    ctx.accounts.treasury_vault.reload()?;
+    let treasury_balance = ctx.accounts.treasury_vault.amount;
+    let amount = treasury_balance.saturating_sub(treasury_balance_before);
+
+    emit!(FeesHarvestedEvent {
+        accounts_harvested,
+        amount,
+        treasury_balance,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    msg!(
+        "Harvested {} tokens of fees from {} accounts",
+        amount,
+        accounts_harvested
+    );
+    Ok(())
+}
@@ -0,0 +1,397 @@
+use anchor_lang::prelude::*;
+
+use crate::{Charity, CharityStatus, ConfigAccount, CustomError, MilestonePlan, UserStakeInfo};
+
+/// Parameters of the staker votes on charity disbursements and vetoes. Votes are
+/// weighted by the staked amount of the voter.
+#[account]
+pub struct GovernanceConfig {
+    pub objection_window: i64,       // seconds to object after a proof
+    pub objection_threshold: u64,    // objection weight blocking the release
+    pub approval_quorum: u64,        // approval weight releasing at once
+    pub veto_threshold: u64,         // veto weight removing a charity
+    pub max_milestone_duration: i64, // latest milestone deadline, in seconds after registration
+    pub veto_period: i64,            // seconds a veto proposal is open to votes
+    pub next_veto_id: u64,
+    pub bump: u8,
+}
+
+/// Proposal to veto a charity. The charity is vetoed once the weight of the votes
+/// reaches the veto threshold before `ends_at`. A charity can be proposed again, with
+/// another reason or after a proposal has ended.
+#[account]
+pub struct CharityVeto {
+    pub id: u64,
+    pub charity: Pubkey,
+    pub proposer: Pubkey,
+    pub reason: u8, // reason code recorded on the charity
+    pub weight: u64,
+    pub voter_count: u64,
+    pub ends_at: i64, // end of the voting window
+    pub bump: u8,
+}
+
+/// Vote of a staker for a charity veto.
+#[account]
+pub struct VetoVote {
+    pub veto: Pubkey,
+    pub voter: Pubkey,
+    pub weight: u64,
+    pub bump: u8,
+}
+
+#[event]
+pub struct GovernanceConfigUpdatedEvent {
+    pub objection_window: i64,
+    pub objection_threshold: u64,
+    pub approval_quorum: u64,
+    pub veto_threshold: u64,
+    pub max_milestone_duration: i64,
+    pub veto_period: i64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CharityVetoProposedEvent {
+    pub veto_id: u64,
+    pub charity: Pubkey,
+    pub proposer: Pubkey,
+    pub reason: u8,
+    pub ends_at: i64,
+}
+
+#[event]
+pub struct CharityVetoVotedEvent {
+    pub veto_id: u64,
+    pub charity: Pubkey,
+    pub voter: Pubkey,
+    pub weight: u64,
+    pub total_weight: u64,
+    pub vetoed: bool,
+}
+
+#[derive(Accounts)]
+pub struct InitializeGovernanceConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<GovernanceConfig>(),
+        seeds = [b"governance_config".as_ref()],
+        bump
+    )]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGovernanceConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"governance_config".as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64)]
+pub struct ProposeCharityVeto<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    /// Only required when the charity has a milestone plan.
+    #[account(
+        seeds = [b"milestone_plan".as_ref(), charity.key().as_ref()],
+        bump = milestone_plan.bump,
+    )]
+    pub milestone_plan: Option<Account<'info, MilestonePlan>>,
+    #[account(
+        mut,
+        seeds = [b"governance_config".as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + std::mem::size_of::<CharityVeto>(),
+        seeds = [b"charity_veto".as_ref(), governance_config.next_veto_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub charity_veto: Account<'info, CharityVeto>,
+    #[account(
+        seeds = [b"user", proposer.key().as_ref()],
+        bump = user.bump
+    )]
+    pub user: Box<Account<'info, UserStakeInfo>>,
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64, veto_id: u64)]
+pub struct VoteCharityVeto<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    /// Only required when the charity has a milestone plan.
+    #[account(
+        seeds = [b"milestone_plan".as_ref(), charity.key().as_ref()],
+        bump = milestone_plan.bump,
+    )]
+    pub milestone_plan: Option<Account<'info, MilestonePlan>>,
+    #[account(
+        mut,
+        seeds = [b"charity_veto".as_ref(), veto_id.to_le_bytes().as_ref()],
+        bump = charity_veto.bump,
+        constraint = charity_veto.charity == charity.key() @ CustomError::InvalidArg,
+    )]
+    pub charity_veto: Account<'info, CharityVeto>,
+    #[account(
+        seeds = [b"governance_config".as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Account<'info, GovernanceConfig>,
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + std::mem::size_of::<VetoVote>(),
+        seeds = [b"veto_vote".as_ref(), charity_veto.key().as_ref(), voter.key().as_ref()],
+        bump
+    )]
+    pub veto_vote: Account<'info, VetoVote>,
+    #[account(
+        mut,
+        seeds = [b"user", voter.key().as_ref()],
+        bump = user.bump
+    )]
+    pub user: Box<Account<'info, UserStakeInfo>>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+fn set_governance_config(
+    governance_config: &mut GovernanceConfig,
+    objection_window: i64,
+    objection_threshold: u64,
+    approval_quorum: u64,
+    veto_threshold: u64,
+    max_milestone_duration: i64,
+    veto_period: i64,
+) -> Result<()> {
+    require!(
+        objection_window > 0
+            && objection_threshold > 0
+            && approval_quorum > 0
+            && veto_threshold > 0
+            && max_milestone_duration > 0
+            && veto_period > 0,
+        CustomError::InvalidGovernanceConfig
+    );
+    governance_config.objection_window = objection_window;
+    governance_config.objection_threshold = objection_threshold;
+    governance_config.approval_quorum = approval_quorum;
+    governance_config.veto_threshold = veto_threshold;
+    governance_config.max_milestone_duration = max_milestone_duration;
+    governance_config.veto_period = veto_period;
+    Ok(())
+}
+
+/// Stake weight of a voter meeting the governance stake for the minimum voting
+/// duration, the eligibility rules of `cast_vote`.
+pub fn governance_weight(config_account: &ConfigAccount, user: &UserStakeInfo) -> Result<u64> {
+    let now = Clock::get()?.unix_timestamp as u64;
+    let weight = user.total_amount;
+    require!(
+        weight > 0 && weight >= config_account.config.min_governance_stake,
+        CustomError::VotingNotEligible
+    );
+    require!(
+        user.eligible_at > 0
+            && now.saturating_sub(user.eligible_at)
+                >= config_account.config.min_stake_duration_voting,
+        CustomError::VotingNotEligible
+    );
+    Ok(weight)
+}
+
+/// Keeps the stake a vote was weighted with from being withdrawn before the vote
+/// closes, so the same tokens cannot vote again from another wallet.
+pub fn lock_governance_stake(user: &mut UserStakeInfo, closes_at: i64) {
+    user.governance_locked_until = user.governance_locked_until.max(closes_at as u64);
+}
+
+/// Creates the governance config.
+pub fn initialize_governance_config(
+    ctx: Context<InitializeGovernanceConfig>,
+    objection_window: i64,
+    objection_threshold: u64,
+    approval_quorum: u64,
+    veto_threshold: u64,
+    max_milestone_duration: i64,
+    veto_period: i64,
+) -> Result<()> {
+    let governance_config = &mut ctx.accounts.governance_config;
+    set_governance_config(
+        governance_config,
+        objection_window,
+        objection_threshold,
+        approval_quorum,
+        veto_threshold,
+        max_milestone_duration,
+        veto_period,
+    )?;
+    governance_config.bump = ctx.bumps.governance_config;
+    Ok(())
+}
+
+/// Changes the governance parameters, votes in progress use the new values.
+pub fn update_governance_config(
+    ctx: Context<UpdateGovernanceConfig>,
+    objection_window: i64,
+    objection_threshold: u64,
+    approval_quorum: u64,
+    veto_threshold: u64,
+    max_milestone_duration: i64,
+    veto_period: i64,
+) -> Result<()> {
+    set_governance_config(
+        &mut ctx.accounts.governance_config,
+        objection_window,
+        objection_threshold,
+        approval_quorum,
+        veto_threshold,
+        max_milestone_duration,
+        veto_period,
+    )?;
+    emit!(GovernanceConfigUpdatedEvent {
+        objection_window,
+        objection_threshold,
+        approval_quorum,
+        veto_threshold,
+        max_milestone_duration,
+        veto_period,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    Ok(())
+}
+
+/// Opens a veto vote on a charity that is neither removed nor paid, a charity paid in
+/// milestones can be vetoed until all of them are released. The vote is open for the
+/// veto period of the governance config.
+pub fn propose_charity_veto(
+    ctx: Context<ProposeCharityVeto>,
+    _charity_id: u64,
+    reason: u8,
+) -> Result<()> {
+    governance_weight(&ctx.accounts.config_account, &ctx.accounts.user)?;
+    let charity = &ctx.accounts.charity;
+    require!(
+        charity.is_removable(ctx.accounts.milestone_plan.as_deref()),
+        CustomError::CharityNotRemovable
+    );
+    let governance_config = &mut ctx.accounts.governance_config;
+    let charity_veto = &mut ctx.accounts.charity_veto;
+    charity_veto.id = governance_config.next_veto_id;
+    charity_veto.charity = charity.key();
+    charity_veto.proposer = ctx.accounts.proposer.key();
+    charity_veto.reason = reason;
+    charity_veto.ends_at = Clock::get()?
+        .unix_timestamp
+        .checked_add(governance_config.veto_period)
+        .ok_or(CustomError::MathError)?;
+    charity_veto.bump = ctx.bumps.charity_veto;
+    governance_config.next_veto_id += 1;
+
+    emit!(CharityVetoProposedEvent {
+        veto_id: charity_veto.id,
+        charity: charity_veto.charity,
+        proposer: charity_veto.proposer,
+        reason,
+        ends_at: charity_veto.ends_at,
+    });
+    Ok(())
+}
+
+/// Votes for the veto of a charity during the voting window, the charity is vetoed once
+/// the veto threshold is reached. The stake of the voter stays locked until the window ends.
+pub fn vote_charity_veto(
+    ctx: Context<VoteCharityVeto>,
+    _charity_id: u64,
+    _veto_id: u64,
+) -> Result<()> {
+    let weight = governance_weight(&ctx.accounts.config_account, &ctx.accounts.user)?;
+    let milestone_plan = ctx.accounts.milestone_plan.as_deref();
+    let charity = &mut ctx.accounts.charity;
+    require!(
+        charity.is_removable(milestone_plan),
+        CustomError::CharityNotRemovable
+    );
+    let charity_veto = &mut ctx.accounts.charity_veto;
+    require!(
+        Clock::get()?.unix_timestamp <= charity_veto.ends_at,
+        CustomError::VetoVotingClosed
+    );
+    lock_governance_stake(&mut ctx.accounts.user, charity_veto.ends_at);
+    charity_veto.weight = charity_veto
+        .weight
+        .checked_add(weight)
+        .ok_or(CustomError::MathError)?;
+    charity_veto.voter_count += 1;
+
+    let veto_vote = &mut ctx.accounts.veto_vote;
+    veto_vote.veto = charity_veto.key();
+    veto_vote.voter = ctx.accounts.voter.key();
+    veto_vote.weight = weight;
+    veto_vote.bump = ctx.bumps.veto_vote;
+
+    let vetoed = charity_veto.weight >= ctx.accounts.governance_config.veto_threshold;
+    if vetoed {
+        charity.remove(CharityStatus::Vetoed, charity_veto.reason, milestone_plan)?;
+    }
+    emit!(CharityVetoVotedEvent {
+        veto_id: charity_veto.id,
+        charity: charity.key(),
+        voter: veto_vote.voter,
+        weight,
+        total_weight: charity_veto.weight,
+        vetoed,
+    });
+    Ok(())
+}
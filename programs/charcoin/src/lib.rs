@@ -6,19 +6,54 @@ use anchor_lang::prelude::*;
 pub mod burn;
 pub mod donation;
 pub mod marketing;
+pub mod referral;
 pub mod rewards;
 pub mod security;
 pub mod staking;
 pub mod errors;
+pub mod extra_rewards;
+pub mod fees;
+pub mod transfer;
+pub mod treasury;
+pub mod distribution;
+pub mod fee_epoch;
+pub mod schedule;
+pub mod limiter;
+pub mod vesting;
+pub mod buyback;
+pub mod supply;
+pub mod burn_policy;
+pub mod round;
+pub mod governance;
+pub mod milestone;
+pub mod nomination;
+pub mod attestation;
 
 // Re-export public items
 pub use burn::*;
 pub use donation::*;
 pub use marketing::*;
+pub use referral::*;
 pub use rewards::*;
 pub use security::*;
 pub use staking::*;
 pub use errors::*;
+pub use extra_rewards::*;
+pub use fees::*;
+pub use treasury::*;
+pub use distribution::*;
+pub use fee_epoch::*;
+pub use schedule::*;
+pub use limiter::*;
+pub use vesting::*;
+pub use buyback::*;
+pub use supply::*;
+pub use burn_policy::*;
+pub use round::*;
+pub use governance::*;
+pub use milestone::*;
+pub use nomination::*;
+pub use attestation::*;
 
 declare_id!("c37mvW9JM6S3bzsY43VhJWQucW1b8uFyBkK4y3LCdRH");
 
@@ -51,16 +86,37 @@ pub mod charcoin {
 
     // Staking
     /// Stake tokens with a specified lockup duration.
-    pub fn stake_tokens_handler(ctx: Context<Stake>, amount: u64, lockup: u16) -> Result<()> {
+    /// A referrer can only be provided on the user's first stake.
+    pub fn stake_tokens_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Stake<'info>>,
+        amount: u64,
+        lockup: u16,
+        referrer: Option<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        staking::stake_tokens(ctx, amount, lockup, referrer)
+    }
+
+    /// Moves a staking info account created before referrals to the current layout.
+    pub fn migrate_user_stake_info_handler(
+        ctx: Context<MigrateUserStakeInfo>,
+        authority: Pubkey,
+    ) -> Result<()> {
         require!(
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
         );
-        staking::stake_tokens(ctx, amount, lockup)
+        staking::migrate_user_stake_info(ctx, authority)
     }
 
     /// Unstake tokens after 48h delay and lockup period has expired. unstake before lockup period will result in penalty
-    pub fn unstake_tokens_handler(ctx: Context<Unstake>, index: u64) -> Result<()> {
+    pub fn unstake_tokens_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Unstake<'info>>,
+        index: u64,
+    ) -> Result<()> {
         require!(
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
@@ -79,7 +135,11 @@ pub mod charcoin {
         staking::request_unstake_tokens(ctx, index)
     }
 
-    pub fn claim_reward_handler(ctx: Context<ClaimReward>, index: u64) -> Result<()> {
+    /// Claims the CHAR reward of a stake together with every partner reward it earned.
+    pub fn claim_reward_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimReward<'info>>,
+        index: u64,
+    ) -> Result<()> {
         require!(
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
@@ -88,14 +148,232 @@ pub mod charcoin {
         staking::claim_reward(ctx, index)
     }
 
+    /// Adds a partner reward mint to the staking pool.
+    pub fn add_extra_reward_handler(
+        ctx: Context<AddExtraReward>,
+        emission_rate: u64,
+        start_time: u64,
+        end_time: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        extra_rewards::add_extra_reward(ctx, emission_rate, start_time, end_time)
+    }
+
+    pub fn end_extra_reward_handler(
+        ctx: Context<UpdateExtraReward>,
+        index: u8,
+        end_time: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        extra_rewards::end_extra_reward(ctx, index, end_time)
+    }
+
+    pub fn remove_extra_reward_handler(ctx: Context<RemoveExtraReward>, index: u8) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        extra_rewards::remove_extra_reward(ctx, index)
+    }
+
+    /// Claims what a stake earned from a removed extra reward campaign.
+    pub fn claim_ended_extra_reward_handler(
+        ctx: Context<ClaimEndedExtraReward>,
+        stake_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        extra_rewards::claim_ended_extra_reward(ctx, stake_id)
+    }
+
+    /// Enables or disables the referral program and sets its reward share and budget.
+    pub fn set_referral_config_handler(
+        ctx: Context<SetReferralConfig>,
+        enabled: bool,
+        reward_bps: u16,
+        budget: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        referral::set_referral_config(ctx, enabled, reward_bps, budget)
+    }
+
     // Burning
-    pub fn buyback_burn_handler(ctx: Context<ExecuteBuyback>) -> Result<()> {
+    pub fn buyback_burn_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteBuyback<'info>>,
+    ) -> Result<()> {
         require!(
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
         );
         burn::execute_buyback(ctx)
     }
+    /// Creates the buyback config and moves the death wallet to its PDA.
+    pub fn initialize_buyback_config_handler(
+        ctx: Context<InitializeBuybackConfig>,
+        min_balance: u64,
+        min_interval: i64,
+        keeper_tip_bps: u16,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        burn::initialize_buyback_config(ctx, min_balance, min_interval, keeper_tip_bps)
+    }
+    /// Changes the thresholds and keeper tip of the death wallet burns.
+    pub fn update_buyback_config_handler(
+        ctx: Context<UpdateBuybackConfig>,
+        min_balance: u64,
+        min_interval: i64,
+        keeper_tip_bps: u16,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        burn::update_buyback_config(ctx, min_balance, min_interval, keeper_tip_bps)
+    }
+    /// Creates the burn ledger.
+    pub fn initialize_burn_ledger_handler(ctx: Context<InitializeBurnLedger>) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        burn::initialize_burn_ledger(ctx)
+    }
+    /// Burns CHAR of the signer, credited on the burn leaderboard.
+    pub fn burn_voluntary_handler(ctx: Context<BurnVoluntary>, amount: u64) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        burn::burn_voluntary(ctx, amount)
+    }
+    /// Creates the supply-responsive burn policy, disabled.
+    pub fn initialize_burn_policy_handler(ctx: Context<InitializeBurnPolicy>) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        burn_policy::initialize_burn_policy(ctx)
+    }
+    /// Sets the burn bounds and target supply curve of the marketing burn share.
+    pub fn set_burn_policy_handler(
+        ctx: Context<SetBurnPolicy>,
+        enabled: bool,
+        min_burn: u16,
+        max_burn: u16,
+        full_burn_excess: u16,
+        taper_excess: u16,
+        curve: Vec<TargetPoint>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        burn_policy::set_burn_policy(
+            ctx,
+            enabled,
+            min_burn,
+            max_burn,
+            full_burn_excess,
+            taper_excess,
+            curve,
+        )
+    }
+    /// Returns the current burn share of the marketing fees. Read only.
+    pub fn get_burn_share_handler(ctx: Context<GetBurnShare>) -> Result<u16> {
+        burn_policy::get_burn_share(ctx)
+    }
+    /// Configures the AMM used by the buyback.
+    pub fn initialize_amm_config_handler(
+        ctx: Context<InitializeAmmConfig>,
+        amm_program: Pubkey,
+        pool: Pubkey,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        buyback::initialize_amm_config(ctx, amm_program, pool)
+    }
+    /// Proposes another AMM program or pool for the buyback, applied after a timelock.
+    pub fn propose_amm_config_handler(
+        ctx: Context<UpdateAmmConfig>,
+        amm_program: Pubkey,
+        pool: Pubkey,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        buyback::propose_amm_config(ctx, amm_program, pool)
+    }
+    /// Applies the proposed AMM program and pool once the timelock has passed.
+    pub fn apply_amm_config_handler(ctx: Context<UpdateAmmConfig>) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        buyback::apply_amm_config(ctx)
+    }
+    /// Buys CHAR with a treasury quote asset through the AMM and burns it.
+    pub fn buyback_swap_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuybackSwap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        buyback::buyback_swap(ctx, amount_in, min_amount_out)
+    }
+    /// Creates the limiter of the quote asset spent by the buyback.
+    pub fn initialize_quote_outflow_limiter_handler(
+        ctx: Context<InitializeQuoteOutflowLimiter>,
+        daily_limit: u64,
+        monthly_limit: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        buyback::initialize_quote_outflow_limiter(ctx, daily_limit, monthly_limit)
+    }
+    /// Lowers the quote outflow limits or queues raised limits behind the timelock.
+    pub fn propose_quote_outflow_limits_handler(
+        ctx: Context<UpdateQuoteOutflowLimiter>,
+        daily_limit: u64,
+        monthly_limit: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        buyback::propose_quote_outflow_limits(ctx, daily_limit, monthly_limit)
+    }
+    /// Applies queued quote outflow limits once the timelock has passed.
+    pub fn apply_quote_outflow_limits_handler(
+        ctx: Context<UpdateQuoteOutflowLimiter>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        buyback::apply_quote_outflow_limits(ctx)
+    }
    
   
 
@@ -114,6 +392,7 @@ pub mod charcoin {
         ctx: Context<RegisterCharity>,
         title: String,
         wallet: Pubkey,
+        category: CharityCategory,
         start_time: u64,
         end_time: u64,
     ) -> Result<()> {
@@ -121,7 +400,7 @@ pub mod charcoin {
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
         );
-        donation::register_charity(ctx, title, wallet, start_time, end_time)
+        donation::register_charity(ctx, title, wallet, category, start_time, end_time)
     }
 
     /// Casts or updates a vote for a charity.
@@ -145,49 +424,670 @@ pub mod charcoin {
         donation::finalize_charity_vote(ctx, charity_id)
     }
 
+    /// Moves a charity account registered before the current layout to it.
+    pub fn migrate_charity_handler(
+        ctx: Context<MigrateCharity>,
+        charity_id: u64,
+        category: CharityCategory,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        donation::migrate_charity(ctx, charity_id, category)
+    }
+
+    /// Updates the profile of a charity, signed by the charity admin.
+    pub fn update_charity_profile_handler(
+        ctx: Context<UpdateCharityProfile>,
+        charity_id: u64,
+        profile: CharityProfile,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        donation::update_charity_profile(ctx, charity_id, profile)
+    }
+
+    /// Cancels a charity with a reason code, signed by the admin.
+    pub fn cancel_charity_handler(
+        ctx: Context<CancelCharity>,
+        charity_id: u64,
+        reason: u8,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        donation::cancel_charity(ctx, charity_id, reason)
+    }
+
+    /// Opens a voting round grouping charities of a category under one voting window.
+    pub fn create_voting_round_handler(
+        ctx: Context<CreateVotingRound>,
+        start_time: u64,
+        end_time: u64,
+        quorum: u64,
+        winner_count: u8,
+        category: CharityCategory,
+        require_attestation: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        round::create_voting_round(
+            ctx,
+            start_time,
+            end_time,
+            quorum,
+            winner_count,
+            category,
+            require_attestation,
+        )
+    }
+
+    /// Adds a charity to a voting round.
+    pub fn add_charity_to_round_handler(
+        ctx: Context<AddCharityToRound>,
+        round_id: u64,
+        charity_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        round::add_charity_to_round(ctx, round_id, charity_id)
+    }
+
+    /// Ranks the charities of a round and records the winners.
+    pub fn finalize_round_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizeRound<'info>>,
+        round_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        round::finalize_round(ctx, round_id)
+    }
+
+    /// Creates the donation vault of a charity category, funded as its donation wallet.
+    pub fn initialize_donation_vault_handler(
+        ctx: Context<InitializeDonationVault>,
+        category: CharityCategory,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        round::initialize_donation_vault(ctx, category)
+    }
+
+    /// Sets the CHAR paid to the winners of an open round.
+    pub fn set_round_allocation_handler(
+        ctx: Context<SetRoundAllocation>,
+        round_id: u64,
+        allocation: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        round::set_round_allocation(ctx, round_id, allocation)
+    }
+
+    /// Pays a round winner its share of the round allocation.
+    pub fn pay_charity_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PayCharity<'info>>,
+        round_id: u64,
+        charity_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        round::pay_charity(ctx, round_id, charity_id)
+    }
+
+    /// Returns the unpaid share of a removed round winner to the donation pool.
+    pub fn reclaim_charity_payout_handler(
+        ctx: Context<ReclaimCharityPayout>,
+        round_id: u64,
+        charity_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        round::reclaim_charity_payout(ctx, round_id, charity_id)
+    }
+
+    /// Creates the parameters of the staker votes on disbursements and vetoes.
+    pub fn initialize_governance_config_handler(
+        ctx: Context<InitializeGovernanceConfig>,
+        objection_window: i64,
+        objection_threshold: u64,
+        approval_quorum: u64,
+        veto_threshold: u64,
+        max_milestone_duration: i64,
+        veto_period: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        governance::initialize_governance_config(
+            ctx,
+            objection_window,
+            objection_threshold,
+            approval_quorum,
+            veto_threshold,
+            max_milestone_duration,
+            veto_period,
+        )
+    }
+
+    /// Updates the parameters of the staker votes on disbursements and vetoes.
+    pub fn update_governance_config_handler(
+        ctx: Context<UpdateGovernanceConfig>,
+        objection_window: i64,
+        objection_threshold: u64,
+        approval_quorum: u64,
+        veto_threshold: u64,
+        max_milestone_duration: i64,
+        veto_period: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        governance::update_governance_config(
+            ctx,
+            objection_window,
+            objection_threshold,
+            approval_quorum,
+            veto_threshold,
+            max_milestone_duration,
+            veto_period,
+        )
+    }
+
+    /// Opens a governance veto vote on a charity.
+    pub fn propose_charity_veto_handler(
+        ctx: Context<ProposeCharityVeto>,
+        charity_id: u64,
+        reason: u8,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        governance::propose_charity_veto(ctx, charity_id, reason)
+    }
+
+    /// Votes for the veto of a charity.
+    pub fn vote_charity_veto_handler(
+        ctx: Context<VoteCharityVeto>,
+        charity_id: u64,
+        veto_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        governance::vote_charity_veto(ctx, charity_id, veto_id)
+    }
+
+    /// Splits the round payout of a winning charity into milestones.
+    pub fn register_milestones_handler(
+        ctx: Context<RegisterMilestones>,
+        round_id: u64,
+        charity_id: u64,
+        milestones: Vec<MilestoneInput>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        milestone::register_milestones(ctx, round_id, charity_id, milestones)
+    }
+
+    /// Submits the proof of a milestone.
+    pub fn submit_milestone_proof_handler(
+        ctx: Context<SubmitMilestoneProof>,
+        charity_id: u64,
+        index: u8,
+        proof_hash: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        milestone::submit_milestone_proof(ctx, charity_id, index, proof_hash)
+    }
+
+    /// Approves or objects to a milestone proof.
+    pub fn vote_milestone_handler(
+        ctx: Context<VoteMilestone>,
+        charity_id: u64,
+        index: u8,
+        approve: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        milestone::vote_milestone(ctx, charity_id, index, approve)
+    }
+
+    /// Pays an approved milestone to the charity.
+    pub fn release_milestone_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReleaseMilestone<'info>>,
+        charity_id: u64,
+        index: u8,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        milestone::release_milestone(ctx, charity_id, index)
+    }
+
+    /// Returns a missed milestone to the donation pool.
+    pub fn reclaim_milestone_handler(
+        ctx: Context<ReclaimMilestone>,
+        charity_id: u64,
+        index: u8,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        milestone::reclaim_milestone(ctx, charity_id, index)
+    }
+
+    /// Initializes the community nominations and their bond vault.
+    pub fn initialize_nomination_config_handler(
+        ctx: Context<InitializeNominationConfig>,
+        bond_amount: u64,
+        endorsement_threshold: u64,
+        nomination_period: i64,
+        acceptance_period: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        nomination::initialize_nomination_config(
+            ctx,
+            bond_amount,
+            endorsement_threshold,
+            nomination_period,
+            acceptance_period,
+        )
+    }
+
+    /// Updates the bond, threshold and periods of the nominations.
+    pub fn update_nomination_config_handler(
+        ctx: Context<UpdateNominationConfig>,
+        bond_amount: u64,
+        endorsement_threshold: u64,
+        nomination_period: i64,
+        acceptance_period: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        nomination::update_nomination_config(
+            ctx,
+            bond_amount,
+            endorsement_threshold,
+            nomination_period,
+            acceptance_period,
+        )
+    }
+
+    /// Nominates a charity against a refundable bond.
+    pub fn nominate_charity_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, NominateCharity<'info>>,
+        title: String,
+        wallet: Pubkey,
+        category: CharityCategory,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        nomination::nominate_charity(ctx, title, wallet, category)
+    }
+
+    /// Endorses a nomination during its nomination phase.
+    pub fn endorse_nomination_handler(
+        ctx: Context<EndorseNomination>,
+        nomination_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        nomination::endorse_nomination(ctx, nomination_id)
+    }
+
+    /// Registers an endorsed nomination as a charity, in a round picked by the admin.
+    pub fn accept_nomination_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AcceptNomination<'info>>,
+        nomination_id: u64,
+        round_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        nomination::accept_nomination(ctx, nomination_id, round_id)
+    }
+
+    /// Sends the bond of an insufficiently endorsed nomination to the donation pool.
+    pub fn reject_nomination_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RejectNomination<'info>>,
+        nomination_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        nomination::reject_nomination(ctx, nomination_id)
+    }
+
+    /// Refunds the bond of an endorsed nomination left unaccepted past the acceptance period.
+    pub fn refund_nomination_bond_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RefundNominationBond<'info>>,
+        nomination_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        nomination::refund_nomination_bond(ctx, nomination_id)
+    }
+
+    /// Appoints or removes a charity verifier.
+    pub fn set_verifier_handler(
+        ctx: Context<SetVerifier>,
+        authority: Pubkey,
+        active: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        attestation::set_verifier(ctx, authority, active)
+    }
+
+    /// Attests a charity wallet, or renews its attestation.
+    pub fn issue_attestation_handler(
+        ctx: Context<IssueAttestation>,
+        wallet: Pubkey,
+        documents_hash: [u8; 32],
+        expires_at: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        attestation::issue_attestation(ctx, wallet, documents_hash, expires_at)
+    }
+
+    /// Revokes the attestation of a charity wallet.
+    pub fn revoke_attestation_handler(
+        ctx: Context<RevokeAttestation>,
+        wallet: Pubkey,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        attestation::revoke_attestation(ctx, wallet)
+    }
+
     //  Rewards
     /// Releases funds from the treasury to staking rewards and charity fund.
-    pub fn release_rewards_handler(
-        ctx: Context<ReleaseRewards>,
-        total_amount: u64,
+    pub fn release_rewards_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReleaseRewards<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        rewards::release_rewards(ctx)
+    }
+    pub fn release_donations_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReleaseDonations<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        rewards::release_donations(ctx)
+    }
+    pub fn release_staking_funds_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReleaseStakingFunds<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        rewards::release_staking_char_funds(ctx)
+    }
+
+    // Treasury
+    /// Creates the program-owned treasury vault.
+    pub fn initialize_treasury_handler(ctx: Context<InitializeTreasury>) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        treasury::initialize_treasury(ctx)
+    }
+    /// Moves the balance of the former treasury token account into the vault.
+    pub fn migrate_treasury_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, MigrateTreasury<'info>>,
     ) -> Result<()> {
         require!(
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
         );
-        rewards::release_rewards(ctx, total_amount)
+        treasury::migrate_treasury(ctx)
     }
-    pub fn release_donations_handler(
-        ctx: Context<ReleaseDonations>,
-        total_amount: u64,
+
+    // Distribution
+    /// Creates the distribution config with the default splits.
+    pub fn initialize_distribution_config_handler(
+        ctx: Context<InitializeDistributionConfig>,
     ) -> Result<()> {
         require!(
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
         );
-        rewards::release_donations(ctx, total_amount)
+        distribution::initialize_distribution_config(ctx)
+    }
+    /// Replaces the split tree of the collected fees.
+    pub fn update_distribution_config_handler(
+        ctx: Context<UpdateDistributionConfig>,
+        splits: DistributionSplits,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        distribution::update_distribution_config(ctx, splits)
+    }
+
+    // Release schedule
+    /// Creates the schedule of the monthly and annual releases.
+    pub fn initialize_release_schedule_handler(
+        ctx: Context<InitializeReleaseSchedule>,
+        period_anchor: i64,
+        monthly_period: i64,
+        annual_period: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        schedule::initialize_release_schedule(ctx, period_anchor, monthly_period, annual_period)
+    }
+    /// Changes the period anchor and lengths of the release schedule.
+    pub fn update_release_schedule_handler(
+        ctx: Context<UpdateReleaseSchedule>,
+        period_anchor: i64,
+        monthly_period: i64,
+        annual_period: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        schedule::update_release_schedule(ctx, period_anchor, monthly_period, annual_period)
+    }
+
+    // Outflow limits
+    /// Creates the limiter of the treasury outflows.
+    pub fn initialize_outflow_limiter_handler(
+        ctx: Context<InitializeOutflowLimiter>,
+        daily_limit: u64,
+        monthly_limit: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        limiter::initialize_outflow_limiter(ctx, daily_limit, monthly_limit)
+    }
+    /// Lowers the outflow limits or queues raised limits behind the timelock.
+    pub fn propose_outflow_limits_handler(
+        ctx: Context<UpdateOutflowLimiter>,
+        daily_limit: u64,
+        monthly_limit: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        limiter::propose_outflow_limits(ctx, daily_limit, monthly_limit)
+    }
+    /// Applies queued outflow limits once the timelock has passed.
+    pub fn apply_outflow_limits_handler(ctx: Context<UpdateOutflowLimiter>) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        limiter::apply_outflow_limits(ctx)
+    }
+
+    // Marketing vesting
+    /// Creates the vesting stream and escrow of a marketing wallet.
+    pub fn initialize_marketing_vesting_handler(
+        ctx: Context<InitializeMarketingVesting>,
+        duration: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        vesting::initialize_marketing_vesting(ctx, duration)
+    }
+    /// Changes the vesting duration of the next marketing deposits.
+    pub fn set_marketing_vesting_duration_handler(
+        ctx: Context<SetMarketingVestingDuration>,
+        duration: i64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        vesting::set_marketing_vesting_duration(ctx, duration)
+    }
+    /// Withdraws the vested marketing funds to the marketing wallet.
+    pub fn withdraw_vested_marketing_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawVestedMarketing<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        vesting::withdraw_vested_marketing(ctx)
+    }
+
+    // Supply
+    /// Creates the supply config, excluding the wallets of the config.
+    pub fn initialize_supply_config_handler(ctx: Context<InitializeSupplyConfig>) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        supply::initialize_supply_config(ctx)
+    }
+    /// Replaces the wallets excluded from the circulating supply.
+    pub fn set_supply_exclusions_handler(
+        ctx: Context<SetSupplyExclusions>,
+        wallets: Vec<Pubkey>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        supply::set_supply_exclusions(ctx, wallets)
+    }
+    /// Returns total, burned, staked, treasury and circulating supply. Read only,
+    /// so it stays available while the program is halted.
+    pub fn get_supply_stats_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, GetSupplyStats<'info>>,
+    ) -> Result<SupplyStats> {
+        supply::get_supply_stats(ctx)
+    }
+
+    // Fees
+    /// Distributes every fee collected since the previous settlement and records the epoch.
+    pub fn settle_fee_epoch_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SettleFeeEpoch<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        fee_epoch::settle_fee_epoch(ctx)
+    }
+    /// Starts the fee releases from the fees recorded so far by the transfer hook.
+    pub fn initialize_fee_release_handler(ctx: Context<InitializeFeeRelease>) -> Result<()> {
+        require!(
+            ctx.accounts.config_account.config.halted == false,
+            CustomError::ProgramIsHalted
+        );
+        rewards::initialize_fee_release(ctx)
     }
-    pub fn release_staking_funds_handler(
-        ctx: Context<ReleaseStakingFunds>,
-        total_amount: u64,
+    /// Collects the withheld CHAR transfer fees into the treasury.
+    pub fn harvest_withheld_fees_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, HarvestWithheldFees<'info>>,
     ) -> Result<()> {
         require!(
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
         );
-        rewards::release_staking_char_funds(ctx, total_amount)
+        fees::harvest_withheld_fees(ctx)
     }
 
     // Marketing
-    pub fn distribute_marketing_funds_handler(
-        ctx: Context<DistributeMarketingFunds>,
-        total_amount: u64,
+    pub fn distribute_marketing_funds_handler<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DistributeMarketingFunds<'info>>,
     ) -> Result<()> {
         require!(
             ctx.accounts.config_account.config.halted == false,
             CustomError::ProgramIsHalted
         );
-        marketing::distribute_marketing_funds(ctx, total_amount)
+        marketing::distribute_marketing_funds(ctx)
     }
 
     pub fn update_settings(
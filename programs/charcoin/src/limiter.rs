@@ -0,0 +1,231 @@
+use anchor_lang::prelude::*;
+
+use crate::{ConfigAccount, CustomError};
+
+pub const HOURLY_BUCKETS: usize = 24;
+pub const DAILY_BUCKETS: usize = 30;
+const ONE_HOUR_IN_SECONDS: i64 = 3600;
+const ONE_DAY_IN_SECONDS: i64 = 86400;
+/// Delay before a raised outflow limit can be applied.
+pub const LIMIT_INCREASE_DELAY: i64 = 2 * ONE_DAY_IN_SECONDS;
+
+/// Caps the treasury outflow over a rolling 24 hours and a rolling 30 days.
+/// Outflows are counted in hourly and daily buckets, indexed by hour and day number.
+#[account]
+pub struct OutflowLimiter {
+    pub daily_limit: u64,   // max outflow per rolling 24 hours
+    pub monthly_limit: u64, // max outflow per rolling 30 days
+    pub hourly_outflow: [u64; HOURLY_BUCKETS],
+    pub daily_outflow: [u64; DAILY_BUCKETS],
+    pub last_hour: i64, // hour number of the latest outflow
+    pub last_day: i64,  // day number of the latest outflow
+    // Raised limits waiting for the timelock
+    pub pending_daily_limit: u64,
+    pub pending_monthly_limit: u64,
+    pub pending_apply_after: i64, // 0 when nothing is pending
+    pub bump: u8,
+}
+
+/// Clears the buckets of the periods elapsed since `last`.
+fn roll<const N: usize>(buckets: &mut [u64; N], last: &mut i64, current: i64) {
+    let elapsed = current.saturating_sub(*last);
+    if elapsed >= N as i64 {
+        *buckets = [0; N];
+    } else {
+        for period in (*last + 1)..=current {
+            buckets[period.rem_euclid(N as i64) as usize] = 0;
+        }
+    }
+    *last = current.max(*last);
+}
+
+impl OutflowLimiter {
+    fn roll(&mut self, now: i64) {
+        roll(
+            &mut self.hourly_outflow,
+            &mut self.last_hour,
+            now / ONE_HOUR_IN_SECONDS,
+        );
+        roll(
+            &mut self.daily_outflow,
+            &mut self.last_day,
+            now / ONE_DAY_IN_SECONDS,
+        );
+    }
+
+    /// Counts a treasury outflow, rejected when it goes over one of the limits.
+    pub fn record_outflow(&mut self, amount: u64, now: i64) -> Result<()> {
+        self.roll(now);
+        let last_24_hours = self.hourly_outflow.iter().sum::<u64>();
+        let last_30_days = self.daily_outflow.iter().sum::<u64>();
+        require!(
+            last_24_hours.saturating_add(amount) <= self.daily_limit
+                && last_30_days.saturating_add(amount) <= self.monthly_limit,
+            CustomError::OutflowLimitExceeded
+        );
+        let hour = &mut self.hourly_outflow[(self.last_hour as usize) % HOURLY_BUCKETS];
+        *hour = hour.checked_add(amount).ok_or(CustomError::MathError)?;
+        let day = &mut self.daily_outflow[(self.last_day as usize) % DAILY_BUCKETS];
+        *day = day.checked_add(amount).ok_or(CustomError::MathError)?;
+        Ok(())
+    }
+
+    /// Sets the limits of a new limiter, starting with empty buckets.
+    pub fn set_up(
+        &mut self,
+        daily_limit: u64,
+        monthly_limit: u64,
+        bump: u8,
+        now: i64,
+    ) -> Result<()> {
+        require!(
+            daily_limit <= monthly_limit,
+            CustomError::InvalidOutflowLimits
+        );
+        self.daily_limit = daily_limit;
+        self.monthly_limit = monthly_limit;
+        self.last_hour = now / ONE_HOUR_IN_SECONDS;
+        self.last_day = now / ONE_DAY_IN_SECONDS;
+        self.bump = bump;
+        Ok(())
+    }
+
+    /// Changes the limits. Lower limits apply immediately, raising any of
+    /// them is queued and can only be applied after `LIMIT_INCREASE_DELAY`.
+    pub fn propose_limits(&mut self, daily_limit: u64, monthly_limit: u64, now: i64) -> Result<()> {
+        require!(
+            daily_limit <= monthly_limit,
+            CustomError::InvalidOutflowLimits
+        );
+        if daily_limit <= self.daily_limit && monthly_limit <= self.monthly_limit {
+            self.daily_limit = daily_limit;
+            self.monthly_limit = monthly_limit;
+            self.pending_apply_after = 0;
+            emit!(OutflowLimitsUpdatedEvent {
+                daily_limit,
+                monthly_limit,
+                timestamp: now as u64,
+            });
+            return Ok(());
+        }
+
+        let apply_after = now + LIMIT_INCREASE_DELAY;
+        self.pending_daily_limit = daily_limit;
+        self.pending_monthly_limit = monthly_limit;
+        self.pending_apply_after = apply_after;
+        emit!(OutflowLimitsProposedEvent {
+            daily_limit,
+            monthly_limit,
+            apply_after,
+        });
+        Ok(())
+    }
+
+    /// Applies the raised limits once the timelock has passed.
+    pub fn apply_limits(&mut self, now: i64) -> Result<()> {
+        require!(
+            self.pending_apply_after != 0,
+            CustomError::NoPendingOutflowLimits
+        );
+        require!(
+            now >= self.pending_apply_after,
+            CustomError::OutflowLimitsTimelocked
+        );
+        self.daily_limit = self.pending_daily_limit;
+        self.monthly_limit = self.pending_monthly_limit;
+        self.pending_apply_after = 0;
+        emit!(OutflowLimitsUpdatedEvent {
+            daily_limit: self.daily_limit,
+            monthly_limit: self.monthly_limit,
+            timestamp: now as u64,
+        });
+        Ok(())
+    }
+}
+
+#[event]
+pub struct OutflowLimitsProposedEvent {
+    pub daily_limit: u64,
+    pub monthly_limit: u64,
+    pub apply_after: i64,
+}
+
+#[event]
+pub struct OutflowLimitsUpdatedEvent {
+    pub daily_limit: u64,
+    pub monthly_limit: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeOutflowLimiter<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<OutflowLimiter>(),
+        seeds = [b"outflow_limiter".as_ref()],
+        bump
+    )]
+    pub outflow_limiter: Account<'info, OutflowLimiter>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateOutflowLimiter<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"outflow_limiter".as_ref()],
+        bump = outflow_limiter.bump,
+    )]
+    pub outflow_limiter: Account<'info, OutflowLimiter>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Creates the outflow limiter.
+pub fn initialize_outflow_limiter(
+    ctx: Context<InitializeOutflowLimiter>,
+    daily_limit: u64,
+    monthly_limit: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let bump = ctx.bumps.outflow_limiter;
+    ctx.accounts
+        .outflow_limiter
+        .set_up(daily_limit, monthly_limit, bump, now)
+}
+
+/// Changes the outflow limits, see `OutflowLimiter::propose_limits`.
+pub fn propose_outflow_limits(
+    ctx: Context<UpdateOutflowLimiter>,
+    daily_limit: u64,
+    monthly_limit: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts
+        .outflow_limiter
+        .propose_limits(daily_limit, monthly_limit, now)
+}
+
+/// Applies the raised limits once the timelock has passed.
+pub fn apply_outflow_limits(ctx: Context<UpdateOutflowLimiter>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.outflow_limiter.apply_limits(now)
+}
@@ -2,10 +2,18 @@ use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock::Clock;
 
 
-use anchor_spl::token_interface::{TokenAccount,Mint};
-use anchor_spl::token_2022::{transfer_checked, Token2022 as Token, TransferChecked};
+use anchor_spl::token_interface::{burn, Burn, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use charcoin_fee_hook::FeeAccumulator;
+
+use crate::transfer::transfer_checked_with_hook;
+use crate::burn_policy::split_marketing;
+use crate::distribution::share;
+use crate::{
+    BurnLedger, BurnPolicy, BurnSource, BuybackBurnEvent, ConfigAccount, CustomError, DistributionConfig, FeeRelease, FeeStream,
+    MarketingVesting, OutflowLimiter, Treasury,
+};
 
-use crate::ConfigAccount;
 #[event]
 pub struct MarketingFundDistributionEvent {
     pub marketing_wallet_1_amount: u64,
@@ -23,97 +31,179 @@ pub struct DistributeMarketingFunds<'info> {
     )]
     pub config_account: Account<'info, ConfigAccount>,
     /// will use https://squads.xyz/ for multi sig
+    /// Authority allowed to trigger the distribution.
     #[account(
-        mut,
-        constraint = config_account.config.treasury_authority == signer1.key() // Ensure the signer is the admin
+        constraint = config_account.config.treasury_authority == signer1.key() // Ensure the signer is the treasury authority
     )]
     pub signer1: Signer<'info>,
 
-    /// This is the source token account from which funds are withdrawn. Its validity is managed by the token program.
-    #[account(mut,
-        constraint = source_ata.mint == config_account.config.char_token_mint, // Ensure the mint matches the config
-        constraint = source_ata.owner == config_account.config.treasury_authority// Ensure the owner matches the marketing wallet
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
     )]
-    pub source_ata: InterfaceAccount<'info, TokenAccount>,
-    /// Destination token account for Marketing Wallet 1 funds.
+    pub treasury: Account<'info, Treasury>,
+    ///  Treasury vault from which funds are withdrawn.
     #[account(
         mut,
-        constraint = dest_wallet1_ata.owner == config_account.config.marketing_wallet_1 ,// Ensure the owner matches the marketing wallet
-        constraint = dest_wallet1_ata.mint == config_account.config.char_token_mint
-
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump = treasury.vault_bump,
     )]
-    pub dest_wallet1_ata: InterfaceAccount<'info, TokenAccount>,
-    /// Destination token  account for Marketing Wallet 2 funds.
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
+    /// Vesting stream of Marketing Wallet 1.
     #[account(
         mut,
-        constraint = dest_wallet2_ata.owner == config_account.config.marketing_wallet_2,// Ensure the owner matches the marketing wallet
-        constraint = dest_wallet2_ata.mint == config_account.config.char_token_mint
+        seeds = [b"marketing_vesting".as_ref(), config_account.config.marketing_wallet_1.as_ref()],
+        bump = marketing_vesting_1.bump,
     )]
-    pub dest_wallet2_ata: InterfaceAccount<'info, TokenAccount>,
+    pub marketing_vesting_1: Box<Account<'info, MarketingVesting>>,
+    /// Escrow receiving the Marketing Wallet 1 funds.
+    #[account(mut, address = marketing_vesting_1.escrow)]
+    pub marketing_escrow_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Vesting stream of Marketing Wallet 2.
     #[account(
         mut,
-        constraint = death_wallet_ata.owner == config_account.config.death_wallet,// Ensure the owner matches the marketing wallet
-        constraint = death_wallet_ata.mint == config_account.config.char_token_mint,// Ensure the owner matches the marketing wallet
+        seeds = [b"marketing_vesting".as_ref(), config_account.config.marketing_wallet_2.as_ref()],
+        bump = marketing_vesting_2.bump,
     )]
-    pub death_wallet_ata: InterfaceAccount<'info, TokenAccount>,
+    pub marketing_vesting_2: Box<Account<'info, MarketingVesting>>,
+    /// Escrow receiving the Marketing Wallet 2 funds.
+    #[account(mut, address = marketing_vesting_2.escrow)]
+    pub marketing_escrow_2: Box<InterfaceAccount<'info, TokenAccount>>,
        #[account(mut,
-    constraint = mint.key() == config_account.config.char_token_mint)]
+    constraint = mint.key() == config_account.config.char_token_mint,
+    constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
     pub mint: InterfaceAccount<'info, Mint>,
-    pub token_program: Program<'info, Token>,
+    /// Fees recorded by the transfer hook.
+    #[account(constraint = fee_accumulator.mint == config_account.config.char_token_mint)]
+    pub fee_accumulator: Account<'info, FeeAccumulator>,
+    #[account(
+        mut,
+        seeds = [b"fee_release".as_ref()],
+        bump = fee_release.bump,
+    )]
+    pub fee_release: Account<'info, FeeRelease>,
+    #[account(
+        seeds = [b"distribution_config".as_ref()],
+        bump = distribution_config.bump,
+    )]
+    pub distribution_config: Account<'info, DistributionConfig>,
+    #[account(
+        mut,
+        seeds = [b"outflow_limiter".as_ref()],
+        bump = outflow_limiter.bump,
+    )]
+    pub outflow_limiter: Account<'info, OutflowLimiter>,
+    #[account(
+        mut,
+        seeds = [b"burn_ledger".as_ref()],
+        bump = burn_ledger.bump,
+    )]
+    pub burn_ledger: Box<Account<'info, BurnLedger>>,
+    #[account(
+        seeds = [b"burn_policy".as_ref()],
+        bump = burn_policy.bump,
+    )]
+    pub burn_policy: Box<Account<'info, BurnPolicy>>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
-/// Distribute marketing funds according to the following split:
-/// - Marketing Wallet 1: 42.5%
-/// - Marketing Wallet 2: 42.5%
-/// - Death Wallet (Burn): 15%
-pub fn distribute_marketing_funds(
-    ctx: Context<DistributeMarketingFunds>,
-    total_amount: u64,
+/// Distribute marketing funds according to the marketing splits of the distribution config
+/// (by default 42.5% to each marketing wallet and 15% burned), or the burn share of the
+/// burn policy when it is enabled. The marketing
+/// wallet shares go to their vesting escrows, the death wallet share is burned.
+pub fn distribute_marketing_funds<'info>(
+    ctx: Context<'_, '_, 'info, 'info, DistributeMarketingFunds<'info>>,
 ) -> Result<()> {
-    // let wallet = &mut ctx.accounts.marketing_wallet;
-    let total = total_amount;
+    // Marketing share of the fees collected since the last distribution
+    let collected = ctx
+        .accounts
+        .fee_release
+        .take_unreleased(FeeStream::Marketing, ctx.accounts.fee_accumulator.total_fees)?;
+    let splits = ctx.accounts.distribution_config.splits;
+    let total = share(collected, splits.marketing)?;
     // Calculate distribution amounts.
-    let amount_wallet1 = (total * 425) / 1000; // 42.5%
-    let amount_wallet2 = (total * 425) / 1000; // 42.5%
-    let amount_death = (total * 150) / 1000; // 15%
+    let now = Clock::get()?.unix_timestamp;
+    let (amount_wallet1, amount_wallet2, amount_death) = split_marketing(
+        total,
+        &splits,
+        &ctx.accounts.burn_policy,
+        ctx.accounts.mint.supply,
+        now,
+    )?;
 
-    // Execute transfers from source to destination accounts.
-    let transfer_ctx1 = CpiContext::new(
+    // Every treasury outflow counts against the outflow limits
+    ctx.accounts.outflow_limiter.record_outflow(
+        amount_wallet1 + amount_wallet2 + amount_death,
+        now,
+    )?;
+
+    let treasury_seeds: &[&[u8]] = &[b"treasury", &[ctx.accounts.treasury.bump]];
+    let signer = &[treasury_seeds];
+
+    // Execute transfers from the treasury vault to destination accounts.
+    let transfer_ctx1 = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
         TransferChecked {
-            from: ctx.accounts.source_ata.to_account_info(),
-            to: ctx.accounts.dest_wallet1_ata.to_account_info(),
+            from: ctx.accounts.treasury_vault.to_account_info(),
+            to: ctx.accounts.marketing_escrow_1.to_account_info(),
             mint:ctx.accounts.mint.to_account_info(),
-            // use signer1; in production.
-            authority: ctx.accounts.signer1.to_account_info(),
+            authority: ctx.accounts.treasury.to_account_info(),
         },
+        signer,
     );
-    transfer_checked(transfer_ctx1, amount_wallet1,ctx.accounts.mint.decimals)?;
+    transfer_checked_with_hook(
+        transfer_ctx1.with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        amount_wallet1,ctx.accounts.mint.decimals)?;
 
-    let transfer_ctx2 = CpiContext::new(
+    let transfer_ctx2 = CpiContext::new_with_signer(
         ctx.accounts.token_program.to_account_info(),
         TransferChecked {
-            from: ctx.accounts.source_ata.to_account_info(),
-            to: ctx.accounts.dest_wallet2_ata.to_account_info(),
-            authority: ctx.accounts.signer1.to_account_info(),
+            from: ctx.accounts.treasury_vault.to_account_info(),
+            to: ctx.accounts.marketing_escrow_2.to_account_info(),
+            authority: ctx.accounts.treasury.to_account_info(),
             mint:ctx.accounts.mint.to_account_info(),
 
         },
+        signer,
     );
-    transfer_checked(transfer_ctx2, amount_wallet2,ctx.accounts.mint.decimals)?;
+    transfer_checked_with_hook(
+        transfer_ctx2.with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        amount_wallet2,ctx.accounts.mint.decimals)?;
 
-    // (Optionally, you might burn the death wallet funds via a separate burn function.)
-    let transfer_death_wallet = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        TransferChecked {
-            from: ctx.accounts.source_ata.to_account_info(),
-            to: ctx.accounts.death_wallet_ata.to_account_info(),
-            authority: ctx.accounts.signer1.to_account_info(),
-                        mint:ctx.accounts.mint.to_account_info(),
+    // Marketing wallet shares vest in their escrows
+    ctx.accounts.marketing_escrow_1.reload()?;
+    ctx.accounts.marketing_escrow_2.reload()?;
+    ctx.accounts
+        .marketing_vesting_1
+        .deposit_escrow_balance(ctx.accounts.marketing_escrow_1.amount, now)?;
+    ctx.accounts
+        .marketing_vesting_2
+        .deposit_escrow_balance(ctx.accounts.marketing_escrow_2.amount, now)?;
 
+    // The death wallet share is burned straight from the treasury vault
+    let burn_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        Burn {
+            mint: ctx.accounts.mint.to_account_info(),
+            from: ctx.accounts.treasury_vault.to_account_info(),
+            authority: ctx.accounts.treasury.to_account_info(),
         },
+        signer,
     );
-    transfer_checked(transfer_death_wallet, amount_death,ctx.accounts.mint.decimals)?;
+    burn(burn_ctx, amount_death)?;
+    let tracker = &mut ctx.accounts.config_account.config;
+    tracker.total_burned = tracker
+        .total_burned
+        .checked_add(amount_death)
+        .ok_or(CustomError::MathError)?;
+    ctx.accounts
+        .burn_ledger
+        .record(BurnSource::MarketingShare, amount_death, now)?;
+    emit!(BuybackBurnEvent {
+        tokens_bought: amount_death,
+        new_total_burned: tracker.total_burned,
+        timestamp: now as u64,
+    });
 
     // Reset the wallet's total funds after distribution.
     // wallet.total_funds = 0;
@@ -127,7 +217,7 @@ pub fn distribute_marketing_funds(
         timestamp: clock.unix_timestamp as u64,
     });
     msg!(
-        "Distributed funds: {} to Marketing Wallet 1, {} to Marketing Wallet 2, {} for Death Wallet",
+        "Distributed funds: {} vesting for Marketing Wallet 1, {} vesting for Marketing Wallet 2, {} burned",
         amount_wallet1,
         amount_wallet2,
         amount_death
@@ -0,0 +1,584 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::attestation::{is_unattested, require_attested};
+use crate::governance::{governance_weight, lock_governance_stake};
+use crate::transfer::transfer_checked_with_hook;
+use crate::{
+    Attestation, Charity, ConfigAccount, CustomError, DonationVault, GovernanceConfig,
+    UserStakeInfo, VotingRound,
+};
+
+/// Max number of milestones of a charity.
+pub const MAX_MILESTONES: usize = 8;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub enum MilestoneStatus {
+    Pending,
+    Submitted,
+    Released,
+    Reclaimed,
+}
+
+/// Milestone as registered by the charity admin.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct MilestoneInput {
+    pub amount: u64,
+    pub evidence_hash: [u8; 32], // hash of the evidence the charity commits to deliver
+    pub deadline: i64,           // last time to submit the proof
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct Milestone {
+    pub amount: u64,
+    pub evidence_hash: [u8; 32],
+    pub deadline: i64,
+    pub proof_hash: [u8; 32],
+    pub submitted_at: i64,
+    pub approvals: u64,  // staked weight approving the proof
+    pub objections: u64, // staked weight objecting during the objection window
+    pub status: MilestoneStatus,
+}
+
+impl Milestone {
+    fn objection_window_open(&self, governance_config: &GovernanceConfig, now: i64) -> bool {
+        now < self.submitted_at + governance_config.objection_window
+    }
+
+    /// Votes on a proof close at the deadline, or at the end of the objection
+    /// window when the proof came just before it.
+    fn voting_closes_at(&self, governance_config: &GovernanceConfig) -> i64 {
+        self.deadline
+            .max(self.submitted_at + governance_config.objection_window)
+    }
+
+    /// A submitted milestone is released once approved, or once the objection window
+    /// has passed without enough objections.
+    pub fn releasable(&self, governance_config: &GovernanceConfig, now: i64) -> bool {
+        self.status == MilestoneStatus::Submitted
+            && (self.approvals >= governance_config.approval_quorum
+                || (!self.objection_window_open(governance_config, now)
+                    && self.objections < governance_config.objection_threshold))
+    }
+
+    /// Past the deadline a milestone without proof, or with a proof objected to and not
+    /// approved, goes back to the donation pool.
+    pub fn reclaimable(&self, governance_config: &GovernanceConfig, now: i64) -> bool {
+        now > self.deadline
+            && match self.status {
+                MilestoneStatus::Pending => true,
+                MilestoneStatus::Submitted => {
+                    !self.objection_window_open(governance_config, now)
+                        && !self.releasable(governance_config, now)
+                }
+                _ => false,
+            }
+    }
+}
+
+/// Round payout of a charity released in tranches. The payout stays in the donation
+/// vault until each milestone is released.
+#[account]
+pub struct MilestonePlan {
+    pub charity: Pubkey,
+    pub round: Pubkey,
+    pub total_amount: u64,
+    pub released: u64,
+    pub reclaimed: u64, // returned to the donation pool
+    pub milestones: [Milestone; MAX_MILESTONES],
+    pub milestone_count: u8,
+    pub bump: u8,
+}
+
+impl MilestonePlan {
+    fn milestone(&mut self, index: u8) -> Result<&mut Milestone> {
+        require!(
+            index < self.milestone_count,
+            CustomError::InvalidMilestoneIndex
+        );
+        Ok(&mut self.milestones[index as usize])
+    }
+
+    /// Whether a milestone is still waiting for its release.
+    pub fn has_unreleased(&self) -> bool {
+        self.milestones[..self.milestone_count as usize]
+            .iter()
+            .any(|m| {
+                matches!(
+                    m.status,
+                    MilestoneStatus::Pending | MilestoneStatus::Submitted
+                )
+            })
+    }
+}
+
+/// Vote of a staker on a milestone proof.
+#[account]
+pub struct MilestoneVote {
+    pub plan: Pubkey,
+    pub index: u8,
+    pub voter: Pubkey,
+    pub weight: u64,
+    pub approve: bool,
+    pub bump: u8,
+}
+
+#[event]
+pub struct MilestoneReleasedEvent {
+    pub charity: Pubkey,
+    pub index: u8,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MilestoneReclaimedEvent {
+    pub charity: Pubkey,
+    pub index: u8,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u64, charity_id: u64)]
+pub struct RegisterMilestones<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [b"voting_round".as_ref(), round_id.to_le_bytes().as_ref()],
+        bump = voting_round.bump,
+    )]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    #[account(
+        mut,
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = charity.admin == charity_admin.key()
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    #[account(
+        init,
+        payer = charity_admin,
+        space = 8 + std::mem::size_of::<MilestonePlan>(),
+        seeds = [b"milestone_plan".as_ref(), charity.key().as_ref()],
+        bump
+    )]
+    pub milestone_plan: Box<Account<'info, MilestonePlan>>,
+    #[account(
+        seeds = [b"governance_config".as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Box<Account<'info, GovernanceConfig>>,
+    #[account(mut)]
+    pub charity_admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64)]
+pub struct SubmitMilestoneProof<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = charity.admin == charity_admin.key()
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    #[account(
+        mut,
+        seeds = [b"milestone_plan".as_ref(), charity.key().as_ref()],
+        bump = milestone_plan.bump,
+    )]
+    pub milestone_plan: Box<Account<'info, MilestonePlan>>,
+    pub charity_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64, index: u8)]
+pub struct VoteMilestone<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    #[account(
+        mut,
+        seeds = [b"milestone_plan".as_ref(), charity.key().as_ref()],
+        bump = milestone_plan.bump,
+    )]
+    pub milestone_plan: Box<Account<'info, MilestonePlan>>,
+    #[account(
+        seeds = [b"governance_config".as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Box<Account<'info, GovernanceConfig>>,
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + std::mem::size_of::<MilestoneVote>(),
+        seeds = [b"milestone_vote".as_ref(), milestone_plan.key().as_ref(), &[index], voter.key().as_ref()],
+        bump
+    )]
+    pub milestone_vote: Box<Account<'info, MilestoneVote>>,
+    #[account(
+        mut,
+        seeds = [b"user", voter.key().as_ref()],
+        bump = user.bump
+    )]
+    pub user: Box<Account<'info, UserStakeInfo>>,
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64)]
+pub struct ReleaseMilestone<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    #[account(
+        mut,
+        seeds = [b"milestone_plan".as_ref(), charity.key().as_ref()],
+        bump = milestone_plan.bump,
+    )]
+    pub milestone_plan: Box<Account<'info, MilestonePlan>>,
+    #[account(mut, address = milestone_plan.round)]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    /// Only required when the round requires attestations.
+    #[account(
+        seeds = [b"attestation".as_ref(), charity.wallet.as_ref()],
+        bump = attestation.bump,
+    )]
+    pub attestation: Option<Account<'info, Attestation>>,
+    #[account(
+        seeds = [b"governance_config".as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Box<Account<'info, GovernanceConfig>>,
+    /// Donation vault of the category of the round.
+    #[account(
+        mut,
+        seeds = [b"donation_vault".as_ref(), &[voting_round.category as u8]],
+        bump = donation_vault.bump,
+    )]
+    pub donation_vault: Box<Account<'info, DonationVault>>,
+    #[account(
+        mut,
+        address = donation_vault.vault,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = charity_token_account.mint == config_account.config.char_token_mint,
+        constraint = charity_token_account.owner == charity.wallet
+    )]
+    pub charity_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(charity_id: u64)]
+pub struct ReclaimMilestone<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    #[account(
+        mut,
+        seeds = [b"milestone_plan".as_ref(), charity.key().as_ref()],
+        bump = milestone_plan.bump,
+    )]
+    pub milestone_plan: Box<Account<'info, MilestonePlan>>,
+    #[account(
+        seeds = [b"governance_config".as_ref()],
+        bump = governance_config.bump,
+    )]
+    pub governance_config: Box<Account<'info, GovernanceConfig>>,
+    #[account(address = milestone_plan.round)]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    /// Donation vault of the category of the round.
+    #[account(
+        mut,
+        seeds = [b"donation_vault".as_ref(), &[voting_round.category as u8]],
+        bump = donation_vault.bump,
+    )]
+    pub donation_vault: Box<Account<'info, DonationVault>>,
+    /// Only required to reclaim the milestones of a charity no longer attested.
+    #[account(
+        seeds = [b"attestation".as_ref(), charity.wallet.as_ref()],
+        bump = attestation.bump,
+    )]
+    pub attestation: Option<Account<'info, Attestation>>,
+    /// Only required to reclaim the milestones of a charity no longer attested.
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Option<Signer<'info>>,
+}
+
+/// Splits the round payout of a winning charity into milestones. The amounts must add
+/// up to the payout, which is then no longer paid at once by `pay_charity`. Deadlines are
+/// at most `max_milestone_duration` of the governance config away.
+pub fn register_milestones(
+    ctx: Context<RegisterMilestones>,
+    _round_id: u64,
+    _charity_id: u64,
+    milestones: Vec<MilestoneInput>,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let max_deadline = now
+        .checked_add(ctx.accounts.governance_config.max_milestone_duration)
+        .ok_or(CustomError::MathError)?;
+    let charity = &mut ctx.accounts.charity;
+    require!(!charity.paid_out, CustomError::CharityAlreadyPaid);
+    require!(!charity.is_removed(), CustomError::CharityRemoved);
+    let total_amount = ctx.accounts.voting_round.winner_payout(&charity.key())?;
+    let sum = milestones
+        .iter()
+        .try_fold(0u64, |sum, m| sum.checked_add(m.amount))
+        .ok_or(CustomError::MathError)?;
+    require!(
+        !milestones.is_empty()
+            && milestones.len() <= MAX_MILESTONES
+            && sum == total_amount
+            && milestones
+                .iter()
+                .all(|m| m.amount > 0 && m.deadline > now && m.deadline <= max_deadline),
+        CustomError::InvalidMilestones
+    );
+
+    let plan = &mut ctx.accounts.milestone_plan;
+    plan.charity = charity.key();
+    plan.round = ctx.accounts.voting_round.key();
+    plan.total_amount = total_amount;
+    for (milestone, input) in plan.milestones.iter_mut().zip(milestones.iter()) {
+        *milestone = Milestone {
+            amount: input.amount,
+            evidence_hash: input.evidence_hash,
+            deadline: input.deadline,
+            proof_hash: [0; 32],
+            submitted_at: 0,
+            approvals: 0,
+            objections: 0,
+            status: MilestoneStatus::Pending,
+        };
+    }
+    plan.milestone_count = milestones.len() as u8;
+    plan.bump = ctx.bumps.milestone_plan;
+    // The payout is now committed to the milestones
+    charity.paid_out = true;
+    Ok(())
+}
+
+/// Submits the proof of a milestone, which opens the objection window.
+pub fn submit_milestone_proof(
+    ctx: Context<SubmitMilestoneProof>,
+    _charity_id: u64,
+    index: u8,
+    proof_hash: [u8; 32],
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let milestone = ctx.accounts.milestone_plan.milestone(index)?;
+    require!(
+        milestone.status == MilestoneStatus::Pending,
+        CustomError::MilestoneNotPending
+    );
+    require!(
+        now <= milestone.deadline,
+        CustomError::MilestoneDeadlinePassed
+    );
+    milestone.proof_hash = proof_hash;
+    milestone.submitted_at = now;
+    milestone.status = MilestoneStatus::Submitted;
+    Ok(())
+}
+
+/// Approves or objects to a milestone proof with the staked amount of the voter, who must
+/// be eligible to vote on charities. Objections are only counted during the objection window,
+/// and the stake of the voter stays locked until the votes on the proof close.
+pub fn vote_milestone(
+    ctx: Context<VoteMilestone>,
+    _charity_id: u64,
+    index: u8,
+    approve: bool,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let weight = governance_weight(&ctx.accounts.config_account, &ctx.accounts.user)?;
+    let governance_config = &ctx.accounts.governance_config;
+    let milestone = ctx.accounts.milestone_plan.milestone(index)?;
+    require!(
+        milestone.status == MilestoneStatus::Submitted,
+        CustomError::MilestoneNotSubmitted
+    );
+    let closes_at = milestone.voting_closes_at(governance_config);
+    require!(now <= closes_at, CustomError::MilestoneVotingClosed);
+    lock_governance_stake(&mut ctx.accounts.user, closes_at);
+    if approve {
+        milestone.approvals = milestone
+            .approvals
+            .checked_add(weight)
+            .ok_or(CustomError::MathError)?;
+    } else {
+        require!(
+            milestone.objection_window_open(governance_config, now),
+            CustomError::ObjectionWindowClosed
+        );
+        milestone.objections = milestone
+            .objections
+            .checked_add(weight)
+            .ok_or(CustomError::MathError)?;
+    }
+
+    let milestone_vote = &mut ctx.accounts.milestone_vote;
+    milestone_vote.plan = ctx.accounts.milestone_plan.key();
+    milestone_vote.index = index;
+    milestone_vote.voter = ctx.accounts.voter.key();
+    milestone_vote.weight = weight;
+    milestone_vote.approve = approve;
+    milestone_vote.bump = ctx.bumps.milestone_vote;
+    Ok(())
+}
+
+/// Pays a released milestone from the donation vault of the round category to the charity wallet.
+pub fn release_milestone<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ReleaseMilestone<'info>>,
+    _charity_id: u64,
+    index: u8,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require_attested(
+        &ctx.accounts.voting_round,
+        &ctx.accounts.charity,
+        ctx.accounts.attestation.as_deref(),
+        now,
+    )?;
+    require!(
+        !ctx.accounts.charity.is_removed(),
+        CustomError::CharityRemoved
+    );
+    let governance_config = &ctx.accounts.governance_config;
+    let plan = &mut ctx.accounts.milestone_plan;
+    let milestone = plan.milestone(index)?;
+    require!(
+        milestone.releasable(governance_config, now),
+        CustomError::MilestoneNotReleasable
+    );
+    milestone.status = MilestoneStatus::Released;
+    let amount = milestone.amount;
+    ctx.accounts.donation_vault.release(amount)?;
+    plan.released = plan
+        .released
+        .checked_add(amount)
+        .ok_or(CustomError::MathError)?;
+    let voting_round = &mut ctx.accounts.voting_round;
+    voting_round.paid_total = voting_round
+        .paid_total
+        .checked_add(amount)
+        .ok_or(CustomError::MathError)?;
+
+    let seeds: &[&[u8]] = &[
+        b"donation_vault",
+        &[ctx.accounts.donation_vault.category as u8],
+        &[ctx.accounts.donation_vault.bump],
+    ];
+    let signer = &[seeds];
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                to: ctx.accounts.charity_token_account.to_account_info(),
+                authority: ctx.accounts.donation_vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            },
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        amount,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    emit!(MilestoneReleasedEvent {
+        charity: ctx.accounts.charity.key(),
+        index,
+        amount,
+        timestamp: now as u64,
+    });
+    Ok(())
+}
+
+/// Returns a milestone missed by the charity, or any unreleased milestone of a removed
+/// charity, to the donation pool. The admin can also return the unreleased milestones
+/// of a charity whose attestation was revoked or expired. The amount never left the
+/// donation vault, it is no longer committed and becomes available to the next round
+/// allocations.
+pub fn reclaim_milestone(
+    ctx: Context<ReclaimMilestone>,
+    _charity_id: u64,
+    index: u8,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let governance_config = &ctx.accounts.governance_config;
+    let plan = &mut ctx.accounts.milestone_plan;
+    let milestone = plan.milestone(index)?;
+    // Unreleased milestones of a removed or unattested charity go back to the pool at once
+    let unattested = ctx.accounts.admin.is_some()
+        && is_unattested(
+            &ctx.accounts.voting_round,
+            ctx.accounts.attestation.as_ref(),
+            now,
+        );
+    let removed = (ctx.accounts.charity.is_removed() || unattested)
+        && matches!(
+            milestone.status,
+            MilestoneStatus::Pending | MilestoneStatus::Submitted
+        );
+    require!(
+        removed || milestone.reclaimable(governance_config, now),
+        CustomError::MilestoneNotReclaimable
+    );
+    milestone.status = MilestoneStatus::Reclaimed;
+    let amount = milestone.amount;
+    ctx.accounts.donation_vault.release(amount)?;
+    plan.reclaimed = plan
+        .reclaimed
+        .checked_add(amount)
+        .ok_or(CustomError::MathError)?;
+
+    emit!(MilestoneReclaimedEvent {
+        charity: ctx.accounts.charity.key(),
+        index,
+        amount,
+        timestamp: now as u64,
+    });
+    Ok(())
+}
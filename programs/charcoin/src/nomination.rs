@@ -0,0 +1,691 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::governance::governance_weight;
+use crate::round::add_to_round;
+use crate::transfer::transfer_checked_with_hook;
+use crate::{
+    Attestation, Charity, CharityCategory, ConfigAccount, CustomError, DonationVault,
+    UserStakeInfo, VotingRound, MAX_CHARITY_TITLE_LEN,
+};
+
+/// Parameters of the community nominations. Nominators post `bond_amount` CHAR, which
+/// is refunded when the nomination gathers `endorsement_threshold` staked CHAR of
+/// endorsements within `nomination_period`, and goes to the donation pool otherwise.
+/// An endorsed nomination the admin has not accepted within `acceptance_period` after
+/// the nomination phase can have its bond refunded by anyone.
+#[account]
+pub struct NominationConfig {
+    pub bond_amount: u64,
+    pub endorsement_threshold: u64,
+    pub nomination_period: i64,
+    pub acceptance_period: i64,
+    pub next_nomination_id: u64,
+    pub bond_vault: Pubkey, // CHAR account holding the bonds
+    pub bond_vault_bump: u8,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub enum NominationStatus {
+    Open,
+    Accepted,
+    Rejected,
+    Lapsed, // endorsed but not accepted in time, the bond was refunded
+}
+
+/// Charity nominated by a staker.
+#[account]
+pub struct Nomination {
+    pub id: u64,
+    pub nominator: Pubkey,
+    pub title: String,
+    pub wallet: Pubkey,
+    pub category: CharityCategory,
+    pub bond: u64, // bond received in the bond vault
+    pub created_at: i64,
+    pub ends_at: i64,      // end of the nomination phase
+    pub accept_by: i64,    // end of the acceptance by the admin
+    pub endorsements: u64, // staked CHAR of the endorsers
+    pub endorsement_count: u64,
+    pub status: NominationStatus,
+    pub charity: Pubkey, // charity registered from the nomination, default if none
+    pub bump: u8,
+}
+
+impl Nomination {
+    pub fn space(title: &str) -> usize {
+        8 + // discriminator
+        8 + // id
+        32 + // nominator
+        4 + title.len() + // title
+        32 + // wallet
+        1 + // category
+        8 + // bond
+        8 + // created_at
+        8 + // ends_at
+        8 + // accept_by
+        8 + // endorsements
+        8 + // endorsement_count
+        1 + // status
+        32 + // charity
+        1 // bump
+    }
+}
+
+/// Endorsement of a nomination by a staker.
+#[account]
+pub struct Endorsement {
+    pub nomination: Pubkey,
+    pub endorser: Pubkey,
+    pub weight: u64,
+    pub bump: u8,
+}
+
+#[event]
+pub struct CharityNominatedEvent {
+    pub nomination_id: u64,
+    pub nominator: Pubkey,
+    pub title: String,
+    pub bond: u64,
+    pub ends_at: i64,
+}
+
+#[event]
+pub struct NominationResolvedEvent {
+    pub nomination_id: u64,
+    pub accepted: bool,
+    pub endorsements: u64,
+    pub charity: Pubkey,
+    pub bond: u64, // refunded when accepted, sent to the donation pool otherwise
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct NominationBondRefundedEvent {
+    pub nomination_id: u64,
+    pub nominator: Pubkey,
+    pub bond: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeNominationConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<NominationConfig>(),
+        seeds = [b"nomination_config".as_ref()],
+        bump
+    )]
+    pub nomination_config: Account<'info, NominationConfig>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = mint,
+        token::authority = nomination_config,
+        token::token_program = token_program,
+        seeds = [b"nomination_bond_vault".as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub bond_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateNominationConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"nomination_config".as_ref()],
+        bump = nomination_config.bump,
+    )]
+    pub nomination_config: Account<'info, NominationConfig>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(title: String)]
+pub struct NominateCharity<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [b"nomination_config".as_ref()],
+        bump = nomination_config.bump,
+    )]
+    pub nomination_config: Box<Account<'info, NominationConfig>>,
+    #[account(
+        init,
+        payer = nominator,
+        space = Nomination::space(&title),
+        seeds = [b"nomination".as_ref(), nomination_config.next_nomination_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub nomination: Box<Account<'info, Nomination>>,
+    #[account(
+        seeds = [b"user", nominator.key().as_ref()],
+        bump = user.bump
+    )]
+    pub user: Box<Account<'info, UserStakeInfo>>,
+    #[account(mut, address = nomination_config.bond_vault)]
+    pub bond_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = nominator_token_account.mint == config_account.config.char_token_mint,
+        constraint = nominator_token_account.owner == nominator.key()
+    )]
+    pub nominator_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub nominator: Signer<'info>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(nomination_id: u64)]
+pub struct EndorseNomination<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [b"nomination".as_ref(), nomination_id.to_le_bytes().as_ref()],
+        bump = nomination.bump,
+    )]
+    pub nomination: Box<Account<'info, Nomination>>,
+    #[account(
+        init,
+        payer = endorser,
+        space = 8 + std::mem::size_of::<Endorsement>(),
+        seeds = [b"endorsement".as_ref(), nomination.key().as_ref(), endorser.key().as_ref()],
+        bump
+    )]
+    pub endorsement: Box<Account<'info, Endorsement>>,
+    #[account(
+        seeds = [b"user", endorser.key().as_ref()],
+        bump = user.bump
+    )]
+    pub user: Box<Account<'info, UserStakeInfo>>,
+    #[account(mut)]
+    pub endorser: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(nomination_id: u64, round_id: u64)]
+pub struct AcceptNomination<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [b"nomination_config".as_ref()],
+        bump = nomination_config.bump,
+    )]
+    pub nomination_config: Box<Account<'info, NominationConfig>>,
+    #[account(
+        mut,
+        seeds = [b"nomination".as_ref(), nomination_id.to_le_bytes().as_ref()],
+        bump = nomination.bump,
+    )]
+    pub nomination: Box<Account<'info, Nomination>>,
+    #[account(
+        init,
+        payer = admin,
+        space = Charity::space(&nomination.title, "", "", ""),
+        seeds=[b"charity".as_ref(), config_account.config.next_charity_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    /// Round the nominated charity competes in, none to register it outside rounds.
+    #[account(
+        mut,
+        seeds = [b"voting_round".as_ref(), round_id.to_le_bytes().as_ref()],
+        bump = voting_round.bump,
+    )]
+    pub voting_round: Option<Box<Account<'info, VotingRound>>>,
+    /// Only required when the round requires attestations.
+    #[account(
+        seeds = [b"attestation".as_ref(), nomination.wallet.as_ref()],
+        bump = attestation.bump,
+    )]
+    pub attestation: Option<Account<'info, Attestation>>,
+    #[account(mut, address = nomination_config.bond_vault)]
+    pub bond_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = nominator_token_account.mint == config_account.config.char_token_mint,
+        constraint = nominator_token_account.owner == nomination.nominator
+    )]
+    pub nominator_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(nomination_id: u64)]
+pub struct RejectNomination<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [b"nomination_config".as_ref()],
+        bump = nomination_config.bump,
+    )]
+    pub nomination_config: Box<Account<'info, NominationConfig>>,
+    #[account(
+        mut,
+        seeds = [b"nomination".as_ref(), nomination_id.to_le_bytes().as_ref()],
+        bump = nomination.bump,
+    )]
+    pub nomination: Box<Account<'info, Nomination>>,
+    #[account(mut, address = nomination_config.bond_vault)]
+    pub bond_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Donation vault of the category of the nomination.
+    #[account(
+        seeds = [b"donation_vault".as_ref(), &[nomination.category as u8]],
+        bump = donation_vault.bump,
+    )]
+    pub donation_vault: Box<Account<'info, DonationVault>>,
+    #[account(mut, address = donation_vault.vault)]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(nomination_id: u64)]
+pub struct RefundNominationBond<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [b"nomination_config".as_ref()],
+        bump = nomination_config.bump,
+    )]
+    pub nomination_config: Box<Account<'info, NominationConfig>>,
+    #[account(
+        mut,
+        seeds = [b"nomination".as_ref(), nomination_id.to_le_bytes().as_ref()],
+        bump = nomination.bump,
+    )]
+    pub nomination: Box<Account<'info, Nomination>>,
+    #[account(mut, address = nomination_config.bond_vault)]
+    pub bond_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = nominator_token_account.mint == config_account.config.char_token_mint,
+        constraint = nominator_token_account.owner == nomination.nominator
+    )]
+    pub nominator_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+fn set_nomination_config(
+    nomination_config: &mut NominationConfig,
+    bond_amount: u64,
+    endorsement_threshold: u64,
+    nomination_period: i64,
+    acceptance_period: i64,
+) -> Result<()> {
+    require!(
+        bond_amount > 0
+            && endorsement_threshold > 0
+            && nomination_period > 0
+            && acceptance_period > 0,
+        CustomError::InvalidNominationConfig
+    );
+    nomination_config.bond_amount = bond_amount;
+    nomination_config.endorsement_threshold = endorsement_threshold;
+    nomination_config.nomination_period = nomination_period;
+    nomination_config.acceptance_period = acceptance_period;
+    Ok(())
+}
+
+/// Creates the nomination config and the bond vault.
+pub fn initialize_nomination_config(
+    ctx: Context<InitializeNominationConfig>,
+    bond_amount: u64,
+    endorsement_threshold: u64,
+    nomination_period: i64,
+    acceptance_period: i64,
+) -> Result<()> {
+    let nomination_config = &mut ctx.accounts.nomination_config;
+    set_nomination_config(
+        nomination_config,
+        bond_amount,
+        endorsement_threshold,
+        nomination_period,
+        acceptance_period,
+    )?;
+    nomination_config.bond_vault = ctx.accounts.bond_vault.key();
+    nomination_config.bond_vault_bump = ctx.bumps.bond_vault;
+    nomination_config.bump = ctx.bumps.nomination_config;
+    Ok(())
+}
+
+/// Changes the bond, threshold and periods of the next nominations.
+pub fn update_nomination_config(
+    ctx: Context<UpdateNominationConfig>,
+    bond_amount: u64,
+    endorsement_threshold: u64,
+    nomination_period: i64,
+    acceptance_period: i64,
+) -> Result<()> {
+    set_nomination_config(
+        &mut ctx.accounts.nomination_config,
+        bond_amount,
+        endorsement_threshold,
+        nomination_period,
+        acceptance_period,
+    )
+}
+
+/// Nominates a charity, the nominator posts the bond.
+pub fn nominate_charity<'info>(
+    ctx: Context<'_, '_, 'info, 'info, NominateCharity<'info>>,
+    title: String,
+    wallet: Pubkey,
+    category: CharityCategory,
+) -> Result<()> {
+    governance_weight(&ctx.accounts.config_account, &ctx.accounts.user)?;
+    require!(
+        title.len() <= MAX_CHARITY_TITLE_LEN,
+        CustomError::CharityTitleTooLong
+    );
+    require!(wallet != Pubkey::default(), CustomError::InvalidArg);
+
+    let bond_before = ctx.accounts.bond_vault.amount;
+    transfer_checked_with_hook(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.nominator_token_account.to_account_info(),
+                to: ctx.accounts.bond_vault.to_account_info(),
+                authority: ctx.accounts.nominator.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        ctx.accounts.nomination_config.bond_amount,
+        ctx.accounts.mint.decimals,
+    )?;
+    // The bond held is what arrived after the transfer fee
+    ctx.accounts.bond_vault.reload()?;
+    let bond = ctx
+        .accounts
+        .bond_vault
+        .amount
+        .checked_sub(bond_before)
+        .ok_or(CustomError::MathError)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let nomination_config = &mut ctx.accounts.nomination_config;
+    let nomination = &mut ctx.accounts.nomination;
+    nomination.id = nomination_config.next_nomination_id;
+    nomination.nominator = ctx.accounts.nominator.key();
+    nomination.title = title;
+    nomination.wallet = wallet;
+    nomination.category = category;
+    nomination.bond = bond;
+    nomination.created_at = now;
+    nomination.ends_at = now + nomination_config.nomination_period;
+    nomination.accept_by = nomination.ends_at + nomination_config.acceptance_period;
+    nomination.status = NominationStatus::Open;
+    nomination.bump = ctx.bumps.nomination;
+    nomination_config.next_nomination_id += 1;
+
+    emit!(CharityNominatedEvent {
+        nomination_id: nomination.id,
+        nominator: nomination.nominator,
+        title: nomination.title.clone(),
+        bond,
+        ends_at: nomination.ends_at,
+    });
+    Ok(())
+}
+
+/// Endorses a nomination with the staked amount of the endorser, who must be eligible
+/// to vote on charities.
+pub fn endorse_nomination(ctx: Context<EndorseNomination>, _nomination_id: u64) -> Result<()> {
+    let weight = governance_weight(&ctx.accounts.config_account, &ctx.accounts.user)?;
+    let now = Clock::get()?.unix_timestamp;
+    let nomination = &mut ctx.accounts.nomination;
+    require!(
+        nomination.status == NominationStatus::Open && now <= nomination.ends_at,
+        CustomError::NominationClosed
+    );
+    nomination.endorsements = nomination
+        .endorsements
+        .checked_add(weight)
+        .ok_or(CustomError::MathError)?;
+    nomination.endorsement_count += 1;
+
+    let endorsement = &mut ctx.accounts.endorsement;
+    endorsement.nomination = nomination.key();
+    endorsement.endorser = ctx.accounts.endorser.key();
+    endorsement.weight = weight;
+    endorsement.bump = ctx.bumps.endorsement;
+    Ok(())
+}
+
+/// Checks the nomination phase is over and the nomination not resolved yet.
+fn require_resolvable(nomination: &Nomination, now: i64) -> Result<()> {
+    require!(
+        nomination.status == NominationStatus::Open,
+        CustomError::NominationAlreadyResolved
+    );
+    require!(
+        now > nomination.ends_at,
+        CustomError::NominationPhaseNotEnded
+    );
+    Ok(())
+}
+
+/// Registers an endorsed nomination as a charity and refunds the bond. The admin picks
+/// the open round of its category the charity joins. Without a round the charity is
+/// registered outside rounds and can be added to a later one with `add_charity_to_round`.
+/// The nominator becomes the charity admin.
+pub fn accept_nomination<'info>(
+    ctx: Context<'_, '_, 'info, 'info, AcceptNomination<'info>>,
+    _nomination_id: u64,
+    _round_id: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require_resolvable(&ctx.accounts.nomination, now)?;
+    require!(
+        ctx.accounts.nomination.endorsements
+            >= ctx.accounts.nomination_config.endorsement_threshold,
+        CustomError::EndorsementThresholdNotReached
+    );
+
+    let nomination = &mut ctx.accounts.nomination;
+    let charity = &mut ctx.accounts.charity;
+    charity.register(
+        ctx.accounts.config_account.config.next_charity_id,
+        nomination.title.clone(),
+        nomination.wallet,
+        nomination.category,
+        nomination.nominator,
+    )?;
+    ctx.accounts.config_account.config.next_charity_id += 1;
+    if let Some(voting_round) = ctx.accounts.voting_round.as_mut() {
+        add_to_round(
+            voting_round,
+            charity,
+            ctx.accounts.attestation.as_deref(),
+            now,
+        )?;
+    }
+    nomination.status = NominationStatus::Accepted;
+    nomination.charity = charity.key();
+
+    let seeds: &[&[u8]] = &[b"nomination_config", &[ctx.accounts.nomination_config.bump]];
+    let signer = &[seeds];
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.bond_vault.to_account_info(),
+                to: ctx.accounts.nominator_token_account.to_account_info(),
+                authority: ctx.accounts.nomination_config.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            },
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        ctx.accounts.nomination.bond,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    emit!(NominationResolvedEvent {
+        nomination_id: ctx.accounts.nomination.id,
+        accepted: true,
+        endorsements: ctx.accounts.nomination.endorsements,
+        charity: ctx.accounts.nomination.charity,
+        bond: ctx.accounts.nomination.bond,
+        timestamp: now as u64,
+    });
+    Ok(())
+}
+
+/// Closes a nomination below the endorsement threshold, its bond goes to the donation
+/// vault of its category.
+pub fn reject_nomination<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RejectNomination<'info>>,
+    _nomination_id: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require_resolvable(&ctx.accounts.nomination, now)?;
+    require!(
+        ctx.accounts.nomination.endorsements < ctx.accounts.nomination_config.endorsement_threshold,
+        CustomError::EndorsementThresholdReached
+    );
+    ctx.accounts.nomination.status = NominationStatus::Rejected;
+
+    let seeds: &[&[u8]] = &[b"nomination_config", &[ctx.accounts.nomination_config.bump]];
+    let signer = &[seeds];
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.bond_vault.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.nomination_config.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            },
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        ctx.accounts.nomination.bond,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    emit!(NominationResolvedEvent {
+        nomination_id: ctx.accounts.nomination.id,
+        accepted: false,
+        endorsements: ctx.accounts.nomination.endorsements,
+        charity: Pubkey::default(),
+        bond: ctx.accounts.nomination.bond,
+        timestamp: now as u64,
+    });
+    Ok(())
+}
+
+/// Refunds the bond of an endorsed nomination the admin did not accept within the
+/// acceptance period. Anyone can call it, the nomination can no longer be accepted.
+pub fn refund_nomination_bond<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RefundNominationBond<'info>>,
+    _nomination_id: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let nomination = &ctx.accounts.nomination;
+    require!(
+        nomination.status == NominationStatus::Open,
+        CustomError::NominationAlreadyResolved
+    );
+    require!(
+        nomination.endorsements >= ctx.accounts.nomination_config.endorsement_threshold,
+        CustomError::EndorsementThresholdNotReached
+    );
+    require!(
+        now > nomination.accept_by,
+        CustomError::NominationAcceptancePending
+    );
+    ctx.accounts.nomination.status = NominationStatus::Lapsed;
+
+    let seeds: &[&[u8]] = &[b"nomination_config", &[ctx.accounts.nomination_config.bump]];
+    let signer = &[seeds];
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.bond_vault.to_account_info(),
+                to: ctx.accounts.nominator_token_account.to_account_info(),
+                authority: ctx.accounts.nomination_config.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            },
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        ctx.accounts.nomination.bond,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    emit!(NominationBondRefundedEvent {
+        nomination_id: ctx.accounts.nomination.id,
+        nominator: ctx.accounts.nomination.nominator,
+        bond: ctx.accounts.nomination.bond,
+        timestamp: now as u64,
+    });
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use anchor_lang::prelude::*;
+
+use crate::{ConfigAccount, CustomError, UserStakeInfo};
+
+#[event]
+pub struct ReferralRegisteredEvent {
+    pub referee: Pubkey,
+    pub referrer: Pubkey,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct ReferralRewardPaidEvent {
+    pub referee: Pubkey,
+    pub referrer: Pubkey,
+    pub amount: u64,
+    pub total_paid: u64,
+    pub timestamp: u64,
+}
+
+/// Global settings of the referral program.
+#[account]
+pub struct ReferralConfig {
+    pub enabled: bool,
+    pub reward_bps: u16, // share of the referee's claimed reward paid to the referrer (500 = 5%)
+    pub budget: u64,     // maximum amount the referral program can ever pay out
+    pub total_paid: u64, // total amount paid to referrers so far
+    pub total_referrals: u64,
+    pub bump: u8,
+}
+
+impl ReferralConfig {
+    /// Referral bonus for a claimed reward, capped by what is left of the budget.
+    pub fn bonus_for(&self, reward_amount: u64) -> Result<u64> {
+        if !self.enabled {
+            return Ok(0);
+        }
+        let bonus = (reward_amount as u128)
+            .checked_mul(self.reward_bps as u128)
+            .ok_or(CustomError::MathError)?
+            .checked_div(10_000)
+            .ok_or(CustomError::MathError)? as u64;
+        let remaining_budget = self.budget.saturating_sub(self.total_paid);
+        Ok(bonus.min(remaining_budget))
+    }
+}
+
+/// Links a first time staker to the staker who referred them.
+pub fn register_referrer(
+    referral_config: &mut ReferralConfig,
+    user: &mut UserStakeInfo,
+    referrer_user: &mut UserStakeInfo,
+    user_authority: Pubkey,
+    referrer: Pubkey,
+) -> Result<()> {
+    require!(referral_config.enabled, CustomError::ReferralProgramDisabled);
+    require!(user.stake_count == 0, CustomError::ReferralOnlyOnFirstStake);
+    require!(referrer != user_authority, CustomError::SelfReferral);
+    // the referrer must be an active staker that was not referred by this user
+    require!(
+        referrer_user.authority == referrer && referrer_user.total_amount > 0,
+        CustomError::InvalidReferrer
+    );
+    require!(
+        referrer_user.referrer != user_authority,
+        CustomError::SelfReferral
+    );
+
+    user.referrer = referrer;
+    referrer_user.referral_count += 1;
+    referral_config.total_referrals += 1;
+
+    emit!(ReferralRegisteredEvent {
+        referee: user_authority,
+        referrer,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    Ok(())
+}
+
+pub fn set_referral_config(
+    ctx: Context<SetReferralConfig>,
+    enabled: bool,
+    reward_bps: u16,
+    budget: u64,
+) -> Result<()> {
+    require!(reward_bps <= 10_000, CustomError::InvalidArg);
+    let referral_config = &mut ctx.accounts.referral_config;
+    referral_config.enabled = enabled;
+    referral_config.reward_bps = reward_bps;
+    referral_config.budget = budget;
+    referral_config.bump = ctx.bumps.referral_config;
+    msg!(
+        "Referral program enabled: {}, reward: {}, budget: {}",
+        enabled,
+        reward_bps,
+        budget
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetReferralConfig<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + std::mem::size_of::<ReferralConfig>(),
+        seeds = [b"referral_config".as_ref()],
+        bump
+    )]
+    pub referral_config: Account<'info, ReferralConfig>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
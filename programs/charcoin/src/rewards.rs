@@ -1,8 +1,92 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token_2022::{transfer_checked, Token2022 as Token, TransferChecked};
-use anchor_spl::token_interface::{TokenAccount,Mint};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use charcoin_fee_hook::FeeAccumulator;
+
+use crate::transfer::transfer_checked_with_hook;
+use crate::distribution::share;
+use crate::{
+    Classification, ConfigAccount, CustomError, DistributionConfig, OutflowLimiter,
+    ReleaseSchedule, StakingPool, Treasury,
+};
+
+/// Distribution streams fed by the CHAR transfer fees.
+pub enum FeeStream {
+    Rewards,
+    Donations,
+    Staking,
+    Marketing,
+}
+
+/// Tracks how much of the fees recorded by the transfer hook each release has distributed.
+/// Once fees are settled by epoch, the per stream releases are closed.
+#[account]
+pub struct FeeRelease {
+    pub rewards_released: u64,
+    pub donations_released: u64,
+    pub staking_released: u64,
+    pub marketing_released: u64,
+    pub bump: u8,
+    pub settled_by_epoch: bool, // set by the first fee epoch settlement
+}
+
+impl FeeRelease {
+    /// Returns the fees collected since the last release of `stream` and marks them as released.
+    pub fn take_unreleased(&mut self, stream: FeeStream, total_fees: u64) -> Result<u64> {
+        require!(!self.settled_by_epoch, CustomError::FeesSettledByEpoch);
+        let released = match stream {
+            FeeStream::Rewards => &mut self.rewards_released,
+            FeeStream::Donations => &mut self.donations_released,
+            FeeStream::Staking => &mut self.staking_released,
+            FeeStream::Marketing => &mut self.marketing_released,
+        };
+        let amount = total_fees
+            .checked_sub(*released)
+            .ok_or(CustomError::MathError)?;
+        require!(amount > 0, CustomError::NoFeesToRelease);
+        *released = total_fees;
+        Ok(amount)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeRelease<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    /// Fees recorded by the transfer hook.
+    #[account(constraint = fee_accumulator.mint == config_account.config.char_token_mint)]
+    pub fee_accumulator: Account<'info, FeeAccumulator>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<FeeRelease>(),
+        seeds = [b"fee_release".as_ref()],
+        bump
+    )]
+    pub fee_release: Account<'info, FeeRelease>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
-use crate::{ConfigAccount, StakingPool};
+/// Starts the fee releases from the fees recorded so far.
+pub fn initialize_fee_release(ctx: Context<InitializeFeeRelease>) -> Result<()> {
+    let total_fees = ctx.accounts.fee_accumulator.total_fees;
+    let fee_release = &mut ctx.accounts.fee_release;
+    fee_release.rewards_released = total_fees;
+    fee_release.donations_released = total_fees;
+    fee_release.staking_released = total_fees;
+    fee_release.marketing_released = total_fees;
+    fee_release.bump = ctx.bumps.fee_release;
+    Ok(())
+}
 
 #[derive(Accounts)]
 pub struct ReleaseRewards<'info> {
@@ -13,14 +97,18 @@ pub struct ReleaseRewards<'info> {
         )]
     pub config_account: Account<'info, ConfigAccount>,
 
-    ///  Treasury token account holding funds to be distributed.
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Account<'info, Treasury>,
+    ///  Treasury vault holding funds to be distributed.
     #[account(
         mut,
-        constraint = treasury_ata.mint == config_account.config.char_token_mint,
-        constraint = treasury_ata.owner == config_account.config.treasury_authority
-
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump = treasury.vault_bump,
     )]
-    pub treasury_ata: InterfaceAccount<'info, TokenAccount>,
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
@@ -50,15 +138,41 @@ pub struct ReleaseRewards<'info> {
   
 
     /// will use https://squads.xyz/ for multi sig
-    /// Authority for treasury withdrawals.
+    /// Authority allowed to trigger treasury releases.
     #[account(
-        mut,
         constraint = config_account.config.treasury_authority == treasury_authority.key()
     )]
     pub treasury_authority: Signer<'info>,
-    #[account(constraint = mint.key() == config_account.config.char_token_mint)]
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
     pub mint: InterfaceAccount<'info, Mint>,
-    pub token_program: Program<'info, Token>,
+    /// Fees recorded by the transfer hook.
+    #[account(constraint = fee_accumulator.mint == config_account.config.char_token_mint)]
+    pub fee_accumulator: Account<'info, FeeAccumulator>,
+    #[account(
+        mut,
+        seeds = [b"fee_release".as_ref()],
+        bump = fee_release.bump,
+    )]
+    pub fee_release: Account<'info, FeeRelease>,
+    #[account(
+        seeds = [b"distribution_config".as_ref()],
+        bump = distribution_config.bump,
+    )]
+    pub distribution_config: Account<'info, DistributionConfig>,
+    #[account(
+        mut,
+        seeds = [b"outflow_limiter".as_ref()],
+        bump = outflow_limiter.bump,
+    )]
+    pub outflow_limiter: Account<'info, OutflowLimiter>,
+    #[account(
+        mut,
+        seeds = [b"release_schedule".as_ref()],
+        bump = release_schedule.bump,
+    )]
+    pub release_schedule: Account<'info, ReleaseSchedule>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 #[derive(Accounts)]
 pub struct ReleaseDonations<'info> {
@@ -69,14 +183,18 @@ pub struct ReleaseDonations<'info> {
         )]
     pub config_account: Account<'info, ConfigAccount>,
 
-    ///  Treasury token account holding funds to be distributed.
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Account<'info, Treasury>,
+    ///  Treasury vault holding funds to be distributed.
     #[account(
         mut,
-        constraint = treasury_ata.mint == config_account.config.char_token_mint,
-        constraint = treasury_ata.owner == config_account.config.treasury_authority
-
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump = treasury.vault_bump,
     )]
-    pub treasury_ata: InterfaceAccount<'info, TokenAccount>,
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
 
 
     #[account(mut,
@@ -115,138 +233,161 @@ pub struct ReleaseDonations<'info> {
     pub char_funds_ata: InterfaceAccount<'info, TokenAccount>,
 
     /// will use https://squads.xyz/ for multi sig
-    /// Authority for treasury withdrawals.
+    /// Authority allowed to trigger treasury releases.
     #[account(
-        mut,
         constraint = config_account.config.treasury_authority == treasury_authority.key()
     )]
     pub treasury_authority: Signer<'info>,
-    #[account(constraint = mint.key() == config_account.config.char_token_mint)]
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
     pub mint: InterfaceAccount<'info, Mint>,
-    pub token_program: Program<'info, Token>,
+    /// Fees recorded by the transfer hook.
+    #[account(constraint = fee_accumulator.mint == config_account.config.char_token_mint)]
+    pub fee_accumulator: Account<'info, FeeAccumulator>,
+    #[account(
+        mut,
+        seeds = [b"fee_release".as_ref()],
+        bump = fee_release.bump,
+    )]
+    pub fee_release: Account<'info, FeeRelease>,
+    #[account(
+        seeds = [b"distribution_config".as_ref()],
+        bump = distribution_config.bump,
+    )]
+    pub distribution_config: Account<'info, DistributionConfig>,
+    #[account(
+        mut,
+        seeds = [b"outflow_limiter".as_ref()],
+        bump = outflow_limiter.bump,
+    )]
+    pub outflow_limiter: Account<'info, OutflowLimiter>,
+    #[account(
+        mut,
+        seeds = [b"release_schedule".as_ref()],
+        bump = release_schedule.bump,
+    )]
+    pub release_schedule: Account<'info, ReleaseSchedule>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
-pub fn release_rewards(ctx: Context<ReleaseRewards>, total_amount: u64) -> Result<()> {
-    // Fixed distribution percentages from the CHAR Coin schema
-    let donation_percent = 750; // 75% to donation ecosystem
-
-   
-
-    // Calculate donation ecosystem total (75%)
-    let donation_total = total_amount
-        .checked_mul(donation_percent as u64)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap();
-
-    // Split donation into subcategories
-    let reward_system = donation_total
-        .checked_mul(200)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 20% of (donation_total)
-
-
-
-    let monthly_reward_classification = reward_system
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 50% of (reward_system)
-
-    let monthly_top_tier_percentage = monthly_reward_classification
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 50% of (monthly_reward_classification)
-    let monthly_charity_lottery_percentage = monthly_reward_classification
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 50% of (monthly_reward_classification)
-
-
-
-
-    let annual_reward_classification = reward_system
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 50% of (reward_system)
-
-    let annual_top_tier_percentage = annual_reward_classification
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 50% of (annual_reward_classification)
-    let annual_charity_lottery_percentage = annual_reward_classification
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 50% of (annual_reward_classification)
-
-   
-
-    transfer_checked(
-        CpiContext::new(
+pub fn release_rewards<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ReleaseRewards<'info>>,
+) -> Result<()> {
+    // Split the fees collected since the last release
+    let total_amount = ctx
+        .accounts
+        .fee_release
+        .take_unreleased(FeeStream::Rewards, ctx.accounts.fee_accumulator.total_fees)?;
+    let treasury_seeds: &[&[u8]] = &[b"treasury", &[ctx.accounts.treasury.bump]];
+    let signer = &[treasury_seeds];
+    let splits = ctx.accounts.distribution_config.splits;
+
+    // Reward system share of the donation ecosystem
+    let donation_total = share(total_amount, splits.donation_ecosystem)?;
+    let reward_system = share(donation_total, splits.reward_system)?;
+
+    let monthly_reward_classification = share(reward_system, splits.monthly_rewards)?;
+    let monthly_top_tier_percentage = share(monthly_reward_classification, splits.top_tier)?;
+    let monthly_charity_lottery_percentage =
+        share(monthly_reward_classification, splits.charity_lottery)?;
+
+    let annual_reward_classification = share(reward_system, splits.annual_rewards)?;
+    let annual_top_tier_percentage = share(annual_reward_classification, splits.top_tier)?;
+    let annual_charity_lottery_percentage =
+        share(annual_reward_classification, splits.charity_lottery)?;
+
+    // Monthly rewards once per monthly period, annual shares wait in the vault for the annual release
+    let now = Clock::get()?.unix_timestamp;
+    let release_schedule = &mut ctx.accounts.release_schedule;
+    release_schedule.record_release(Classification::MonthlyRewards, now)?;
+    let [annual_top_tier_percentage, annual_charity_lottery_percentage] = release_schedule
+        .accrue_annual(
+            Classification::AnnualRewards,
+            [annual_top_tier_percentage, annual_charity_lottery_percentage],
+            now,
+        )?;
+
+    // Every treasury outflow counts against the outflow limits
+    ctx.accounts.outflow_limiter.record_outflow(
+        monthly_top_tier_percentage
+            + monthly_charity_lottery_percentage
+            + annual_top_tier_percentage
+            + annual_charity_lottery_percentage,
+        now,
+    )?;
+
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
+                from: ctx.accounts.treasury_vault.to_account_info(),
                 to: ctx.accounts.monthly_top_tier_ata.to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
+                authority: ctx.accounts.treasury.to_account_info(),
                 mint:ctx.accounts.mint.to_account_info()
 
             },
-        ),
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
         monthly_top_tier_percentage,
                 ctx.accounts.mint.decimals
 
     )?;
 
-    transfer_checked(
-        CpiContext::new(
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
+                from: ctx.accounts.treasury_vault.to_account_info(),
                 to: ctx.accounts.monthly_charity_lottery_ata.to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
+                authority: ctx.accounts.treasury.to_account_info(),
                 mint:ctx.accounts.mint.to_account_info()
             },
-        ),
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
         monthly_charity_lottery_percentage,
         ctx.accounts.mint.decimals
     )?;
-    transfer_checked(
-        CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
-                to: ctx.accounts.annual_top_tier_ata.to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
-                mint:ctx.accounts.mint.to_account_info()
-
-            },
-        ),
-        annual_top_tier_percentage,
-                ctx.accounts.mint.decimals
-
-    )?;
-
-    transfer_checked(
-        CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
-                to: ctx.accounts.annual_charity_lottery_ata.to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
-                mint:ctx.accounts.mint.to_account_info()
-
-            },
-        ),
-        annual_charity_lottery_percentage,
-                ctx.accounts.mint.decimals
-
-    )?;
+    if annual_top_tier_percentage > 0 {
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.treasury_vault.to_account_info(),
+                    to: ctx.accounts.annual_top_tier_ata.to_account_info(),
+                    authority: ctx.accounts.treasury.to_account_info(),
+                    mint:ctx.accounts.mint.to_account_info()
+
+                },
+                signer,
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            annual_top_tier_percentage,
+                    ctx.accounts.mint.decimals
+
+        )?;
+    }
+
+    if annual_charity_lottery_percentage > 0 {
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.treasury_vault.to_account_info(),
+                    to: ctx.accounts.annual_charity_lottery_ata.to_account_info(),
+                    authority: ctx.accounts.treasury.to_account_info(),
+                    mint:ctx.accounts.mint.to_account_info()
+
+                },
+                signer,
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            annual_charity_lottery_percentage,
+                    ctx.accounts.mint.decimals
+
+        )?;
+    }
 
     Ok(())
 }
@@ -254,145 +395,154 @@ pub fn release_rewards(ctx: Context<ReleaseRewards>, total_amount: u64) -> Resul
 
 
 
-pub fn release_donations(ctx:Context<ReleaseDonations>,total_amount: u64)->Result<()>{
-        let donation_percent = 750; // 75% to donation ecosystem
-  // Calculate donation ecosystem total (75%)
-    let donation_total = total_amount
-        .checked_mul(donation_percent as u64)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap();
-
-     let donation_system = donation_total
-        .checked_mul(800)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 80% of (donation_total)
-
- 
+pub fn release_donations<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ReleaseDonations<'info>>,
+)->Result<()>{
+    // Split the fees collected since the last release
+    let total_amount = ctx
+        .accounts
+        .fee_release
+        .take_unreleased(FeeStream::Donations, ctx.accounts.fee_accumulator.total_fees)?;
+    let treasury_seeds: &[&[u8]] = &[b"treasury", &[ctx.accounts.treasury.bump]];
+    let signer = &[treasury_seeds];
+    let splits = ctx.accounts.distribution_config.splits;
+
+    // Donation system share of the donation ecosystem
+    let donation_total = share(total_amount, splits.donation_ecosystem)?;
+    let donation_system = share(donation_total, splits.donation_system)?;
+
+    let monthly_donation_fund = share(donation_system, splits.monthly_donations)?;
+    let annual_donation_fund = share(donation_system, splits.annual_donations)?;
+    let char_fund = share(donation_system, splits.char_fund)?;
+
+    let monthly_one_time_causes_percentage =
+        share(monthly_donation_fund, splits.one_time_causes)?;
+    let monthly_infinite_impact_causes_percentage =
+        share(monthly_donation_fund, splits.infinite_impact_causes)?;
+    let annual_one_time_causes_percentage = share(annual_donation_fund, splits.one_time_causes)?;
+    let annual_infinite_impact_causes_percentage =
+        share(annual_donation_fund, splits.infinite_impact_causes)?;
+
+    // Monthly donations once per monthly period, annual shares wait in the vault for the annual release
+    let now = Clock::get()?.unix_timestamp;
+    let release_schedule = &mut ctx.accounts.release_schedule;
+    release_schedule.record_release(Classification::MonthlyDonations, now)?;
+    let [annual_one_time_causes_percentage, annual_infinite_impact_causes_percentage] =
+        release_schedule.accrue_annual(
+            Classification::AnnualDonations,
+            [
+                annual_one_time_causes_percentage,
+                annual_infinite_impact_causes_percentage,
+            ],
+            now,
+        )?;
+
+    // Every treasury outflow counts against the outflow limits
+    ctx.accounts.outflow_limiter.record_outflow(
+        monthly_one_time_causes_percentage
+            + monthly_infinite_impact_causes_percentage
+            + annual_one_time_causes_percentage
+            + annual_infinite_impact_causes_percentage
+            + char_fund,
+        now,
+    )?;
 
-   // Split monthly donation into immediate and reserved portions
-    let monthly_donation_fund = donation_system
-        .checked_mul(800)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 80% of (monthly_donation_amount)
-    let annual_donation_fund = donation_system
-        .checked_mul(100)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 10% of (monthly_donation_amount)
-  let char_fund = donation_system
-        .checked_mul(100)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap(); // 10% of (monthly_donation_amount)
-
-    let monthly_one_time_causes_percentage = monthly_donation_fund
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap();
-
-    let monthly_infinite_impact_causes_percentage = monthly_donation_fund
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap();
-    let annual_one_time_causes_percentage = annual_donation_fund
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap();
-
-    let annual_infinite_impact_causes_percentage = annual_donation_fund
-        .checked_mul(500)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap();
-
-    transfer_checked(
-        CpiContext::new(
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
+                from: ctx.accounts.treasury_vault.to_account_info(),
                 to: ctx.accounts.monthly_one_time_causes_ata.to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
+                authority: ctx.accounts.treasury.to_account_info(),
                 mint:ctx.accounts.mint.to_account_info()
 
             },
-        ),
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
         monthly_one_time_causes_percentage,
                 ctx.accounts.mint.decimals
 
     )?;
 
-    transfer_checked(
-        CpiContext::new(
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
+                from: ctx.accounts.treasury_vault.to_account_info(),
                 to: ctx
                     .accounts
                     .monthly_infinite_impact_causes_ata
                     .to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
+                authority: ctx.accounts.treasury.to_account_info(),
                 mint:ctx.accounts.mint.to_account_info()
 
             },
-        ),
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
         monthly_infinite_impact_causes_percentage,
                 ctx.accounts.mint.decimals
 
     )?;
 
-    transfer_checked(
-        CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
-                to: ctx.accounts.annual_one_time_causes_ata.to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
-                mint:ctx.accounts.mint.to_account_info()
-
-            },
-        ),
-        annual_one_time_causes_percentage,
-                ctx.accounts.mint.decimals
-
-    )?;
-
-    transfer_checked(
-        CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
-                to: ctx
-                    .accounts
-                    .annual_infinite_impact_causes_ata
-                    .to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
-                mint:ctx.accounts.mint.to_account_info()
-
-            },
-        ),
-        annual_infinite_impact_causes_percentage,
-                ctx.accounts.mint.decimals
-
-    )?;
+    if annual_one_time_causes_percentage > 0 {
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.treasury_vault.to_account_info(),
+                    to: ctx.accounts.annual_one_time_causes_ata.to_account_info(),
+                    authority: ctx.accounts.treasury.to_account_info(),
+                    mint:ctx.accounts.mint.to_account_info()
+
+                },
+                signer,
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            annual_one_time_causes_percentage,
+                    ctx.accounts.mint.decimals
+
+        )?;
+    }
+
+    if annual_infinite_impact_causes_percentage > 0 {
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.treasury_vault.to_account_info(),
+                    to: ctx
+                        .accounts
+                        .annual_infinite_impact_causes_ata
+                        .to_account_info(),
+                    authority: ctx.accounts.treasury.to_account_info(),
+                    mint:ctx.accounts.mint.to_account_info()
+
+                },
+                signer,
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            annual_infinite_impact_causes_percentage,
+                    ctx.accounts.mint.decimals
+
+        )?;
+    }
  
 
     // Transfer to char funds
-    transfer_checked(
-        CpiContext::new(
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
+                from: ctx.accounts.treasury_vault.to_account_info(),
                 to: ctx.accounts.char_funds_ata.to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
+                authority: ctx.accounts.treasury.to_account_info(),
                 mint:ctx.accounts.mint.to_account_info()
             },
-        ),
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
         char_fund,
                 ctx.accounts.mint.decimals
 
@@ -417,13 +567,18 @@ pub struct ReleaseStakingFunds<'info> {
         bump = staking_pool.bump,
     )]
     pub staking_pool: Account<'info, StakingPool>,
-    ///  Treasury token account holding funds to be distributed.
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Account<'info, Treasury>,
+    ///  Treasury vault holding funds to be distributed.
     #[account(
         mut,
-        constraint = treasury_ata.mint == config_account.config.char_token_mint,
-        constraint = treasury_ata.owner == config_account.config.treasury_authority
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump = treasury.vault_bump,
     )]
-    pub treasury_ata: InterfaceAccount<'info, TokenAccount>,
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         constraint = staking_reward_ata.mint == config_account.config.char_token_mint,
@@ -435,42 +590,72 @@ pub struct ReleaseStakingFunds<'info> {
 
 
     /// will use https://squads.xyz/ for multi sig
-    /// Authority for treasury withdrawals.
+    /// Authority allowed to trigger treasury releases.
     #[account(
-        mut,
         constraint = config_account.config.treasury_authority == treasury_authority.key()
     )]
     pub treasury_authority: Signer<'info>,
         #[account(mut,
-    constraint = mint.key() == config_account.config.char_token_mint)]
+    constraint = mint.key() == config_account.config.char_token_mint,
+    constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
     pub mint: InterfaceAccount<'info, Mint>,
-    pub token_program: Program<'info, Token>,
+    /// Fees recorded by the transfer hook.
+    #[account(constraint = fee_accumulator.mint == config_account.config.char_token_mint)]
+    pub fee_accumulator: Account<'info, FeeAccumulator>,
+    #[account(
+        mut,
+        seeds = [b"fee_release".as_ref()],
+        bump = fee_release.bump,
+    )]
+    pub fee_release: Account<'info, FeeRelease>,
+    #[account(
+        seeds = [b"distribution_config".as_ref()],
+        bump = distribution_config.bump,
+    )]
+    pub distribution_config: Account<'info, DistributionConfig>,
+    #[account(
+        mut,
+        seeds = [b"outflow_limiter".as_ref()],
+        bump = outflow_limiter.bump,
+    )]
+    pub outflow_limiter: Account<'info, OutflowLimiter>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 
 
-pub fn release_staking_char_funds(ctx: Context<ReleaseStakingFunds>, total_amount: u64) ->Result<()>{
-    let staking_percent = 150; // 15% to staking rewards
+pub fn release_staking_char_funds<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ReleaseStakingFunds<'info>>,
+) ->Result<()>{
+    // Split the fees collected since the last release
+    let total_amount = ctx
+        .accounts
+        .fee_release
+        .take_unreleased(FeeStream::Staking, ctx.accounts.fee_accumulator.total_fees)?;
+    let treasury_seeds: &[&[u8]] = &[b"treasury", &[ctx.accounts.treasury.bump]];
+    let signer = &[treasury_seeds];
+    // Staking rewards share of the fees
+    let staking_amount = share(total_amount, ctx.accounts.distribution_config.splits.staking)?;
 
- // Calculate staking amount (15%)
-    let staking_amount = total_amount
-        .checked_mul(staking_percent as u64)
-        .unwrap()
-        .checked_div(1000)
-        .unwrap();
+    // Every treasury outflow counts against the outflow limits
+    ctx.accounts
+        .outflow_limiter
+        .record_outflow(staking_amount, Clock::get()?.unix_timestamp)?;
 
     // Transfer to staking rewards
-    transfer_checked(
-        CpiContext::new(
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             TransferChecked {
-                from: ctx.accounts.treasury_ata.to_account_info(),
+                from: ctx.accounts.treasury_vault.to_account_info(),
                 to: ctx.accounts.staking_reward_ata.to_account_info(),
-                authority: ctx.accounts.treasury_authority.to_account_info(),
+                authority: ctx.accounts.treasury.to_account_info(),
                                 mint:ctx.accounts.mint.to_account_info()
 
             },
-        ),
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
         staking_amount,
                 ctx.accounts.mint.decimals
 
@@ -0,0 +1,706 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::attestation::{is_unattested, require_attested};
+use crate::distribution::share;
+use crate::transfer::transfer_checked_with_hook;
+use crate::{Attestation, Charity, CharityCategory, CharityStatus, ConfigAccount, CustomError};
+
+/// Max number of charities competing in a round.
+pub const MAX_ROUND_CHARITIES: usize = 16;
+/// Max number of winners of a round.
+pub const MAX_ROUND_WINNERS: usize = 8;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Debug)]
+pub enum RoundStatus {
+    Open,
+    Finalized,
+    QuorumNotReached,
+}
+
+/// Owner of the donation vault of a charity category, paying the round winners of the
+/// category. It replaces the donation wallet of the category in the config, so the fee
+/// settlements fund the vault. `committed` is the CHAR allocated to rounds and not yet
+/// paid out or returned, round allocations only take from the rest of the vault.
+#[account]
+pub struct DonationVault {
+    pub category: CharityCategory,
+    pub vault: Pubkey,
+    pub bump: u8,
+    pub vault_bump: u8,
+    pub committed: u64,
+}
+
+impl DonationVault {
+    /// Releases committed CHAR once paid out or returned to the pool.
+    pub fn release(&mut self, amount: u64) -> Result<()> {
+        self.committed = self
+            .committed
+            .checked_sub(amount)
+            .ok_or(CustomError::MathError)?;
+        Ok(())
+    }
+}
+
+/// Numbers the voting rounds.
+#[account]
+pub struct RoundRegistry {
+    pub next_round_id: u64,
+    pub bump: u8,
+}
+
+/// Competition between charities of one category with a single voting window. Charities added to the
+/// round take its voting window. At finalization the charities are ranked by votes and
+/// the first `winner_count` with votes win, each with its share of the winners' votes.
+/// Each winner is paid its share of `allocation` from the donation vault. Rounds with
+/// `require_attestation` only accept and pay charities with a valid attestation.
+#[account]
+pub struct VotingRound {
+    pub id: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub quorum: u64, // minimum total votes of the round
+    pub category: CharityCategory,
+    pub require_attestation: bool,
+    pub winner_count: u8,
+    pub charities: [Pubkey; MAX_ROUND_CHARITIES],
+    pub charity_count: u8,
+    pub status: RoundStatus,
+    pub total_votes: u64,                        // set at finalization
+    pub winners: [Pubkey; MAX_ROUND_WINNERS],    // charity accounts, by rank
+    pub winner_shares: [u16; MAX_ROUND_WINNERS], // per-mille of the payout
+    pub winners_selected: u8,
+    pub allocation: u64, // CHAR paid to the winners from the donation vault
+    pub paid_total: u64,
+    pub bump: u8,
+}
+
+impl VotingRound {
+    pub fn charities(&self) -> &[Pubkey] {
+        &self.charities[..self.charity_count as usize]
+    }
+
+    /// Share of the allocation won by a charity of the finalized round.
+    pub fn winner_payout(&self, charity: &Pubkey) -> Result<u64> {
+        require!(
+            self.status == RoundStatus::Finalized,
+            CustomError::RoundNotFinalized
+        );
+        let rank = self.winners[..self.winners_selected as usize]
+            .iter()
+            .position(|winner| winner == charity)
+            .ok_or(CustomError::CharityNotWinner)?;
+        share(self.allocation, self.winner_shares[rank])
+    }
+}
+
+#[event]
+pub struct RoundFinalizedEvent {
+    pub round_id: u64,
+    pub total_votes: u64,
+    pub quorum_reached: bool,
+    pub winners: Vec<Pubkey>,
+    pub winner_shares: Vec<u16>,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CharityPayoutReclaimedEvent {
+    pub round_id: u64,
+    pub charity_id: u64,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct CharityPaidEvent {
+    pub round_id: u64,
+    pub charity_id: u64,
+    pub wallet: Pubkey,
+    pub amount: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+#[instruction(category: CharityCategory)]
+pub struct InitializeDonationVault<'info> {
+    #[account(
+        mut,
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<DonationVault>(),
+        seeds = [b"donation_vault".as_ref(), &[category as u8]],
+        bump
+    )]
+    pub donation_vault: Account<'info, DonationVault>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = mint,
+        token::authority = donation_vault,
+        token::token_program = token_program,
+        seeds = [b"donation_vault".as_ref(), mint.key().as_ref(), &[category as u8]],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CreateVotingRound<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + std::mem::size_of::<RoundRegistry>(),
+        seeds = [b"round_registry".as_ref()],
+        bump
+    )]
+    pub round_registry: Account<'info, RoundRegistry>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<VotingRound>(),
+        seeds = [b"voting_round".as_ref(), round_registry.next_round_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u64, charity_id: u64)]
+pub struct AddCharityToRound<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"voting_round".as_ref(), round_id.to_le_bytes().as_ref()],
+        bump = voting_round.bump,
+    )]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    #[account(
+        mut,
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Account<'info, Charity>,
+    /// Only required when the round requires attestations.
+    #[account(
+        seeds = [b"attestation".as_ref(), charity.wallet.as_ref()],
+        bump = attestation.bump,
+    )]
+    pub attestation: Option<Account<'info, Attestation>>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u64)]
+pub struct FinalizeRound<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"voting_round".as_ref(), round_id.to_le_bytes().as_ref()],
+        bump = voting_round.bump,
+    )]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    /// Donation vault of the category of the round.
+    #[account(
+        mut,
+        seeds = [b"donation_vault".as_ref(), &[voting_round.category as u8]],
+        bump = donation_vault.bump,
+    )]
+    pub donation_vault: Box<Account<'info, DonationVault>>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u64)]
+pub struct SetRoundAllocation<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"voting_round".as_ref(), round_id.to_le_bytes().as_ref()],
+        bump = voting_round.bump,
+    )]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    /// Donation vault of the category of the round.
+    #[account(
+        mut,
+        seeds = [b"donation_vault".as_ref(), &[voting_round.category as u8]],
+        bump = donation_vault.bump,
+    )]
+    pub donation_vault: Box<Account<'info, DonationVault>>,
+    #[account(address = donation_vault.vault)]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u64, charity_id: u64)]
+pub struct PayCharity<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        mut,
+        seeds = [b"voting_round".as_ref(), round_id.to_le_bytes().as_ref()],
+        bump = voting_round.bump,
+    )]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    #[account(
+        mut,
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    /// Only required when the round requires attestations.
+    #[account(
+        seeds = [b"attestation".as_ref(), charity.wallet.as_ref()],
+        bump = attestation.bump,
+    )]
+    pub attestation: Option<Account<'info, Attestation>>,
+    /// Donation vault of the category of the round.
+    #[account(
+        mut,
+        seeds = [b"donation_vault".as_ref(), &[voting_round.category as u8]],
+        bump = donation_vault.bump,
+    )]
+    pub donation_vault: Box<Account<'info, DonationVault>>,
+    #[account(
+        mut,
+        address = donation_vault.vault,
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = charity_token_account.mint == config_account.config.char_token_mint,
+        constraint = charity_token_account.owner == charity.wallet
+    )]
+    pub charity_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(round_id: u64, charity_id: u64)]
+pub struct ReclaimCharityPayout<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [b"voting_round".as_ref(), round_id.to_le_bytes().as_ref()],
+        bump = voting_round.bump,
+    )]
+    pub voting_round: Box<Account<'info, VotingRound>>,
+    #[account(
+        mut,
+        seeds=[b"charity".as_ref(), charity_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub charity: Box<Account<'info, Charity>>,
+    /// Donation vault of the category of the round.
+    #[account(
+        mut,
+        seeds = [b"donation_vault".as_ref(), &[voting_round.category as u8]],
+        bump = donation_vault.bump,
+    )]
+    pub donation_vault: Box<Account<'info, DonationVault>>,
+    /// Only required to reclaim the share of a winner no longer attested.
+    #[account(
+        seeds = [b"attestation".as_ref(), charity.wallet.as_ref()],
+        bump = attestation.bump,
+    )]
+    pub attestation: Option<Account<'info, Attestation>>,
+    /// Only required to reclaim the share of a winner no longer attested.
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Option<Signer<'info>>,
+}
+
+/// Creates the donation vault of a category and makes it the donation wallet of the
+/// category in the config.
+pub fn initialize_donation_vault(
+    ctx: Context<InitializeDonationVault>,
+    category: CharityCategory,
+) -> Result<()> {
+    let donation_vault = &mut ctx.accounts.donation_vault;
+    *category.funding_wallet(&mut ctx.accounts.config_account.config) = donation_vault.key();
+    donation_vault.category = category;
+    donation_vault.vault = ctx.accounts.vault.key();
+    donation_vault.bump = ctx.bumps.donation_vault;
+    donation_vault.vault_bump = ctx.bumps.vault;
+    Ok(())
+}
+
+/// Opens a new voting round.
+pub fn create_voting_round(
+    ctx: Context<CreateVotingRound>,
+    start_time: u64,
+    end_time: u64,
+    quorum: u64,
+    winner_count: u8,
+    category: CharityCategory,
+    require_attestation: bool,
+) -> Result<()> {
+    require!(
+        start_time > 0
+            && start_time < end_time
+            && winner_count > 0
+            && winner_count as usize <= MAX_ROUND_WINNERS,
+        CustomError::InvalidRoundConfig
+    );
+    let registry = &mut ctx.accounts.round_registry;
+    let voting_round = &mut ctx.accounts.voting_round;
+    voting_round.id = registry.next_round_id;
+    voting_round.start_time = start_time;
+    voting_round.end_time = end_time;
+    voting_round.quorum = quorum;
+    voting_round.winner_count = winner_count;
+    voting_round.category = category;
+    voting_round.require_attestation = require_attestation;
+    voting_round.status = RoundStatus::Open;
+    voting_round.bump = ctx.bumps.voting_round;
+    registry.next_round_id += 1;
+    registry.bump = ctx.bumps.round_registry;
+    msg!("Voting round {} created", voting_round.id);
+    Ok(())
+}
+
+/// Adds a charity of the category of the round. The charity takes the voting window of
+/// the round, so it must not have received votes yet.
+pub fn add_charity_to_round(
+    ctx: Context<AddCharityToRound>,
+    _round_id: u64,
+    _charity_id: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    add_to_round(
+        &mut ctx.accounts.voting_round,
+        &mut ctx.accounts.charity,
+        ctx.accounts.attestation.as_deref(),
+        now,
+    )
+}
+
+/// Adds a charity to an open round and gives it the voting window of the round.
+pub fn add_to_round(
+    voting_round: &mut Account<VotingRound>,
+    charity: &mut Account<Charity>,
+    attestation: Option<&Attestation>,
+    now: i64,
+) -> Result<()> {
+    require!(
+        voting_round.status == RoundStatus::Open && now as u64 <= voting_round.end_time,
+        CustomError::RoundClosed
+    );
+    require!(
+        charity.round == Pubkey::default()
+            && charity.status == CharityStatus::Active
+            && charity.total_votes == 0,
+        CustomError::CharityNotEligibleForRound
+    );
+    require!(
+        charity.category == voting_round.category,
+        CustomError::CharityCategoryMismatch
+    );
+    require_attested(voting_round, charity, attestation, now)?;
+    require!(
+        (voting_round.charity_count as usize) < MAX_ROUND_CHARITIES,
+        CustomError::RoundFull
+    );
+
+    let index = voting_round.charity_count as usize;
+    voting_round.charities[index] = charity.key();
+    voting_round.charity_count += 1;
+    charity.round = voting_round.key();
+    charity.start_time = voting_round.start_time;
+    charity.end_time = voting_round.end_time;
+    Ok(())
+}
+
+/// Ranks the charities of the round by votes and records the winners and their shares.
+/// The charities of the round are passed as remaining accounts, in round order. Removed
+/// charities are left out of the tally. A round below quorum has no winners and its
+/// allocation goes back to the donation pool, as does the rounding dust of the shares.
+pub fn finalize_round<'info>(
+    ctx: Context<'_, '_, 'info, 'info, FinalizeRound<'info>>,
+    _round_id: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp as u64;
+    let voting_round = &mut ctx.accounts.voting_round;
+    require!(now > voting_round.end_time, CustomError::VotingNotEnded);
+    require!(
+        voting_round.status == RoundStatus::Open,
+        CustomError::RoundClosed
+    );
+    require!(
+        ctx.remaining_accounts.len() == voting_round.charity_count as usize,
+        CustomError::InvalidRoundCharity
+    );
+
+    let mut ranking: Vec<(u64, u64, Pubkey)> = Vec::with_capacity(ctx.remaining_accounts.len());
+    let mut total_votes: u64 = 0;
+    for (info, expected) in ctx.remaining_accounts.iter().zip(voting_round.charities()) {
+        require!(info.key == expected, CustomError::InvalidRoundCharity);
+        let charity = Account::<Charity>::try_from(info)?;
+        // Votes of cancelled and vetoed charities do not count
+        if charity.is_removed() {
+            continue;
+        }
+        total_votes = total_votes
+            .checked_add(charity.total_votes)
+            .ok_or(CustomError::MathError)?;
+        ranking.push((charity.total_votes, charity.id, info.key()));
+    }
+    // Most votes first, the earliest registered charity wins a tie
+    ranking.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    voting_round.total_votes = total_votes;
+    let quorum_reached = total_votes > 0 && total_votes >= voting_round.quorum;
+    if quorum_reached {
+        let winners: Vec<&(u64, u64, Pubkey)> = ranking
+            .iter()
+            .filter(|(votes, _, _)| *votes > 0)
+            .take(voting_round.winner_count as usize)
+            .collect();
+        let winner_votes = winners
+            .iter()
+            .map(|(votes, _, _)| *votes as u128)
+            .sum::<u128>();
+        let mut allocated: u16 = 0;
+        for (index, (votes, _, key)) in winners.iter().enumerate() {
+            // The last winner takes the rounding dust so the shares add up to 1000
+            let share = if index + 1 == winners.len() {
+                1000 - allocated
+            } else {
+                (*votes as u128 * 1000 / winner_votes) as u16
+            };
+            voting_round.winners[index] = *key;
+            voting_round.winner_shares[index] = share;
+            allocated += share;
+        }
+        voting_round.winners_selected = winners.len() as u8;
+        voting_round.status = RoundStatus::Finalized;
+    } else {
+        voting_round.status = RoundStatus::QuorumNotReached;
+    }
+    let mut payouts: u64 = 0;
+    for winner_share in &voting_round.winner_shares[..voting_round.winners_selected as usize] {
+        payouts = payouts
+            .checked_add(share(voting_round.allocation, *winner_share)?)
+            .ok_or(CustomError::MathError)?;
+    }
+    ctx.accounts
+        .donation_vault
+        .release(voting_round.allocation - payouts)?;
+
+    let selected = voting_round.winners_selected as usize;
+    emit!(RoundFinalizedEvent {
+        round_id: voting_round.id,
+        total_votes,
+        quorum_reached,
+        winners: voting_round.winners[..selected].to_vec(),
+        winner_shares: voting_round.winner_shares[..selected].to_vec(),
+        timestamp: now,
+    });
+    msg!(
+        "Voting round {} finalized with {} total votes",
+        voting_round.id,
+        total_votes
+    );
+    Ok(())
+}
+
+/// Sets the CHAR paid to the winners of an open round. The allocation replaces the
+/// previous one of the round and is committed in the donation vault, it cannot exceed
+/// the vault balance not committed to other rounds.
+pub fn set_round_allocation(
+    ctx: Context<SetRoundAllocation>,
+    _round_id: u64,
+    allocation: u64,
+) -> Result<()> {
+    let voting_round = &mut ctx.accounts.voting_round;
+    require!(
+        voting_round.status == RoundStatus::Open,
+        CustomError::RoundClosed
+    );
+    let donation_vault = &mut ctx.accounts.donation_vault;
+    donation_vault.release(voting_round.allocation)?;
+    require!(
+        allocation
+            <= ctx
+                .accounts
+                .vault
+                .amount
+                .saturating_sub(donation_vault.committed),
+        CustomError::AllocationExceedsVault
+    );
+    donation_vault.committed = donation_vault
+        .committed
+        .checked_add(allocation)
+        .ok_or(CustomError::MathError)?;
+    voting_round.allocation = allocation;
+    Ok(())
+}
+
+/// Pays a winner of a finalized round its share of the round allocation from the
+/// donation vault of the round category. Anyone can trigger the payout, a charity is paid once.
+/// One-time causes are only paid through their milestone plan.
+pub fn pay_charity<'info>(
+    ctx: Context<'_, '_, 'info, 'info, PayCharity<'info>>,
+    _round_id: u64,
+    _charity_id: u64,
+) -> Result<()> {
+    require!(
+        !ctx.accounts.charity.paid_out,
+        CustomError::CharityAlreadyPaid
+    );
+    require!(
+        !ctx.accounts.charity.is_removed(),
+        CustomError::CharityRemoved
+    );
+    require!(
+        !ctx.accounts.charity.category.is_one_time(),
+        CustomError::MilestonePlanRequired
+    );
+    require_attested(
+        &ctx.accounts.voting_round,
+        &ctx.accounts.charity,
+        ctx.accounts.attestation.as_deref(),
+        Clock::get()?.unix_timestamp,
+    )?;
+    let amount = ctx
+        .accounts
+        .voting_round
+        .winner_payout(&ctx.accounts.charity.key())?;
+
+    ctx.accounts.charity.paid_out = true;
+    ctx.accounts.donation_vault.release(amount)?;
+    let voting_round = &mut ctx.accounts.voting_round;
+    voting_round.paid_total = voting_round
+        .paid_total
+        .checked_add(amount)
+        .ok_or(CustomError::MathError)?;
+
+    let seeds: &[&[u8]] = &[
+        b"donation_vault",
+        &[ctx.accounts.donation_vault.category as u8],
+        &[ctx.accounts.donation_vault.bump],
+    ];
+    let signer = &[seeds];
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                to: ctx.accounts.charity_token_account.to_account_info(),
+                authority: ctx.accounts.donation_vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            },
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        amount,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    emit!(CharityPaidEvent {
+        round_id: ctx.accounts.voting_round.id,
+        charity_id: ctx.accounts.charity.id,
+        wallet: ctx.accounts.charity.wallet,
+        amount,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    msg!(
+        "Paid {} to charity '{}'",
+        amount,
+        ctx.accounts.charity.title
+    );
+    Ok(())
+}
+
+/// Returns the share of a round winner removed before it was paid to the donation pool.
+/// The share never left the donation vault, it is no longer committed and the charity
+/// is marked as settled. Anyone can trigger it. The share of a winner whose attestation
+/// was revoked or expired can be returned by the admin.
+pub fn reclaim_charity_payout(
+    ctx: Context<ReclaimCharityPayout>,
+    _round_id: u64,
+    _charity_id: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let unattested = ctx.accounts.admin.is_some()
+        && is_unattested(
+            &ctx.accounts.voting_round,
+            ctx.accounts.attestation.as_ref(),
+            now,
+        );
+    let charity = &mut ctx.accounts.charity;
+    require!(!charity.paid_out, CustomError::CharityAlreadyPaid);
+    require!(
+        charity.is_removed() || unattested,
+        CustomError::CharityPayoutNotReclaimable
+    );
+    let amount = ctx.accounts.voting_round.winner_payout(&charity.key())?;
+    charity.paid_out = true;
+    ctx.accounts.donation_vault.release(amount)?;
+
+    emit!(CharityPayoutReclaimedEvent {
+        round_id: ctx.accounts.voting_round.id,
+        charity_id: charity.id,
+        amount,
+        timestamp: now as u64,
+    });
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use anchor_lang::prelude::*;
+
+use crate::{ConfigAccount, CustomError};
+
+/// Release classifications with their own cadence.
+#[derive(Clone, Copy)]
+pub enum Classification {
+    MonthlyRewards = 0,
+    AnnualRewards = 1,
+    MonthlyDonations = 2,
+    AnnualDonations = 3,
+}
+
+/// Cadence of the monthly and annual releases. Periods are counted from
+/// `period_anchor`, a release is allowed once per period of its classification.
+#[account]
+pub struct ReleaseSchedule {
+    pub period_anchor: i64,
+    pub monthly_period: i64,       // length of a monthly period in seconds
+    pub annual_period: i64,        // length of an annual period in seconds
+    pub last_release_at: [i64; 4], // indexed by `Classification`, 0 when never released
+    pub annual_rewards_reserve: [u64; 2], // top tier, charity lottery
+    pub annual_donations_reserve: [u64; 2], // one time causes, infinite impact causes
+    pub bump: u8,
+}
+
+impl ReleaseSchedule {
+    fn period_length(&self, classification: Classification) -> i64 {
+        match classification {
+            Classification::MonthlyRewards | Classification::MonthlyDonations => {
+                self.monthly_period
+            }
+            Classification::AnnualRewards | Classification::AnnualDonations => self.annual_period,
+        }
+    }
+
+    /// Index of the period containing `timestamp`, None before the anchor.
+    pub fn period_of(&self, classification: Classification, timestamp: i64) -> Option<i64> {
+        if timestamp < self.period_anchor {
+            return None;
+        }
+        Some((timestamp - self.period_anchor) / self.period_length(classification))
+    }
+
+    /// Whether `classification` was not released yet in the current period.
+    pub fn is_due(&self, classification: Classification, now: i64) -> Result<bool> {
+        let current = self
+            .period_of(classification, now)
+            .ok_or(CustomError::ReleaseScheduleNotStarted)?;
+        let last_release_at = self.last_release_at[classification as usize];
+        if last_release_at == 0 {
+            return Ok(true);
+        }
+        // the anchor never moves past a release, see `update_release_schedule`
+        let last = self
+            .period_of(classification, last_release_at)
+            .ok_or(CustomError::ReleaseAnchorAfterLastRelease)?;
+        Ok(last < current)
+    }
+
+    /// Records a release, rejected when the classification was already released this period.
+    pub fn record_release(&mut self, classification: Classification, now: i64) -> Result<()> {
+        require!(
+            self.is_due(classification, now)?,
+            CustomError::ReleasePeriodNotElapsed
+        );
+        self.last_release_at[classification as usize] = now;
+        Ok(())
+    }
+
+    /// Adds annual shares to the reserve kept in the treasury vault. Once per annual
+    /// period the whole reserve is returned to be paid out and the reserve is emptied.
+    pub fn accrue_annual(
+        &mut self,
+        classification: Classification,
+        shares: [u64; 2],
+        now: i64,
+    ) -> Result<[u64; 2]> {
+        let due = self.is_due(classification, now)?;
+        let reserve = match classification {
+            Classification::AnnualRewards => &mut self.annual_rewards_reserve,
+            Classification::AnnualDonations => &mut self.annual_donations_reserve,
+            _ => return err!(CustomError::InvalidClassification),
+        };
+        for (reserved, share) in reserve.iter_mut().zip(shares) {
+            *reserved = reserved.checked_add(share).ok_or(CustomError::MathError)?;
+        }
+        if !due {
+            return Ok([0, 0]);
+        }
+        let payout = *reserve;
+        *reserve = [0, 0];
+        self.last_release_at[classification as usize] = now;
+        Ok(payout)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeReleaseSchedule<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<ReleaseSchedule>(),
+        seeds = [b"release_schedule".as_ref()],
+        bump
+    )]
+    pub release_schedule: Account<'info, ReleaseSchedule>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateReleaseSchedule<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"release_schedule".as_ref()],
+        bump = release_schedule.bump,
+    )]
+    pub release_schedule: Account<'info, ReleaseSchedule>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+fn set_periods(
+    release_schedule: &mut ReleaseSchedule,
+    period_anchor: i64,
+    monthly_period: i64,
+    annual_period: i64,
+) -> Result<()> {
+    require!(
+        monthly_period > 0 && annual_period >= monthly_period,
+        CustomError::InvalidReleasePeriod
+    );
+    release_schedule.period_anchor = period_anchor;
+    release_schedule.monthly_period = monthly_period;
+    release_schedule.annual_period = annual_period;
+    Ok(())
+}
+
+/// Creates the release schedule.
+pub fn initialize_release_schedule(
+    ctx: Context<InitializeReleaseSchedule>,
+    period_anchor: i64,
+    monthly_period: i64,
+    annual_period: i64,
+) -> Result<()> {
+    let release_schedule = &mut ctx.accounts.release_schedule;
+    set_periods(
+        release_schedule,
+        period_anchor,
+        monthly_period,
+        annual_period,
+    )?;
+    release_schedule.bump = ctx.bumps.release_schedule;
+    Ok(())
+}
+
+/// Moves the period anchor or changes the period lengths. Past releases keep
+/// their time, so they still count for the period they fall in. The anchor cannot
+/// move past the last release of any classification, which would make every classification due again.
+pub fn update_release_schedule(
+    ctx: Context<UpdateReleaseSchedule>,
+    period_anchor: i64,
+    monthly_period: i64,
+    annual_period: i64,
+) -> Result<()> {
+    require!(
+        ctx.accounts
+            .release_schedule
+            .last_release_at
+            .iter()
+            .all(|x| *x == 0 || period_anchor <= *x),
+        CustomError::ReleaseAnchorAfterLastRelease
+    );
+    set_periods(
+        &mut ctx.accounts.release_schedule,
+        period_anchor,
+        monthly_period,
+        annual_period,
+    )
+}
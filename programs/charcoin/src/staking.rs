@@ -1,13 +1,19 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock::Clock;
-use anchor_spl::token_2022::{transfer_checked, Token2022 as Token, TransferChecked};
-use anchor_spl::token_interface::{TokenAccount,Mint};
-use crate::{ConfigAccount, CustomError};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+use crate::{ConfigAccount, CustomError, ReferralConfig, ReferralRewardPaidEvent};
+use crate::transfer::transfer_checked_with_hook;
+use crate::{extra_rewards, referral, ExtraReward, ExtraRewardCheckpoint, MAX_EXTRA_REWARD_MINTS};
 const FOURTY_EIGHT_HOURS_IN_SECONDS:u32 = 172800;
 const ONE_DAY_IN_SECONDS:u32 = 86400;
 
 
-pub fn stake_tokens(ctx: Context<Stake>, amount: u64, lockup: u16) -> Result<()> {
+pub fn stake_tokens<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Stake<'info>>,
+    amount: u64,
+    lockup: u16,
+    referrer: Option<Pubkey>,
+) -> Result<()> {
     require!(amount > 0, CustomError::NoStakedTokens);
     let staking_pool = &mut ctx.accounts.staking_pool;
     let config_account = &mut ctx.accounts.config_account;
@@ -34,8 +40,9 @@ pub fn stake_tokens(ctx: Context<Stake>, amount: u64, lockup: u16) -> Result<()>
     let char_balance_before = ctx.accounts.pool_token_account.amount;
     
     let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-    transfer_checked(cpi_ctx, amount,ctx.accounts.mint.decimals)?;
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+    transfer_checked_with_hook(cpi_ctx, amount,ctx.accounts.mint.decimals)?;
 
     ctx.accounts.pool_token_account.reload()?; // latest balance after transfer
     
@@ -59,6 +66,26 @@ pub fn stake_tokens(ctx: Context<Stake>, amount: u64, lockup: u16) -> Result<()>
         user.eligible_at = clock;
     }
     
+    if let Some(referrer) = referrer {
+        let referral_config = ctx
+            .accounts
+            .referral_config
+            .as_mut()
+            .ok_or(CustomError::ReferrerAccountsMissing)?;
+        let referrer_user = ctx
+            .accounts
+            .referrer_user
+            .as_mut()
+            .ok_or(CustomError::ReferrerAccountsMissing)?;
+        referral::register_referrer(
+            referral_config,
+            user,
+            referrer_user,
+            ctx.accounts.user_authority.key(),
+            referrer,
+        )?;
+    }
+
     user.total_amount += received_amount;
     user.stake_count += 1;
 
@@ -82,6 +109,58 @@ pub fn stake_tokens(ctx: Context<Stake>, amount: u64, lockup: u16) -> Result<()>
 
 
 
+/// Moves a staking info account created before referrals to the current layout. The
+/// account is resized and the new fields start empty, anyone can pay the extra rent.
+pub fn migrate_user_stake_info(ctx: Context<MigrateUserStakeInfo>, _authority: Pubkey) -> Result<()> {
+    let info = ctx.accounts.user.to_account_info();
+    require!(
+        info.owner == &crate::ID && info.data_len() == LEGACY_USER_STAKE_INFO_SPACE,
+        CustomError::UserStakeInfoNotLegacy
+    );
+    let legacy = {
+        let data = info.try_borrow_data()?;
+        require!(
+            data[..8] == *UserStakeInfo::DISCRIMINATOR,
+            CustomError::UserStakeInfoNotLegacy
+        );
+        LegacyUserStakeInfo::deserialize(&mut &data[8..])?
+    };
+    let user = UserStakeInfo {
+        authority: legacy.authority,
+        staking_pool: legacy.staking_pool,
+        eligible_at: legacy.eligible_at,
+        voting_power: legacy.voting_power,
+        total_amount: legacy.total_amount,
+        reward_issued: legacy.reward_issued,
+        stake_count: legacy.stake_count,
+        bump: legacy.bump,
+        last_vote_time: legacy.last_vote_time,
+        consumed_stake_id_upper_bound: legacy.consumed_stake_id_upper_bound,
+        referrer: Pubkey::default(),
+        referral_count: 0,
+        referral_rewards: 0,
+        governance_locked_until: 0,
+    };
+
+    let space = 8 + std::mem::size_of::<UserStakeInfo>();
+    let rent = Rent::get()?.minimum_balance(space).saturating_sub(info.lamports());
+    if rent > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: info.clone(),
+                },
+            ),
+            rent,
+        )?;
+    }
+    info.resize(space)?;
+    user.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
 pub fn request_unstake_tokens(ctx: Context<UnstakeRequest>, stake_id: u64) -> Result<()> {
     let user_stake = &mut ctx.accounts.user_stake;
     let user = &mut ctx.accounts.user;
@@ -118,7 +197,10 @@ pub fn request_unstake_tokens(ctx: Context<UnstakeRequest>, stake_id: u64) -> Re
     Ok(())
 }
 
-pub fn unstake_tokens(ctx: Context<Unstake>, _stake_id: u64) -> Result<()> {
+pub fn unstake_tokens<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Unstake<'info>>,
+    _stake_id: u64,
+) -> Result<()> {
     let user = &mut ctx.accounts.user;
     let user_stake = &mut ctx.accounts.user_stake;
     let staking_pool = &mut ctx.accounts.staking_pool;
@@ -144,9 +226,19 @@ pub fn unstake_tokens(ctx: Context<Unstake>, _stake_id: u64) -> Result<()> {
     let min_staking_duration = (user_stake.lockup as u64 * ONE_DAY_IN_SECONDS as u64).try_into().unwrap();
     let staking_duration = clock.saturating_sub(user_stake.staked_at);
 
+    // The stake counted in milestone and veto votes until they close
+    require!(
+        clock >= user.governance_locked_until,
+        CustomError::StakeLockedByVote
+    );
+
     user_stake.unstaked_at = clock;
     user.total_amount -= user_stake.amount;
     staking_pool.total_staked -= user_stake.amount;
+    // Below the governance stake the voting duration starts over on the next stake
+    if user.total_amount < ctx.accounts.config_account.config.min_governance_stake {
+        user.eligible_at = 0;
+    }
 
 
 
@@ -190,8 +282,9 @@ pub fn unstake_tokens(ctx: Context<Unstake>, _stake_id: u64) -> Result<()> {
     };
 
     let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-    transfer_checked(cpi_ctx, amount_to_return,ctx.accounts.mint.decimals)?;
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer)
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+    transfer_checked_with_hook(cpi_ctx, amount_to_return,ctx.accounts.mint.decimals)?;
 
     if fee != 0 {
         // send penalty fee to staking reward account ata
@@ -203,8 +296,9 @@ pub fn unstake_tokens(ctx: Context<Unstake>, _stake_id: u64) -> Result<()> {
         };
 
         let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        transfer_checked(cpi_ctx, fee,ctx.accounts.mint.decimals)?;
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer)
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+        transfer_checked_with_hook(cpi_ctx, fee,ctx.accounts.mint.decimals)?;
     }
 
     msg!(
@@ -215,7 +309,10 @@ pub fn unstake_tokens(ctx: Context<Unstake>, _stake_id: u64) -> Result<()> {
     Ok(())
 }
 
-pub fn claim_reward(ctx: Context<ClaimReward>, _stake_id: u64) -> Result<()> {
+pub fn claim_reward<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ClaimReward<'info>>,
+    _stake_id: u64,
+) -> Result<()> {
     require!(
         ctx.accounts.staking_reward_ata.amount > 0,
         CustomError::StakingRewardInsufficientBalance
@@ -256,6 +353,16 @@ pub fn claim_reward(ctx: Context<ClaimReward>, _stake_id: u64) -> Result<()> {
 
     user_stake.current_period = total_periods_earned;
 
+    // remaining accounts hold the extra reward accounts first, then the transfer hook accounts
+    let extra_reward_accounts_len = 4 * staking_pool
+        .extra_rewards
+        .iter()
+        .filter(|x| x.is_active())
+        .count();
+    let (extra_reward_accounts, hook_accounts) = ctx
+        .remaining_accounts
+        .split_at(extra_reward_accounts_len.min(ctx.remaining_accounts.len()));
+
     let reward_percentage = staking_pool
         .stake_lockup_reward_array
         .iter()
@@ -267,9 +374,10 @@ pub fn claim_reward(ctx: Context<ClaimReward>, _stake_id: u64) -> Result<()> {
         (claimable_periods * (user_stake.amount as u64) * reward_percentage as u64) / 1000;
     require!(reward_amount > 0, CustomError::NothingToClaim);
 
+    let token_mint = staking_pool.token_mint;
     let seeds: &[&[u8]] = &[
         b"staking_reward",
-        staking_pool.token_mint.as_ref(),
+        token_mint.as_ref(),
         &[ctx.bumps.staking_reward],
     ];  
     let signer = &[seeds];
@@ -283,12 +391,74 @@ pub fn claim_reward(ctx: Context<ClaimReward>, _stake_id: u64) -> Result<()> {
     };
 
     let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-    transfer_checked(cpi_ctx, reward_amount,ctx.accounts.mint.decimals)?;
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer)
+        .with_remaining_accounts(hook_accounts.to_vec());
+    transfer_checked_with_hook(cpi_ctx, reward_amount,ctx.accounts.mint.decimals)?;
 
     ctx.accounts.staking_pool.reward_issued += reward_amount;
     user.reward_issued += reward_amount;
     msg!("Claimed reward of {} tokens", reward_amount);
+
+    // Pay the partner tokens co-sponsoring the pool
+    let stake_end = if user_stake.unstaked_at == 0 {
+        clock
+    } else {
+        user_stake.unstaked_at
+    };
+    extra_rewards::claim_extra_rewards(
+        &mut ctx.accounts.staking_pool,
+        user_stake,
+        ctx.accounts.user_authority.key(),
+        stake_end,
+        extra_reward_accounts,
+    )?;
+
+    // Pay the referrer's share of the claimed reward from the staking reward account
+    if user.referrer != Pubkey::default() {
+        let (Some(referral_config), Some(referrer_user), Some(referrer_token_account)) = (
+            ctx.accounts.referral_config.as_mut(),
+            ctx.accounts.referrer_user.as_mut(),
+            ctx.accounts.referrer_token_account.as_ref(),
+        ) else {
+            return Err(CustomError::ReferrerAccountsMissing.into());
+        };
+        require!(
+            referrer_user.authority == user.referrer,
+            CustomError::InvalidReferrer
+        );
+        require!(
+            referrer_token_account.owner == user.referrer
+                && referrer_token_account.mint == token_mint,
+            CustomError::InvalidReferrer
+        );
+
+        ctx.accounts.staking_reward_ata.reload()?;
+        let bonus = referral_config
+            .bonus_for(reward_amount)?
+            .min(ctx.accounts.staking_reward_ata.amount);
+        if bonus > 0 {
+            let cpi_accounts = TransferChecked {
+                from: ctx.accounts.staking_reward_ata.to_account_info(),
+                to: referrer_token_account.to_account_info(),
+                authority: ctx.accounts.staking_reward.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            };
+            let cpi_program = ctx.accounts.token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer)
+                .with_remaining_accounts(hook_accounts.to_vec());
+            transfer_checked_with_hook(cpi_ctx, bonus, ctx.accounts.mint.decimals)?;
+
+            referral_config.total_paid += bonus;
+            referrer_user.referral_rewards += bonus;
+            emit!(ReferralRewardPaidEvent {
+                referee: ctx.accounts.user_authority.key(),
+                referrer: user.referrer,
+                amount: bonus,
+                total_paid: referral_config.total_paid,
+                timestamp: clock,
+            });
+        }
+    }
     Ok(())
 }
 
@@ -405,6 +575,28 @@ pub struct SetReward<'info> {
 }
 
 #[derive(Accounts)]
+#[instruction(authority: Pubkey)]
+pub struct MigrateUserStakeInfo<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    /// CHECK: legacy staking info, too short to deserialize as `UserStakeInfo`.
+    #[account(
+        mut,
+        seeds = [b"user".as_ref(), authority.as_ref()],
+        bump,
+    )]
+    pub user: UncheckedAccount<'info>,
+    /// Pays the rent of the added fields.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, lockup: u16, referrer: Option<Pubkey>)]
 pub struct Stake<'info> {
     #[account(
         mut,
@@ -419,7 +611,8 @@ pub struct Stake<'info> {
     )]
     pub staking_pool: Account<'info, StakingPool>,
    #[account(mut,
-    constraint = mint.key() == config_account.config.char_token_mint)]
+    constraint = mint.key() == config_account.config.char_token_mint,
+    constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         init_if_needed,
@@ -453,8 +646,23 @@ pub struct Stake<'info> {
     )]
     pub pool_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    /// Only required when a referrer is provided.
+    #[account(
+        mut,
+        seeds = [b"referral_config".as_ref()],
+        bump = referral_config.bump,
+    )]
+    pub referral_config: Option<Account<'info, ReferralConfig>>,
+    /// Staking info of the referrer, only required when a referrer is provided.
+    #[account(
+        mut,
+        seeds = [b"user".as_ref(), referrer.unwrap_or_default().as_ref()],
+        bump = referrer_user.bump,
+    )]
+    pub referrer_user: Option<Account<'info, UserStakeInfo>>,
+
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
@@ -473,7 +681,8 @@ pub struct Unstake<'info> {
     )]
     pub staking_pool: Account<'info, StakingPool>,
  #[account(mut,
-    constraint = mint.key() == config_account.config.char_token_mint)]
+    constraint = mint.key() == config_account.config.char_token_mint,
+    constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
@@ -510,7 +719,7 @@ pub struct Unstake<'info> {
     )]
     pub pool_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
@@ -557,6 +766,7 @@ pub struct ClaimReward<'info> {
         )]
     pub config_account: Account<'info, ConfigAccount>,
     #[account(
+        mut,
         seeds = [b"staking_pool".as_ref(), staking_pool.token_mint.as_ref()],
         bump = staking_pool.bump,
     )]
@@ -571,6 +781,7 @@ pub struct ClaimReward<'info> {
     )]
     pub user: Account<'info, UserStakeInfo>,
     #[account(
+        mut,
         seeds = [b"user_stake".as_ref(), user_authority.key().as_ref(),stake_id.to_le_bytes().as_ref()],
         bump
     )]
@@ -600,9 +811,26 @@ pub struct ClaimReward<'info> {
     )]
     pub staking_reward: Account<'info, StakingRewards>,
  #[account(mut,
-    constraint = mint.key() == config_account.config.char_token_mint)]
+    constraint = mint.key() == config_account.config.char_token_mint,
+    constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
     pub mint: InterfaceAccount<'info, Mint>,
-    pub token_program: Program<'info, Token>,
+
+    /// Referral accounts, only required when the user was referred.
+    #[account(
+        mut,
+        seeds = [b"referral_config".as_ref()],
+        bump = referral_config.bump,
+    )]
+    pub referral_config: Option<Account<'info, ReferralConfig>>,
+    #[account(
+        mut,
+        seeds = [b"user".as_ref(), user.referrer.as_ref()],
+        bump = referrer_user.bump,
+    )]
+    pub referrer_user: Option<Account<'info, UserStakeInfo>>,
+    #[account(mut)]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Clone, Copy, Debug, AnchorSerialize, AnchorDeserialize)]
@@ -623,6 +851,7 @@ pub struct StakingPool {
     pub reward_issued: u64,
     pub bump: u8,
     pub stake_lockup_reward_array: [LockupReward; 4],
+    pub extra_rewards: [ExtraReward; MAX_EXTRA_REWARD_MINTS], // partner reward mints
 }
 
 #[account]
@@ -642,8 +871,30 @@ pub struct UserStakeInfo {
     pub bump: u8,
     pub last_vote_time:u64,
     pub consumed_stake_id_upper_bound: u64, // default to 0
+    pub referrer: Pubkey,           // staker who referred this user, default if none
+    pub referral_count: u64,        // number of users referred by this user
+    pub referral_rewards: u64,      // total referral rewards earned by this user
+    pub governance_locked_until: u64, // end of the latest milestone or veto vote of the user
 }
 
+/// Layout of the staking info accounts created before referrals, see `migrate_user_stake_info`.
+#[derive(AnchorDeserialize)]
+struct LegacyUserStakeInfo {
+    authority: Pubkey,
+    staking_pool: Pubkey,
+    eligible_at: u64,
+    voting_power: u64,
+    total_amount: u64,
+    reward_issued: u64,
+    stake_count: u64,
+    bump: u8,
+    last_vote_time: u64,
+    consumed_stake_id_upper_bound: u64,
+}
+
+/// Size of the staking info accounts created before referrals.
+pub const LEGACY_USER_STAKE_INFO_SPACE: usize = 8 + std::mem::size_of::<LegacyUserStakeInfo>();
+
 #[account]
 pub struct UserStakesEntry {
     pub stake_id: u64,
@@ -653,4 +904,5 @@ pub struct UserStakesEntry {
     pub unstake_requested_at: u64,
     pub current_period: u64,
     pub unstaked_at: u64,
+    pub extra_rewards: [ExtraRewardCheckpoint; MAX_EXTRA_REWARD_MINTS],
 }
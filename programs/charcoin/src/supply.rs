@@ -0,0 +1,209 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+
+use crate::{ConfigAccount, CustomError, StakingPool, Treasury};
+
+/// Max number of wallets excluded from the circulating supply.
+pub const MAX_SUPPLY_EXCLUSIONS: usize = 16;
+
+/// Program and foundation wallets whose CHAR is not circulating.
+#[account]
+pub struct SupplyConfig {
+    pub excluded_wallets: [Pubkey; MAX_SUPPLY_EXCLUSIONS],
+    pub excluded_count: u8,
+    pub bump: u8,
+}
+
+impl SupplyConfig {
+    /// Sets the excluded wallets, each wallet is kept once so its balance is only counted once.
+    fn set_excluded(&mut self, wallets: &[Pubkey]) -> Result<()> {
+        let mut unique: Vec<Pubkey> = Vec::with_capacity(wallets.len());
+        for wallet in wallets {
+            if !unique.contains(wallet) {
+                unique.push(*wallet);
+            }
+        }
+        require!(
+            unique.len() <= MAX_SUPPLY_EXCLUSIONS,
+            CustomError::TooManySupplyExclusions
+        );
+        self.excluded_wallets = [Pubkey::default(); MAX_SUPPLY_EXCLUSIONS];
+        self.excluded_wallets[..unique.len()].copy_from_slice(&unique);
+        self.excluded_count = unique.len() as u8;
+        Ok(())
+    }
+}
+
+/// Tokenomics numbers returned by `get_supply_stats`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SupplyStats {
+    pub total_supply: u64,
+    pub total_burned: u64,
+    pub total_staked: u64,
+    pub treasury_balance: u64,
+    pub reward_vault_balance: u64,
+    pub excluded_balance: u64, // held by the excluded wallets
+    pub circulating_supply: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeSupplyConfig<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<SupplyConfig>(),
+        seeds = [b"supply_config".as_ref()],
+        bump
+    )]
+    pub supply_config: Account<'info, SupplyConfig>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetSupplyExclusions<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"supply_config".as_ref()],
+        bump = supply_config.bump,
+    )]
+    pub supply_config: Account<'info, SupplyConfig>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetSupplyStats<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+    #[account(
+        seeds = [b"supply_config".as_ref()],
+        bump = supply_config.bump,
+    )]
+    pub supply_config: Box<Account<'info, SupplyConfig>>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint)]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds = [b"staking_pool".as_ref(), mint.key().as_ref()],
+        bump = staking_pool.bump,
+    )]
+    pub staking_pool: Box<Account<'info, StakingPool>>,
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+    #[account(
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump = treasury.vault_bump,
+    )]
+    pub treasury_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = staking_reward_ata.mint == config_account.config.char_token_mint,
+        constraint = staking_reward_ata.owner == staking_pool.staking_reward_account
+    )]
+    pub staking_reward_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+}
+
+/// Creates the supply config, excluding the wallets of the config.
+pub fn initialize_supply_config(ctx: Context<InitializeSupplyConfig>) -> Result<()> {
+    let config = &ctx.accounts.config_account.config;
+    let supply_config = &mut ctx.accounts.supply_config;
+    supply_config.set_excluded(&[
+        config.monthly_top_tier_wallet,
+        config.monthly_charity_lottery_wallet,
+        config.annual_top_tier_wallet,
+        config.annual_charity_lottery_wallet,
+        config.monthly_one_time_causes_wallet,
+        config.monthly_infinite_impact_causes_wallet,
+        config.annual_one_time_causes_wallet,
+        config.annual_infinite_impact_causes_wallet,
+        config.char_funds,
+        config.marketing_wallet_1,
+        config.marketing_wallet_2,
+        config.death_wallet,
+        config.treasury_authority,
+    ])?;
+    supply_config.bump = ctx.bumps.supply_config;
+    Ok(())
+}
+
+/// Replaces the list of wallets excluded from the circulating supply.
+pub fn set_supply_exclusions(
+    ctx: Context<SetSupplyExclusions>,
+    wallets: Vec<Pubkey>,
+) -> Result<()> {
+    ctx.accounts.supply_config.set_excluded(&wallets)
+}
+
+/// Returns the tokenomics numbers. The CHAR associated token account of every
+/// excluded wallet is passed as remaining accounts, in the order of the supply config,
+/// circulating supply is the total supply minus the treasury vault, the staking reward
+/// vault and those accounts. Staked CHAR belongs to holders and stays in the
+/// circulating supply.
+pub fn get_supply_stats<'info>(
+    ctx: Context<'_, '_, 'info, 'info, GetSupplyStats<'info>>,
+) -> Result<SupplyStats> {
+    let supply_config = &ctx.accounts.supply_config;
+    let excluded_wallets = &supply_config.excluded_wallets[..supply_config.excluded_count as usize];
+    require!(
+        ctx.remaining_accounts.len() == excluded_wallets.len(),
+        CustomError::InvalidSupplyExclusion
+    );
+    let mint = ctx.accounts.mint.key();
+    let token_program = ctx.accounts.mint.to_account_info().owner;
+    let mut excluded_balance: u64 = 0;
+    for (wallet, info) in excluded_wallets.iter().zip(ctx.remaining_accounts) {
+        require!(
+            info.key()
+                == get_associated_token_address_with_program_id(wallet, &mint, token_program),
+            CustomError::InvalidSupplyExclusion
+        );
+        // a wallet without a token account holds no CHAR
+        if info.data_is_empty() {
+            continue;
+        }
+        let token_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+        excluded_balance = excluded_balance
+            .checked_add(token_account.amount)
+            .ok_or(CustomError::MathError)?;
+    }
+
+    let total_supply = ctx.accounts.mint.supply;
+    let treasury_balance = ctx.accounts.treasury_vault.amount;
+    let reward_vault_balance = ctx.accounts.staking_reward_ata.amount;
+    let stats = SupplyStats {
+        total_supply,
+        total_burned: ctx.accounts.config_account.config.total_burned,
+        total_staked: ctx.accounts.staking_pool.total_staked,
+        treasury_balance,
+        reward_vault_balance,
+        excluded_balance,
+        circulating_supply: total_supply
+            .saturating_sub(treasury_balance)
+            .saturating_sub(reward_vault_balance)
+            .saturating_sub(excluded_balance),
+    };
+    msg!("Circulating supply {}", stats.circulating_supply);
+    Ok(stats)
+}
@@ -0,0 +1,135 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::transfer::transfer_checked_with_hook;
+use crate::{ConfigAccount, CustomError};
+
+/// Owner of the treasury vault. Funds leave the vault only through the
+/// program's release and distribution instructions, signed by this PDA.
+#[account]
+pub struct Treasury {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub bump: u8,
+    pub vault_bump: u8,
+}
+
+#[event]
+pub struct TreasuryMigratedEvent {
+    pub amount: u64,
+    pub vault_balance: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeTreasury<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<Treasury>(),
+        seeds = [b"treasury".as_ref()],
+        bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = mint,
+        token::authority = treasury,
+        token::token_program = token_program,
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Creates the treasury PDA and its CHAR vault.
+pub fn initialize_treasury(ctx: Context<InitializeTreasury>) -> Result<()> {
+    let treasury = &mut ctx.accounts.treasury;
+    treasury.mint = ctx.accounts.mint.key();
+    treasury.vault = ctx.accounts.treasury_vault.key();
+    treasury.bump = ctx.bumps.treasury;
+    treasury.vault_bump = ctx.bumps.treasury_vault;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateTreasury<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        seeds = [b"treasury".as_ref()],
+        bump = treasury.bump,
+    )]
+    pub treasury: Account<'info, Treasury>,
+    #[account(
+        mut,
+        seeds = [b"treasury_vault".as_ref(), mint.key().as_ref()],
+        bump = treasury.vault_bump,
+    )]
+    pub treasury_vault: InterfaceAccount<'info, TokenAccount>,
+    ///  Former treasury token account owned by the treasury authority.
+    #[account(
+        mut,
+        constraint = treasury_ata.mint == config_account.config.char_token_mint,
+        constraint = treasury_ata.owner == config_account.config.treasury_authority
+    )]
+    pub treasury_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = config_account.config.treasury_authority == treasury_authority.key()
+    )]
+    pub treasury_authority: Signer<'info>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Moves the whole balance of the former treasury token account into the vault.
+pub fn migrate_treasury<'info>(
+    ctx: Context<'_, '_, 'info, 'info, MigrateTreasury<'info>>,
+) -> Result<()> {
+    let amount = ctx.accounts.treasury_ata.amount;
+    require!(amount > 0, CustomError::TreasuryEmpty);
+
+    transfer_checked_with_hook(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.treasury_ata.to_account_info(),
+                to: ctx.accounts.treasury_vault.to_account_info(),
+                authority: ctx.accounts.treasury_authority.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        amount,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    ctx.accounts.treasury_vault.reload()?;
+    emit!(TreasuryMigratedEvent {
+        amount,
+        vault_balance: ctx.accounts.treasury_vault.amount,
+        timestamp: Clock::get()?.unix_timestamp as u64,
+    });
+    Ok(())
+}
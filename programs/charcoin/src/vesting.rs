@@ -0,0 +1,260 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::transfer::transfer_checked_with_hook;
+use crate::{ConfigAccount, CustomError};
+
+/// Vesting stream of a marketing wallet. Deposits are held in an escrow token account
+/// and unlock linearly over `duration`. A deposit into a running stream is merged with it,
+/// the end of the stream becomes the average of the remaining time and `duration`
+/// weighted by the locked and deposited amounts.
+#[account]
+pub struct MarketingVesting {
+    pub wallet: Pubkey, // marketing wallet the stream belongs to
+    pub escrow: Pubkey,
+    pub duration: i64, // vesting duration of new deposits in seconds
+    pub locked: u64,   // amount still vesting at `start_time`
+    pub unlocked: u64, // vested amount not withdrawn yet
+    pub start_time: i64,
+    pub end_time: i64,
+    pub total_deposited: u64,
+    pub total_withdrawn: u64,
+    pub bump: u8,
+    pub escrow_bump: u8,
+}
+
+impl MarketingVesting {
+    /// Moves the amount vested since `start_time` from `locked` to `unlocked`.
+    pub fn checkpoint(&mut self, now: i64) -> Result<()> {
+        if now <= self.start_time || self.locked == 0 {
+            return Ok(());
+        }
+        let vested = if now >= self.end_time {
+            self.locked
+        } else {
+            ((self.locked as u128)
+                .checked_mul((now - self.start_time) as u128)
+                .ok_or(CustomError::MathError)?
+                / (self.end_time - self.start_time) as u128) as u64
+        };
+        self.locked -= vested;
+        self.unlocked = self
+            .unlocked
+            .checked_add(vested)
+            .ok_or(CustomError::MathError)?;
+        self.start_time = now;
+        Ok(())
+    }
+
+    /// Adds a deposit to the stream.
+    pub fn deposit(&mut self, amount: u64, now: i64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        self.checkpoint(now)?;
+        let remaining = self.end_time.saturating_sub(now).max(0) as u128;
+        let locked = self.locked as u128;
+        let total = locked + amount as u128;
+        let weighted_end =
+            (locked * remaining + amount as u128 * self.duration as u128).div_ceil(total);
+
+        self.locked = self
+            .locked
+            .checked_add(amount)
+            .ok_or(CustomError::MathError)?;
+        self.start_time = now;
+        self.end_time = now + weighted_end as i64;
+        self.total_deposited = self
+            .total_deposited
+            .checked_add(amount)
+            .ok_or(CustomError::MathError)?;
+        Ok(())
+    }
+
+    /// Deposits whatever reached the escrow since the last deposit. Reading the
+    /// escrow balance accounts for the transfer fee withheld on the way in.
+    pub fn deposit_escrow_balance(&mut self, escrow_balance: u64, now: i64) -> Result<()> {
+        let received = escrow_balance
+            .checked_sub(self.locked + self.unlocked)
+            .ok_or(CustomError::MathError)?;
+        self.deposit(received, now)
+    }
+}
+
+#[event]
+pub struct MarketingVestingWithdrawnEvent {
+    pub wallet: Pubkey,
+    pub amount: u64,
+    pub locked: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeMarketingVesting<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    /// CHECK: one of the two marketing wallets of the config.
+    #[account(
+        constraint = marketing_wallet.key() == config_account.config.marketing_wallet_1
+            || marketing_wallet.key() == config_account.config.marketing_wallet_2
+            @ CustomError::InvalidMarketingWallet
+    )]
+    pub marketing_wallet: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + std::mem::size_of::<MarketingVesting>(),
+        seeds = [b"marketing_vesting".as_ref(), marketing_wallet.key().as_ref()],
+        bump
+    )]
+    pub marketing_vesting: Account<'info, MarketingVesting>,
+    #[account(
+        init,
+        payer = admin,
+        token::mint = mint,
+        token::authority = marketing_vesting,
+        token::token_program = token_program,
+        seeds = [b"marketing_escrow".as_ref(), marketing_wallet.key().as_ref()],
+        bump
+    )]
+    pub marketing_escrow: InterfaceAccount<'info, TokenAccount>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetMarketingVestingDuration<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"marketing_vesting".as_ref(), marketing_vesting.wallet.as_ref()],
+        bump = marketing_vesting.bump,
+    )]
+    pub marketing_vesting: Account<'info, MarketingVesting>,
+    #[account(
+        constraint = admin.key() == config_account.config.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawVestedMarketing<'info> {
+    #[account(
+        seeds=[b"config".as_ref()],
+        bump
+    )]
+    pub config_account: Account<'info, ConfigAccount>,
+    #[account(
+        mut,
+        seeds = [b"marketing_vesting".as_ref(), marketing_wallet.key().as_ref()],
+        bump = marketing_vesting.bump,
+    )]
+    pub marketing_vesting: Account<'info, MarketingVesting>,
+    #[account(
+        mut,
+        seeds = [b"marketing_escrow".as_ref(), marketing_wallet.key().as_ref()],
+        bump = marketing_vesting.escrow_bump,
+    )]
+    pub marketing_escrow: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = wallet_token_account.mint == config_account.config.char_token_mint,
+        constraint = wallet_token_account.owner == marketing_wallet.key()
+    )]
+    pub wallet_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub marketing_wallet: Signer<'info>,
+    #[account(constraint = mint.key() == config_account.config.char_token_mint,
+        constraint = *mint.to_account_info().owner == token_program.key() @ CustomError::TokenProgramMismatch)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Creates the vesting stream and escrow of a marketing wallet.
+pub fn initialize_marketing_vesting(
+    ctx: Context<InitializeMarketingVesting>,
+    duration: i64,
+) -> Result<()> {
+    require!(duration > 0, CustomError::InvalidVestingDuration);
+    let now = Clock::get()?.unix_timestamp;
+    let marketing_vesting = &mut ctx.accounts.marketing_vesting;
+    marketing_vesting.wallet = ctx.accounts.marketing_wallet.key();
+    marketing_vesting.escrow = ctx.accounts.marketing_escrow.key();
+    marketing_vesting.duration = duration;
+    marketing_vesting.start_time = now;
+    marketing_vesting.end_time = now;
+    marketing_vesting.bump = ctx.bumps.marketing_vesting;
+    marketing_vesting.escrow_bump = ctx.bumps.marketing_escrow;
+    Ok(())
+}
+
+/// Changes the vesting duration of the next deposits.
+pub fn set_marketing_vesting_duration(
+    ctx: Context<SetMarketingVestingDuration>,
+    duration: i64,
+) -> Result<()> {
+    require!(duration > 0, CustomError::InvalidVestingDuration);
+    ctx.accounts.marketing_vesting.duration = duration;
+    Ok(())
+}
+
+/// Sends the amount unlocked so far to the marketing wallet.
+pub fn withdraw_vested_marketing<'info>(
+    ctx: Context<'_, '_, 'info, 'info, WithdrawVestedMarketing<'info>>,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let marketing_vesting = &mut ctx.accounts.marketing_vesting;
+    marketing_vesting.checkpoint(now)?;
+    let amount = marketing_vesting.unlocked;
+    require!(amount > 0, CustomError::NothingVested);
+    marketing_vesting.unlocked = 0;
+    marketing_vesting.total_withdrawn = marketing_vesting
+        .total_withdrawn
+        .checked_add(amount)
+        .ok_or(CustomError::MathError)?;
+
+    let wallet = ctx.accounts.marketing_wallet.key();
+    let seeds: &[&[u8]] = &[
+        b"marketing_vesting",
+        wallet.as_ref(),
+        &[ctx.accounts.marketing_vesting.bump],
+    ];
+    let signer = &[seeds];
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.marketing_escrow.to_account_info(),
+                to: ctx.accounts.wallet_token_account.to_account_info(),
+                authority: ctx.accounts.marketing_vesting.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+            },
+            signer,
+        )
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+        amount,
+        ctx.accounts.mint.decimals,
+    )?;
+
+    emit!(MarketingVestingWithdrawnEvent {
+        wallet,
+        amount,
+        locked: ctx.accounts.marketing_vesting.locked,
+        timestamp: now as u64,
+    });
+    Ok(())
+}
@@ -0,0 +1,154 @@
+#![allow(unexpected_cfgs)]
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use anchor_spl::token_2022::spl_token_2022::extension::transfer_hook::TransferHookAccount;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, StateWithExtensions,
+};
+use anchor_spl::token_2022::spl_token_2022::state::{Account as SplAccount, Mint as SplMint};
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use spl_discriminator::SplDiscriminate;
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+declare_id!("GP8LKC3KR9KhsQ6d2u7kEir1njwD5G8eJiu51BAVGC4g");
+
+/// Companion program of CHAR Coin. Token-2022 calls it on every CHAR transfer
+/// and it records the transfer fee charged into a fee accumulator, so the
+/// charcoin program can distribute exactly the fees that were collected.
+/// It lives in its own program because a hook inside charcoin would be
+/// re-entered by every transfer charcoin makes itself.
+#[program]
+pub mod charcoin_fee_hook {
+    use super::*;
+
+    /// Creates the extra account meta list read by Token-2022 and the fee accumulator of a mint.
+    pub fn initialize_extra_account_meta_list(
+        ctx: Context<InitializeExtraAccountMetaList>,
+    ) -> Result<()> {
+        let extra_account_metas = extra_account_metas()?;
+        let mut data = ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &extra_account_metas)?;
+
+        let fee_accumulator = &mut ctx.accounts.fee_accumulator;
+        fee_accumulator.mint = ctx.accounts.mint.key();
+        fee_accumulator.bump = ctx.bumps.fee_accumulator;
+        Ok(())
+    }
+
+    /// Records the fee charged on a CHAR transfer.
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn transfer_hook(ctx: Context<TransferHook>, amount: u64) -> Result<()> {
+        // only count transfers that Token-2022 is actually executing
+        {
+            let source_info = ctx.accounts.source_token.to_account_info();
+            let source_data = source_info.try_borrow_data()?;
+            let source = StateWithExtensions::<SplAccount>::unpack(&source_data)?;
+            let hook_account = source.get_extension::<TransferHookAccount>()?;
+            require!(
+                bool::from(hook_account.transferring),
+                FeeHookError::NotTransferring
+            );
+        }
+
+        let fee = {
+            let mint_info = ctx.accounts.mint.to_account_info();
+            let mint_data = mint_info.try_borrow_data()?;
+            let mint = StateWithExtensions::<SplMint>::unpack(&mint_data)?;
+            match mint.get_extension::<TransferFeeConfig>() {
+                Ok(fee_config) => fee_config
+                    .calculate_epoch_fee(Clock::get()?.epoch, amount)
+                    .ok_or(FeeHookError::MathError)?,
+                Err(_) => 0,
+            }
+        };
+
+        let fee_accumulator = &mut ctx.accounts.fee_accumulator;
+        fee_accumulator.total_fees = fee_accumulator
+            .total_fees
+            .checked_add(fee)
+            .ok_or(FeeHookError::MathError)?;
+        fee_accumulator.total_transfers += 1;
+        Ok(())
+    }
+}
+
+/// Accounts Token-2022 appends to the execute instruction: the fee accumulator of the mint.
+fn extra_account_metas() -> Result<Vec<ExtraAccountMeta>> {
+    Ok(vec![ExtraAccountMeta::new_with_seeds(
+        &[
+            Seed::Literal {
+                bytes: b"fee_accumulator".to_vec(),
+            },
+            Seed::AccountKey { index: 1 }, // mint
+        ],
+        false,
+        true,
+    )?])
+}
+
+/// Running total of the transfer fees charged on a mint.
+#[account]
+pub struct FeeAccumulator {
+    pub mint: Pubkey,
+    pub total_fees: u64,      // total fees charged since the hook was installed
+    pub total_transfers: u64, // number of transfers recorded
+    pub bump: u8,
+}
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: ExtraAccountMetaList account, initialized by the instruction.
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(extra_account_metas()?.len())?,
+        seeds = [b"extra-account-metas".as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<FeeAccumulator>(),
+        seeds = [b"fee_accumulator".as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub fee_accumulator: Account<'info, FeeAccumulator>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Order of the accounts is fixed by the transfer hook interface.
+#[derive(Accounts)]
+pub struct TransferHook<'info> {
+    #[account(token::mint = mint)]
+    pub source_token: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(token::mint = mint)]
+    pub destination_token: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: owner or delegate of the source account, not used.
+    pub owner: UncheckedAccount<'info>,
+    /// CHECK: ExtraAccountMetaList account.
+    #[account(
+        seeds = [b"extra-account-metas".as_ref(), mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"fee_accumulator".as_ref(), mint.key().as_ref()],
+        bump = fee_accumulator.bump
+    )]
+    pub fee_accumulator: Account<'info, FeeAccumulator>,
+}
+
+#[error_code]
+pub enum FeeHookError {
+    #[msg("Hook called outside of a transfer")]
+    NotTransferring,
+    #[msg("Math error occurred.")]
+    MathError,
+}
@@ -0,0 +1,167 @@
+#![allow(unexpected_cfgs)]
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::onchain::invoke_transfer_checked;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+declare_id!("H6K3DhQr4JmBTQvX3nJ3Xkp9NutiZV17MMcfzwW243GF");
+
+/// Swap fee in basis points.
+pub const FEE_BPS: u64 = 30;
+
+/// Minimal constant product AMM with the swap interface expected by the
+/// charcoin buyback adapter. Only meant for local tests: liquidity is added
+/// by sending tokens to the pool vaults directly.
+#[program]
+pub mod mock_amm {
+    use super::*;
+
+    /// Creates a pool and its two vaults.
+    pub fn initialize_pool(ctx: Context<InitializePool>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.base_mint = ctx.accounts.base_mint.key();
+        pool.quote_mint = ctx.accounts.quote_mint.key();
+        pool.base_vault = ctx.accounts.base_vault.key();
+        pool.quote_vault = ctx.accounts.quote_vault.key();
+        pool.bump = ctx.bumps.pool;
+        Ok(())
+    }
+
+    /// Swaps `amount_in` of the source mint for the destination mint at the
+    /// constant product price. Remaining accounts are forwarded to both
+    /// transfers for mints with a transfer hook.
+    pub fn swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<()> {
+        require!(amount_in > 0, MockAmmError::ZeroAmount);
+        let reserve_in = ctx.accounts.pool_source_vault.amount as u128;
+        let reserve_out = ctx.accounts.pool_destination_vault.amount as u128;
+        let amount_in_after_fee = amount_in as u128 * (10_000 - FEE_BPS) as u128 / 10_000;
+        let amount_out =
+            (reserve_out * amount_in_after_fee / (reserve_in + amount_in_after_fee)) as u64;
+        require!(amount_out >= min_amount_out, MockAmmError::SlippageExceeded);
+
+        invoke_transfer_checked(
+            ctx.accounts.source_token_program.key,
+            ctx.accounts.user_source.to_account_info(),
+            ctx.accounts.source_mint.to_account_info(),
+            ctx.accounts.pool_source_vault.to_account_info(),
+            ctx.accounts.user.to_account_info(),
+            ctx.remaining_accounts,
+            amount_in,
+            ctx.accounts.source_mint.decimals,
+            &[],
+        )?;
+
+        let pool = &ctx.accounts.pool;
+        let seeds: &[&[u8]] = &[
+            b"pool",
+            pool.base_mint.as_ref(),
+            pool.quote_mint.as_ref(),
+            &[pool.bump],
+        ];
+        invoke_transfer_checked(
+            ctx.accounts.destination_token_program.key,
+            ctx.accounts.pool_destination_vault.to_account_info(),
+            ctx.accounts.destination_mint.to_account_info(),
+            ctx.accounts.user_destination.to_account_info(),
+            ctx.accounts.pool.to_account_info(),
+            ctx.remaining_accounts,
+            amount_out,
+            ctx.accounts.destination_mint.decimals,
+            &[seeds],
+        )?;
+        Ok(())
+    }
+}
+
+#[account]
+pub struct Pool {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub bump: u8,
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<Pool>(),
+        seeds = [b"pool".as_ref(), base_mint.key().as_ref(), quote_mint.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        init,
+        payer = payer,
+        token::mint = base_mint,
+        token::authority = pool,
+        token::token_program = base_token_program,
+        seeds = [b"pool_vault".as_ref(), pool.key().as_ref(), base_mint.key().as_ref()],
+        bump
+    )]
+    pub base_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = payer,
+        token::mint = quote_mint,
+        token::authority = pool,
+        token::token_program = quote_token_program,
+        seeds = [b"pool_vault".as_ref(), pool.key().as_ref(), quote_mint.key().as_ref()],
+        bump
+    )]
+    pub quote_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = quote_token_program)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Order of the accounts is the swap interface of the buyback adapter.
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    pub pool: Account<'info, Pool>,
+    #[account(mut, token::mint = source_mint)]
+    pub user_source: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = destination_mint)]
+    pub user_destination: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = pool_source_vault.key() == pool.base_vault
+            || pool_source_vault.key() == pool.quote_vault @ MockAmmError::InvalidVault,
+        token::mint = source_mint
+    )]
+    pub pool_source_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = pool_destination_vault.key() == pool.base_vault
+            || pool_destination_vault.key() == pool.quote_vault @ MockAmmError::InvalidVault,
+        constraint = pool_destination_vault.key() != pool_source_vault.key() @ MockAmmError::InvalidVault,
+        token::mint = destination_mint
+    )]
+    pub pool_destination_vault: InterfaceAccount<'info, TokenAccount>,
+    pub source_mint: InterfaceAccount<'info, Mint>,
+    pub destination_mint: InterfaceAccount<'info, Mint>,
+    pub user: Signer<'info>,
+    pub source_token_program: Interface<'info, TokenInterface>,
+    pub destination_token_program: Interface<'info, TokenInterface>,
+}
+
+#[error_code]
+pub enum MockAmmError {
+    #[msg("Swap amount must be positive")]
+    ZeroAmount,
+    #[msg("Swap output below the minimum")]
+    SlippageExceeded,
+    #[msg("Vault does not belong to the pool")]
+    InvalidVault,
+}
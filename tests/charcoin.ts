@@ -1,7 +1,9 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Charcoin } from "../target/types/charcoin";
-import { createInitializeMintInstruction, createInitializeTransferFeeConfigInstruction, createMint, ExtensionType, getMintLen, getOrCreateAssociatedTokenAccount, mintTo, TOKEN_2022_PROGRAM_ID } from "@solana/spl-token";
+import { CharcoinFeeHook } from "../target/types/charcoin_fee_hook";
+import { MockAmm } from "../target/types/mock_amm";
+import { createInitializeMintInstruction, createInitializeTransferFeeConfigInstruction, createInitializeTransferHookInstruction, createMint, ExtensionType, getAssociatedTokenAddressSync, getMintLen, getOrCreateAssociatedTokenAccount, mintTo, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID as SPL_TOKEN_PROGRAM_ID } from "@solana/spl-token";
 import { assert, use } from "chai";
 import { ASSOCIATED_PROGRAM_ID } from "@coral-xyz/anchor/dist/cjs/utils/token";
 const TOKEN_PROGRAM_ID = TOKEN_2022_PROGRAM_ID
@@ -33,6 +35,72 @@ describe("char coin test", () => {
     [Buffer.from('config')],
     program.programId
   );
+  const [feeWithdrawAuthority] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('fee_withdraw_authority')],
+    program.programId
+  );
+  const [feeRelease] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('fee_release')],
+    program.programId
+  );
+  const [distributionConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('distribution_config')],
+    program.programId
+  );
+  const [releaseSchedule] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('release_schedule')],
+    program.programId
+  );
+  const [outflowLimiter] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('outflow_limiter')],
+    program.programId
+  );
+  const [burnLedger] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('burn_ledger')],
+    program.programId
+  );
+  const [burnPolicy] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('burn_policy')],
+    program.programId
+  );
+  const [roundRegistry] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('round_registry')],
+    program.programId
+  );
+  const [votingRound] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('voting_round'), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+    program.programId
+  );
+  // donation vault authority and token account of a charity category
+  const donationVaultOf = (category: number) => [
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('donation_vault'), Buffer.from([category])],
+      program.programId
+    )[0],
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('donation_vault'), tokenMint.toBuffer(), Buffer.from([category])],
+      program.programId
+    )[0],
+  ];
+  const [governanceConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('governance_config')],
+    program.programId
+  );
+  const [buybackConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('buyback_config')],
+    program.programId
+  );
+  // the death wallet is a program PDA, its CHAR is burned by keepers
+  const [deathWalletPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('death_wallet')],
+    program.programId
+  );
+  const [treasury] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('treasury')],
+    program.programId
+  );
+  const hookProgram = anchor.workspace.charcoinFeeHook as Program<CharcoinFeeHook>;
+  const ammProgram = anchor.workspace.mockAmm as Program<MockAmm>;
 
 
 
@@ -52,6 +120,22 @@ describe("char coin test", () => {
   let charFunds = anchor.web3.Keypair.generate()
   let marketingWallet1 = anchor.web3.Keypair.generate()
   let marketingWallet2 = anchor.web3.Keypair.generate()
+  const [marketingVesting1] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('marketing_vesting'), marketingWallet1.publicKey.toBuffer()],
+    program.programId
+  );
+  const [marketingEscrow1] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('marketing_escrow'), marketingWallet1.publicKey.toBuffer()],
+    program.programId
+  );
+  const [marketingVesting2] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('marketing_vesting'), marketingWallet2.publicKey.toBuffer()],
+    program.programId
+  );
+  const [marketingEscrow2] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from('marketing_escrow'), marketingWallet2.publicKey.toBuffer()],
+    program.programId
+  );
   let deathWallet = anchor.web3.Keypair.generate()
   let treasuryAuthority = anchor.web3.Keypair.generate()
 
@@ -64,15 +148,21 @@ describe("char coin test", () => {
   let marketingWallet1Ata
   let marketingWallet2Ata
   let treasuryAuthorityAta
+  let treasuryVault
   let deathWalletAta
   let stakingRewardAccount;
   let stakingRewardAta;
+  let extraAccountMetaList
+  let feeAccumulator
+  // accounts Token-2022 needs to call the transfer hook on CHAR transfers
+  let hookAccounts
   before(async () => {
     await airdropSol(admin.publicKey, 20 * 1e9); // 20 SOL
     await airdropSol(user.publicKey, 5 * 1e9);
 
 const extensions = [
     ExtensionType.TransferFeeConfig,
+    ExtensionType.TransferHook,
 ];
 const mintLen = getMintLen(extensions);
   tokenMint = anchor.web3.Keypair.generate()
@@ -91,15 +181,50 @@ const maxFee = BigInt(9 * Math.pow(10, 6)); // 9 tokens
         createInitializeTransferFeeConfigInstruction(
             tokenMint.publicKey,
             admin.publicKey, // transferFeeConfigAuthority
-            admin.publicKey, // withdrawWithheldAuthority
+            feeWithdrawAuthority, // withdrawWithheldAuthority
             feeBasisPoints, // transferFeeBasisPoints
             maxFee, // maximumFee
             TOKEN_2022_PROGRAM_ID
         ),
+        createInitializeTransferHookInstruction(
+            tokenMint.publicKey,
+            admin.publicKey, // transferHookAuthority
+            hookProgram.programId,
+            TOKEN_2022_PROGRAM_ID
+        ),
         createInitializeMintInstruction(tokenMint.publicKey, 6, admin.publicKey, null, TOKEN_2022_PROGRAM_ID)
     );
     await sendAndConfirmTransaction(program.provider.connection, mintTransaction, [admin, tokenMint], undefined);
     tokenMint = tokenMint.publicKey;
+
+    [extraAccountMetaList] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('extra-account-metas'), tokenMint.toBuffer()],
+      hookProgram.programId
+    );
+    [feeAccumulator] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('fee_accumulator'), tokenMint.toBuffer()],
+      hookProgram.programId
+    );
+    await hookProgram.methods
+      .initializeExtraAccountMetaList()
+      .accounts({
+        payer: admin.publicKey,
+        extraAccountMetaList: extraAccountMetaList,
+        feeAccumulator: feeAccumulator,
+        mint: tokenMint,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    [treasuryVault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('treasury_vault'), tokenMint.toBuffer()],
+      program.programId
+    );
+    hookAccounts = [
+      { pubkey: extraAccountMetaList, isSigner: false, isWritable: false },
+      { pubkey: feeAccumulator, isSigner: false, isWritable: true },
+      { pubkey: hookProgram.programId, isSigner: false, isWritable: false },
+    ];
     // tokenMint = await createMint(
     //   program.provider.connection,
     //   admin,
@@ -198,8 +323,8 @@ const maxFee = BigInt(9 * Math.pow(10, 6)); // 9 tokens
       program.provider.connection,
       admin,
       tokenMint,
-      deathWallet.publicKey,
-      false,
+      deathWalletPda,
+      true,
           null,
     null,
     TOKEN_2022_PROGRAM_ID,
@@ -324,387 +449,2957 @@ const maxFee = BigInt(9 * Math.pow(10, 6)); // 9 tokens
 
 
 
-  it("stake", async () => {
-    // 1st time
-      
-
-    let [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from('user_stake'), user.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+  it("referral config", async () => {
+    const [referralConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('referral_config')],
       program.programId
     );
     await program.methods
-      .stakeTokensHandler(
-        new anchor.BN(10e6), // 1 tokens
-        new anchor.BN(1) // 1 days for devnet
+      .setReferralConfigHandler(
+        true,
+        500, // 5% of the referee's claimed rewards
+        new anchor.BN(1000e6) // 1000 tokens budget
       )
       .accounts({
         configAccount: configAccount,
-        mint:tokenMint,
-        stakingPool: stakingPool,
-        user: userStakePDA,
-        userStake: userStake,
-        userAuthority: user.publicKey,
-        userTokenAccount: userAta.address,
-        poolTokenAccount: stakingPoolAta.address,
+        referralConfig: referralConfig,
+        admin: admin.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
-        tokenProgram: TOKEN_PROGRAM_ID,
       })
-      .signers([user])
+      .signers([admin])
       .rpc();
 
-    const data = await program.account.userStakeInfo.fetch(userStakePDA)
-    const stake_data = await program.account.userStakesEntry.fetch(userStake)
-
-    // assert.equal(1e6, Number(data.totalAmount));
-    // // assert.equal(30, Number(stake_data.lockup));
-    // assert.equal(1, Number(stake_data.lockup));
-    // assert.equal(1e6, Number(stake_data.amount));
-
+    const data = await program.account.referralConfig.fetch(referralConfig)
+    assert.equal(data.enabled, true)
+    assert.equal(data.rewardBps, 500)
+    assert.equal(Number(data.totalPaid), 0)
   });
 
+  it("treasury vault", async () => {
+    await program.methods
+      .initializeTreasuryHandler()
+      .accounts({
+        configAccount: configAccount,
+        treasury: treasury,
+        treasuryVault: treasuryVault,
+        mint: tokenMint,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([admin])
+      .rpc();
 
+    const before = await program.provider.connection.getTokenAccountBalance(treasuryAuthorityAta.address)
+    await program.methods
+      .migrateTreasuryHandler()
+      .accounts({
+        configAccount: configAccount,
+        treasury: treasury,
+        treasuryVault: treasuryVault,
+        treasuryAta: treasuryAuthorityAta.address,
+        treasuryAuthority: treasuryAuthority.publicKey,
+        mint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([treasuryAuthority])
+      .rpc();
+    const vault = await program.provider.connection.getTokenAccountBalance(treasuryVault)
+    const ata = await program.provider.connection.getTokenAccountBalance(treasuryAuthorityAta.address)
+    assert.equal(ata.value.amount, "0")
+    // the vault receives the migrated balance minus the transfer fee, capped at 9 tokens
+    assert.equal(Number(vault.value.amount), Number(before.value.amount) - 9e6)
+  });
 
+  it("distribution config", async () => {
+    await program.methods
+      .initializeDistributionConfigHandler()
+      .accounts({
+        configAccount: configAccount,
+        distributionConfig: distributionConfig,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    const data = await program.account.distributionConfig.fetch(distributionConfig)
+    assert.equal(data.splits.donationEcosystem, 750)
 
-
-  
-
-
-  it("claim reward", async () => {
-        let balance = (await program.provider.connection.getTokenAccountBalance(userAta.address))
-        await sleep(3000)
-
-    await mintTo(
-      program.provider.connection,
-      admin, // fee payer
-      tokenMint,
-      stakingRewardAta.address, // destination ATA
-      admin, // mint authority
-      1_000_000_00000,
-          [],
-   {},
-   TOKEN_PROGRAM_ID,
-    );
-      const [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('user_stake'), user.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
-        program.programId
-      );
+    // every level must add up to 1000
+    try {
       await program.methods
-        .claimRewardHandler(new anchor.BN(0))
+        .updateDistributionConfigHandler({ ...data.splits, burn: 200 })
         .accounts({
           configAccount: configAccount,
-          stakingPool: stakingPool,
-          user: userStakePDA,
-          userAuthority: user.publicKey,
-          userStake: userStake,
-        mint:tokenMint,
-
-          userTokenAccount: userAta.address,
-          stakingRewardAta: stakingRewardAta.address,
-          tokenProgram: TOKEN_PROGRAM_ID,
+          distributionConfig: distributionConfig,
+          admin: admin.publicKey,
         })
-        .signers([user])
+        .signers([admin])
         .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("InvalidDistributionSplit"))
+    }
 
-            balance = (await program.provider.connection.getTokenAccountBalance(userAta.address))
-   
+    await program.methods
+      .updateDistributionConfigHandler({ ...data.splits, marketingWallet1: 450, marketingWallet2: 400 })
+      .accounts({
+        configAccount: configAccount,
+        distributionConfig: distributionConfig,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    const updated = await program.account.distributionConfig.fetch(distributionConfig)
+    assert.equal(updated.splits.marketingWallet1, 450)
   });
 
-
-  it("Emergency halt", async () => {
-    let data = await program.account.configAccount.fetch(configAccount)
-    assert.equal(data.config.halted, false)
+  it("release schedule", async () => {
     await program.methods
-      .changeEmergencyStateHandler(true)
+      .initializeReleaseScheduleHandler(
+        new anchor.BN(Math.floor(Date.now() / 1000) - 60), // period anchor
+        new anchor.BN(30 * 86400), // monthly period
+        new anchor.BN(365 * 86400), // annual period
+      )
       .accounts({
         configAccount: configAccount,
+        releaseSchedule: releaseSchedule,
+        admin: admin.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
-        payer: admin.publicKey,
-
       })
       .signers([admin])
       .rpc();
-    data = await program.account.configAccount.fetch(configAccount)
-    assert.equal(data.config.halted, true)
-
-
+    const data = await program.account.releaseSchedule.fetch(releaseSchedule)
+    assert.equal(Number(data.monthlyPeriod), 30 * 86400)
   });
 
+  it("outflow limiter", async () => {
+    await program.methods
+      .initializeOutflowLimiterHandler(new anchor.BN(1000e6), new anchor.BN(10000e6))
+      .accounts({
+        configAccount: configAccount,
+        outflowLimiter: outflowLimiter,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
 
-  it("halt distribute marketing funds", async () => {
+    // raised limits wait for the timelock
+    await program.methods
+      .proposeOutflowLimitsHandler(new anchor.BN(2000e6), new anchor.BN(20000e6))
+      .accounts({
+        configAccount: configAccount,
+        outflowLimiter: outflowLimiter,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
     try {
       await program.methods
-        .distributeMarketingFundsHandler(new anchor.BN(1000e6))
+        .applyOutflowLimitsHandler()
         .accounts({
           configAccount: configAccount,
-          signer1: treasuryAuthority.publicKey,
-          sourceAta: treasuryAuthorityAta.address,
-          destWallet1Ata: marketingWallet1Ata.address,
-          destWallet2Ata: marketingWallet2Ata.address,
-          tokenProgram: TOKEN_PROGRAM_ID,
-          deathWalletAta: deathWalletAta.address,
-                  mint:tokenMint,
-
+          outflowLimiter: outflowLimiter,
+          admin: admin.publicKey,
         })
-        .signers([treasuryAuthority])
+        .signers([admin])
         .rpc();
+      assert(false);
     } catch (e) {
-      if (e instanceof anchor.AnchorError) {
-        assert(e.message.includes("ProgramIsHalted"))
-      } else {
-        assert(false);
-      }
+      assert(e.message.includes("OutflowLimitsTimelocked"))
     }
 
+    // lower limits apply immediately
+    await program.methods
+      .proposeOutflowLimitsHandler(new anchor.BN(500e6), new anchor.BN(5000e6))
+      .accounts({
+        configAccount: configAccount,
+        outflowLimiter: outflowLimiter,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    const data = await program.account.outflowLimiter.fetch(outflowLimiter)
+    assert.equal(Number(data.dailyLimit), 500e6)
+    assert.equal(Number(data.pendingApplyAfter), 0)
+  });
 
+  it("burn ledger", async () => {
     await program.methods
-      .changeEmergencyStateHandler(false)
+      .initializeBurnLedgerHandler()
       .accounts({
         configAccount: configAccount,
+        burnLedger: burnLedger,
+        admin: admin.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
-        payer: admin.publicKey,
-
       })
       .signers([admin])
       .rpc();
-
-
-
+    const data = await program.account.burnLedger.fetch(burnLedger)
+    assert.equal(Number(data.burnCount), 0)
   });
-  it("distribute marketing funds", async () => {
-    let total = 1000e6; // 1000 tokens
-    let amount_wallet1 = (total * 425) / 1000; // 42.5%
-    let amount_wallet2 = (total * 425) / 1000; // 42.5%
-    let amount_death = (total * 150) / 1000; // 15%
-
-    let balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet1Ata.address))
-    assert.equal(balance.value.amount, "0");
-    balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet2Ata.address))
-    assert.equal(balance.value.amount, "0");
-    balance = (await program.provider.connection.getTokenAccountBalance(deathWalletAta.address))
-    assert.equal(balance.value.amount, "0");
 
+  it("buyback config", async () => {
     await program.methods
-      .distributeMarketingFundsHandler(new anchor.BN(total))
+      .initializeBuybackConfigHandler(new anchor.BN(1_000_000), new anchor.BN(3600), 50)
       .accounts({
         configAccount: configAccount,
-        signer1: treasuryAuthority.publicKey,
-        sourceAta: treasuryAuthorityAta.address,
-        destWallet1Ata: marketingWallet1Ata.address,
-        destWallet2Ata: marketingWallet2Ata.address,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        deathWalletAta: deathWalletAta.address,
-                mint:tokenMint,
+        buybackConfig: buybackConfig,
+        deathWallet: deathWalletPda,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    const data = await program.account.configAccount.fetch(configAccount)
+    assert.equal(data.config.deathWallet.toBase58(), deathWalletPda.toBase58())
 
+    try {
+      await program.methods
+        .updateBuybackConfigHandler(new anchor.BN(1_000_000), new anchor.BN(3600), 500)
+        .accounts({
+          configAccount: configAccount,
+          buybackConfig: buybackConfig,
+          admin: admin.publicKey,
+        })
+        .signers([admin])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("InvalidKeeperTip"))
+    }
+  });
 
+  it("burn policy", async () => {
+    await program.methods
+      .initializeBurnPolicyHandler()
+      .accounts({
+        configAccount: configAccount,
+        burnPolicy: burnPolicy,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
       })
-      .signers([treasuryAuthority])
+      .signers([admin])
       .rpc();
-    // balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet1Ata.address))
-    // assert.equal(balance.value.amount, amount_wallet1.toString());
-    // balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet2Ata.address))
-    // assert.equal(balance.value.amount, amount_wallet2.toString());
-    // balance = (await program.provider.connection.getTokenAccountBalance(deathWalletAta.address))
-    // assert.equal(balance.value.amount, amount_death.toString());
-  })
-  
-  it("release Funds", async () => {
+    let data = await program.account.burnPolicy.fetch(burnPolicy)
+    assert.equal(data.enabled, false)
 
-
-    let charFundsAta = await getOrCreateAssociatedTokenAccount(
-      program.provider.connection,
-      admin,
-      tokenMint,
-      charFunds.publicKey,
-      false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+    // min burn above max burn
+    try {
+      await program.methods
+        .setBurnPolicyHandler(true, 600, 100, 500, 50, [
+          { timestamp: new anchor.BN(0), targetSupply: new anchor.BN(1) },
+        ])
+        .accounts({
+          configAccount: configAccount,
+          burnPolicy: burnPolicy,
+          admin: admin.publicKey,
+        })
+        .signers([admin])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("InvalidBurnPolicy"))
+    }
+    // curve not in time order
+    try {
+      await program.methods
+        .setBurnPolicyHandler(true, 100, 600, 500, 50, [
+          { timestamp: new anchor.BN(10), targetSupply: new anchor.BN(1) },
+          { timestamp: new anchor.BN(5), targetSupply: new anchor.BN(1) },
+        ])
+        .accounts({
+          configAccount: configAccount,
+          burnPolicy: burnPolicy,
+          admin: admin.publicKey,
+        })
+        .signers([admin])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("InvalidBurnPolicy"))
+    }
+    data = await program.account.burnPolicy.fetch(burnPolicy)
+    assert.equal(data.pointCount, 0)
+
+    // the share tapers to zero right above the target and reaches the maximum far above it
+    const supply = Number((await program.provider.connection.getTokenSupply(tokenMint)).value.amount)
+    const burnShareAt = async (targetSupply: number) => {
+      await program.methods
+        .setBurnPolicyHandler(true, 100, 600, 500, 50, [
+          { timestamp: new anchor.BN(0), targetSupply: new anchor.BN(targetSupply) },
+        ])
+        .accounts({
+          configAccount: configAccount,
+          burnPolicy: burnPolicy,
+          admin: admin.publicKey,
+        })
+        .signers([admin])
+        .rpc();
+      return await program.methods
+        .getBurnShareHandler()
+        .accounts({
+          configAccount: configAccount,
+          burnPolicy: burnPolicy,
+          distributionConfig: distributionConfig,
+          mint: tokenMint,
+        })
+        .view();
+    }
+    assert.equal(await burnShareAt(supply), 0)
+    assert.equal(await burnShareAt(supply - 1), 0)
+    // 25 per-mille above target, half way through the taper
+    const halfway = await burnShareAt(Math.floor(supply * 1000 / 1025))
+    assert.isAbove(halfway, 0)
+    assert.isBelow(halfway, 100)
+    assert.equal(await burnShareAt(1), 600)
+
+    // back to the fixed splits
+    await program.methods
+      .setBurnPolicyHandler(false, 0, 0, 500, 50, [])
+      .accounts({
+        configAccount: configAccount,
+        burnPolicy: burnPolicy,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    data = await program.account.burnPolicy.fetch(burnPolicy)
+    assert.equal(data.enabled, false)
+    // a disabled policy burns the fixed share of the distribution config
+    const burnShare = await program.methods
+      .getBurnShareHandler()
+      .accounts({
+        configAccount: configAccount,
+        burnPolicy: burnPolicy,
+        distributionConfig: distributionConfig,
+        mint: tokenMint,
+      })
+      .view();
+    const splits = (await program.account.distributionConfig.fetch(distributionConfig)).splits
+    assert.equal(burnShare, splits.burn)
+  });
+
+  it("marketing vesting", async () => {
+    for (const [wallet, vesting, escrow] of [
+      [marketingWallet1.publicKey, marketingVesting1, marketingEscrow1],
+      [marketingWallet2.publicKey, marketingVesting2, marketingEscrow2],
+    ]) {
+      await program.methods
+        .initializeMarketingVestingHandler(new anchor.BN(86400))
+        .accounts({
+          configAccount: configAccount,
+          marketingWallet: wallet,
+          marketingVesting: vesting,
+          marketingEscrow: escrow,
+          mint: tokenMint,
+          admin: admin.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([admin])
+        .rpc();
+    }
+
+    // only the marketing wallets get a vesting stream
+    try {
+      await program.methods
+        .initializeMarketingVestingHandler(new anchor.BN(86400))
+        .accounts({
+          configAccount: configAccount,
+          marketingWallet: deathWallet.publicKey,
+          mint: tokenMint,
+          admin: admin.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([admin])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("InvalidMarketingWallet"))
+    }
+
+    // short vesting for the withdraw test
+    await program.methods
+      .setMarketingVestingDurationHandler(new anchor.BN(2))
+      .accounts({
+        configAccount: configAccount,
+        marketingVesting: marketingVesting1,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    const data = await program.account.marketingVesting.fetch(marketingVesting1)
+    assert.equal(Number(data.duration), 2)
+    assert.equal(data.escrow.toBase58(), marketingEscrow1.toBase58())
+  });
+
+  it("initialize fee release", async () => {
+    await program.methods
+      .initializeFeeReleaseHandler()
+      .accounts({
+        configAccount: configAccount,
+        feeAccumulator: feeAccumulator,
+        feeRelease: feeRelease,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    const data = await program.account.feeRelease.fetch(feeRelease)
+    assert.equal(Number(data.rewardsReleased), 0)
+  });
+
+  it("stake", async () => {
+    // 1st time
+      
+
+    let [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user_stake'), user.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
     );
-    let monthlyTopTierWalletAta = await getOrCreateAssociatedTokenAccount(
+    const feesBefore = (await hookProgram.account.feeAccumulator.fetch(feeAccumulator)).totalFees
+    await program.methods
+      .stakeTokensHandler(
+        new anchor.BN(10e6), // 1 tokens
+        new anchor.BN(1), // 1 days for devnet
+        null // no referrer
+      )
+      .accounts({
+        configAccount: configAccount,
+        mint:tokenMint,
+        stakingPool: stakingPool,
+        user: userStakePDA,
+        userStake: userStake,
+        userAuthority: user.publicKey,
+        userTokenAccount: userAta.address,
+        poolTokenAccount: stakingPoolAta.address,
+        referralConfig: null,
+        referrerUser: null,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([user])
+      .rpc();
+
+    // the hook recorded the 1% fee of the transfer
+    const accumulator = await hookProgram.account.feeAccumulator.fetch(feeAccumulator)
+    assert.equal(Number(accumulator.totalFees) - Number(feesBefore), 10e6 / 100)
+
+    const data = await program.account.userStakeInfo.fetch(userStakePDA)
+    const stake_data = await program.account.userStakesEntry.fetch(userStake)
+
+    // assert.equal(1e6, Number(data.totalAmount));
+    // // assert.equal(30, Number(stake_data.lockup));
+    // assert.equal(1, Number(stake_data.lockup));
+    // assert.equal(1e6, Number(stake_data.amount));
+
+  });
+
+
+
+
+
+  
+
+
+  it("harvest withheld fees", async () => {
+    const before = await program.provider.connection.getTokenAccountBalance(treasuryVault)
+    await program.methods
+      .harvestWithheldFeesHandler()
+      .accounts({
+        configAccount: configAccount,
+        mint: tokenMint,
+        treasury: treasury,
+        treasuryVault: treasuryVault,
+        withdrawAuthority: feeWithdrawAuthority,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: stakingPoolAta.address, isSigner: false, isWritable: true },
+      ])
+      .rpc();
+    const after = await program.provider.connection.getTokenAccountBalance(treasuryVault)
+    // 1% fee of the 10 tokens staked
+    assert.equal(Number(after.value.amount) - Number(before.value.amount), 10e6 / 100)
+  });
+
+  it("claim reward", async () => {
+        let balance = (await program.provider.connection.getTokenAccountBalance(userAta.address))
+        await sleep(3000)
+
+    await mintTo(
       program.provider.connection,
-      admin,
+      admin, // fee payer
       tokenMint,
-      monthlyTopTierWallet.publicKey,
-      false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+      stakingRewardAta.address, // destination ATA
+      admin, // mint authority
+      1_000_000_00000,
+          [],
+   {},
+   TOKEN_PROGRAM_ID,
     );
-    let annualTopTierWalletAta = await getOrCreateAssociatedTokenAccount(
+      const [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('user_stake'), user.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      // CHAR is a Token-2022 mint, the SPL Token program is rejected
+      try {
+        await program.methods
+          .claimRewardHandler(new anchor.BN(0))
+          .accounts({
+            configAccount: configAccount,
+            stakingPool: stakingPool,
+            user: userStakePDA,
+            userAuthority: user.publicKey,
+            userStake: userStake,
+            mint: tokenMint,
+            userTokenAccount: userAta.address,
+            stakingRewardAta: stakingRewardAta.address,
+            referralConfig: null,
+            referrerUser: null,
+            referrerTokenAccount: null,
+            tokenProgram: SPL_TOKEN_PROGRAM_ID,
+          })
+          .remainingAccounts(hookAccounts)
+          .signers([user])
+          .rpc();
+        assert(false);
+      } catch (e) {
+        assert(e.message.includes("TokenProgramMismatch"))
+      }
+      await program.methods
+        .claimRewardHandler(new anchor.BN(0))
+        .accounts({
+          configAccount: configAccount,
+          stakingPool: stakingPool,
+          user: userStakePDA,
+          userAuthority: user.publicKey,
+          userStake: userStake,
+        mint:tokenMint,
+
+          userTokenAccount: userAta.address,
+          stakingRewardAta: stakingRewardAta.address,
+          referralConfig: null,
+          referrerUser: null,
+          referrerTokenAccount: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(hookAccounts)
+        .signers([user])
+        .rpc();
+
+            balance = (await program.provider.connection.getTokenAccountBalance(userAta.address))
+   
+  });
+
+
+  it("referrals", async () => {
+    const [referralConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('referral_config')],
+      program.programId
+    );
+    const referee = anchor.web3.Keypair.generate()
+    await airdropSol(referee.publicKey, 1e9);
+    const refereeAta = await getOrCreateAssociatedTokenAccount(
       program.provider.connection,
       admin,
       tokenMint,
-      annualTopTierWallet.publicKey,
+      referee.publicKey,
       false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+      null,
+      null,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID,
+    );
+    await mintTo(program.provider.connection, admin, tokenMint, refereeAta.address, admin, 100e6, [], {}, TOKEN_PROGRAM_ID);
+    const [refereeUser] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user'), referee.publicKey.toBuffer()],
+      program.programId
+    );
+    const [refereeStake] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user_stake'), referee.publicKey.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const stake = (staker: anchor.web3.Keypair, stakerUser, stakerStake, stakerAta, referrer, referrerUser) => program.methods
+      .stakeTokensHandler(new anchor.BN(100e6), new anchor.BN(1), referrer)
+      .accounts({
+        configAccount: configAccount,
+        mint: tokenMint,
+        stakingPool: stakingPool,
+        user: stakerUser,
+        userStake: stakerStake,
+        userAuthority: staker.publicKey,
+        userTokenAccount: stakerAta,
+        poolTokenAccount: stakingPoolAta.address,
+        referralConfig: referralConfig,
+        referrerUser: referrerUser,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([staker])
+      .rpc();
+    const stakeAsReferee = (referrer, referrerUser) => stake(referee, refereeUser, refereeStake, refereeAta.address, referrer, referrerUser);
+
+    // a first time staker has no staking info to refer themselves with
+    try {
+      await stakeAsReferee(referee.publicKey, refereeUser);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("AccountNotInitialized"))
+    }
+    // the staking info passed must be the one of the referrer
+    try {
+      await stakeAsReferee(anchor.web3.Keypair.generate().publicKey, userStakePDA);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("ConstraintSeeds"))
+    }
+    await stakeAsReferee(user.publicKey, userStakePDA);
+    const refereeInfo = await program.account.userStakeInfo.fetch(refereeUser)
+    assert.equal(refereeInfo.referrer.toBase58(), user.publicKey.toBase58())
+    assert.equal(Number((await program.account.userStakeInfo.fetch(userStakePDA)).referralCount), 1)
+
+    // staking info created with the referral fields has nothing to migrate
+    try {
+      await program.methods
+        .migrateUserStakeInfoHandler(referee.publicKey)
+        .accounts({
+          configAccount: configAccount,
+          user: refereeUser,
+          payer: admin.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([admin])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("UserStakeInfoNotLegacy"))
+    }
+
+    // referrals are only taken on a first stake, so the referrer cannot be referred back
+    const stakeCount = (await program.account.userStakeInfo.fetch(userStakePDA)).stakeCount
+    const [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user_stake'), user.publicKey.toBuffer(), stakeCount.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    try {
+      await stake(user, userStakePDA, userStake, userAta.address, referee.publicKey, refereeUser);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("ReferralOnlyOnFirstStake"))
+    }
+
+    // the bonus on the referee's reward is capped by the remaining budget
+    const budget = 100_000
+    await program.methods
+      .setReferralConfigHandler(true, 500, new anchor.BN(budget))
+      .accounts({
+        configAccount: configAccount,
+        referralConfig: referralConfig,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    await sleep(3000)
+    const referrerBefore = Number((await program.provider.connection.getTokenAccountBalance(userAta.address)).value.amount)
+    const refereeBefore = Number((await program.provider.connection.getTokenAccountBalance(refereeAta.address)).value.amount)
+    await program.methods
+      .claimRewardHandler(new anchor.BN(0))
+      .accounts({
+        configAccount: configAccount,
+        stakingPool: stakingPool,
+        user: refereeUser,
+        userAuthority: referee.publicKey,
+        userStake: refereeStake,
+        mint: tokenMint,
+        userTokenAccount: refereeAta.address,
+        stakingRewardAta: stakingRewardAta.address,
+        referralConfig: referralConfig,
+        referrerUser: userStakePDA,
+        referrerTokenAccount: userAta.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([referee])
+      .rpc();
+    const reward = Number((await program.account.userStakeInfo.fetch(refereeUser)).rewardIssued)
+    assert.isAbove(reward, 0)
+    assert.isAbove(Math.floor(reward * 500 / 10_000), budget)
+    const config = await program.account.referralConfig.fetch(referralConfig)
+    assert.equal(Number(config.totalPaid), budget)
+    assert.equal(Number((await program.account.userStakeInfo.fetch(userStakePDA)).referralRewards), budget)
+    // both transfers pay the 1% CHAR transfer fee
+    const referrerAfter = Number((await program.provider.connection.getTokenAccountBalance(userAta.address)).value.amount)
+    const refereeAfter = Number((await program.provider.connection.getTokenAccountBalance(refereeAta.address)).value.amount)
+    assert.equal(referrerAfter - referrerBefore, budget - budget / 100)
+    assert.isAbove(refereeAfter, refereeBefore)
+  });
+
+  it("extra rewards", async () => {
+    const connection = program.provider.connection
+    // partner token on the SPL Token program, paying 1 unit per staked CHAR unit per day
+    const partnerMint = await createMint(connection, admin, admin.publicKey, null, 6, undefined, {}, SPL_TOKEN_PROGRAM_ID);
+    const [rewardVault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('reward_vault'), stakingPool.toBuffer(), partnerMint.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .addExtraRewardHandler(new anchor.BN(1e9), new anchor.BN(0), new anchor.BN(0))
+      .accounts({
+        configAccount: configAccount,
+        stakingPool: stakingPool,
+        rewardMint: partnerMint,
+        rewardVault: rewardVault,
+        admin: admin.publicKey,
+        tokenProgram: SPL_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    await mintTo(connection, admin, partnerMint, rewardVault, admin, 1e12, [], {}, SPL_TOKEN_PROGRAM_ID);
+    const userPartnerAta = await getOrCreateAssociatedTokenAccount(
+      connection,
+      admin,
+      partnerMint,
+      user.publicKey,
+      false,
+      null,
+      null,
+      SPL_TOKEN_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID,
+    );
+    const pool = await program.account.stakingPool.fetch(stakingPool)
+    const index = pool.extraRewards.findIndex((x) => x.mint.equals(partnerMint))
+    const startTime = pool.extraRewards[index].startTime
+    // a new stake earns the partner token from its start
+    const stakeId = (await program.account.userStakeInfo.fetch(userStakePDA)).stakeCount
+    const [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user_stake'), user.publicKey.toBuffer(), stakeId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .stakeTokensHandler(new anchor.BN(10e6), new anchor.BN(1), null)
+      .accounts({
+        configAccount: configAccount,
+        mint: tokenMint,
+        stakingPool: stakingPool,
+        user: userStakePDA,
+        userStake: userStake,
+        userAuthority: user.publicKey,
+        userTokenAccount: userAta.address,
+        poolTokenAccount: stakingPoolAta.address,
+        referralConfig: null,
+        referrerUser: null,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([user])
+      .rpc();
+    const partnerBalance = async () => Number((await connection.getTokenAccountBalance(userPartnerAta.address)).value.amount)
+    const charBalance = async () => Number((await connection.getTokenAccountBalance(userAta.address)).value.amount)
+
+    // the partner reward is paid with the CHAR reward, its accounts come before the hook accounts
+    await sleep(3000)
+    const charBefore = await charBalance()
+    await program.methods
+      .claimRewardHandler(stakeId)
+      .accounts({
+        configAccount: configAccount,
+        stakingPool: stakingPool,
+        user: userStakePDA,
+        userAuthority: user.publicKey,
+        userStake: userStake,
+        mint: tokenMint,
+        userTokenAccount: userAta.address,
+        stakingRewardAta: stakingRewardAta.address,
+        referralConfig: null,
+        referrerUser: null,
+        referrerTokenAccount: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: partnerMint, isSigner: false, isWritable: false },
+        { pubkey: rewardVault, isSigner: false, isWritable: true },
+        { pubkey: userPartnerAta.address, isSigner: false, isWritable: true },
+        { pubkey: SPL_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+        ...hookAccounts,
+      ])
+      .signers([user])
+      .rpc();
+    assert.isAbove(await charBalance(), charBefore)
+    const claimed = await partnerBalance()
+    assert.isAbove(claimed, 0)
+    assert.equal(Number((await program.account.userStakesEntry.fetch(userStake)).extraRewards[index].claimed), claimed)
+
+    // end the campaign, keep earning until its end and remove it
+    const now = await connection.getBlockTime(await connection.getSlot())
+    await program.methods
+      .endExtraRewardHandler(index, new anchor.BN(now + 2))
+      .accounts({
+        configAccount: configAccount,
+        stakingPool: stakingPool,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    await sleep(4000)
+    const [endedExtraReward] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('ended_extra_reward'), stakingPool.toBuffer(), partnerMint.toBuffer(), startTime.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .removeExtraRewardHandler(index)
+      .accounts({
+        configAccount: configAccount,
+        stakingPool: stakingPool,
+        rewardMint: partnerMint,
+        endedExtraReward: endedExtraReward,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    assert.isFalse((await program.account.stakingPool.fetch(stakingPool)).extraRewards[index].mint.equals(partnerMint))
+
+    // what was earned before the removal is still paid, once
+    const [receipt] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('extra_reward_receipt'), endedExtraReward.toBuffer(), userStake.toBuffer()],
+      program.programId
+    );
+    const claimEnded = () => program.methods
+      .claimEndedExtraRewardHandler(stakeId)
+      .accounts({
+        configAccount: configAccount,
+        stakingPool: stakingPool,
+        endedExtraReward: endedExtraReward,
+        userStake: userStake,
+        receipt: receipt,
+        userAuthority: user.publicKey,
+        rewardMint: partnerMint,
+        rewardVault: rewardVault,
+        userRewardTokenAccount: userPartnerAta.address,
+        tokenProgram: SPL_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+    await claimEnded()
+    const settled = (await program.account.extraRewardReceipt.fetch(receipt)).amount
+    assert.isAbove(Number(settled), 0)
+    assert.equal(await partnerBalance(), claimed + Number(settled))
+    const ended = await program.account.endedExtraReward.fetch(endedExtraReward)
+    assert.equal(Number(ended.reward.totalIssued), claimed + Number(settled))
+    assert.equal(Number((await program.account.userStakesEntry.fetch(userStake)).extraRewards[index].claimed), 0)
+    // the receipt already exists, the second settlement fails
+    let settledTwice = true
+    try {
+      await claimEnded()
+    } catch (e) {
+      settledTwice = false
+    }
+    assert.isFalse(settledTwice)
+    assert.equal(await partnerBalance(), claimed + Number(settled))
+  });
+
+  it("Emergency halt", async () => {
+    let data = await program.account.configAccount.fetch(configAccount)
+    assert.equal(data.config.halted, false)
+    await program.methods
+      .changeEmergencyStateHandler(true)
+      .accounts({
+        configAccount: configAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        payer: admin.publicKey,
+
+      })
+      .signers([admin])
+      .rpc();
+    data = await program.account.configAccount.fetch(configAccount)
+    assert.equal(data.config.halted, true)
+
+
+  });
+
+
+  it("halt distribute marketing funds", async () => {
+    try {
+      await program.methods
+        .distributeMarketingFundsHandler()
+        .accounts({
+          configAccount: configAccount,
+          signer1: treasuryAuthority.publicKey,
+          treasury: treasury,
+          treasuryVault: treasuryVault,
+          marketingVesting1: marketingVesting1,
+          marketingEscrow1: marketingEscrow1,
+          marketingVesting2: marketingVesting2,
+          marketingEscrow2: marketingEscrow2,
+          tokenProgram: TOKEN_PROGRAM_ID,
+                  mint:tokenMint,
+          feeAccumulator: feeAccumulator,
+          feeRelease: feeRelease,
+          distributionConfig: distributionConfig,
+          outflowLimiter: outflowLimiter,
+          burnLedger: burnLedger,
+          burnPolicy: burnPolicy,
+        })
+        .remainingAccounts(hookAccounts)
+        .signers([treasuryAuthority])
+        .rpc();
+    } catch (e) {
+      if (e instanceof anchor.AnchorError) {
+        assert(e.message.includes("ProgramIsHalted"))
+      } else {
+        assert(false);
+      }
+    }
+
+
+    await program.methods
+      .changeEmergencyStateHandler(false)
+      .accounts({
+        configAccount: configAccount,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        payer: admin.publicKey,
+
+      })
+      .signers([admin])
+      .rpc();
+
+
+
+  });
+  it("distribute marketing funds", async () => {
+    // 10% of the fees recorded by the hook
+    const accumulator = await hookProgram.account.feeAccumulator.fetch(feeAccumulator)
+    let total = (Number(accumulator.totalFees) * 100) / 1000;
+    let amount_wallet1 = (total * 450) / 1000; // 45%
+    let amount_wallet2 = (total * 400) / 1000; // 40%
+    let amount_death = (total * 150) / 1000; // 15%
+
+    let balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet1Ata.address))
+    assert.equal(balance.value.amount, "0");
+    balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet2Ata.address))
+    assert.equal(balance.value.amount, "0");
+    const burnedBefore = (await program.account.configAccount.fetch(configAccount)).config.totalBurned
+    const supplyBefore = (await program.provider.connection.getTokenSupply(tokenMint)).value.amount
+
+    await program.methods
+      .distributeMarketingFundsHandler()
+      .accounts({
+        configAccount: configAccount,
+        signer1: treasuryAuthority.publicKey,
+        treasury: treasury,
+        treasuryVault: treasuryVault,
+        marketingVesting1: marketingVesting1,
+        marketingEscrow1: marketingEscrow1,
+        marketingVesting2: marketingVesting2,
+        marketingEscrow2: marketingEscrow2,
+        tokenProgram: TOKEN_PROGRAM_ID,
+                mint:tokenMint,
+        feeAccumulator: feeAccumulator,
+        feeRelease: feeRelease,
+        distributionConfig: distributionConfig,
+        outflowLimiter: outflowLimiter,
+        burnLedger: burnLedger,
+        burnPolicy: burnPolicy,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([treasuryAuthority])
+      .rpc();
+    // balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet1Ata.address))
+    // assert.equal(balance.value.amount, amount_wallet1.toString());
+    // balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet2Ata.address))
+    // assert.equal(balance.value.amount, amount_wallet2.toString());
+    // the death wallet share is burned by the distribution
+    const burned = Number((await program.account.configAccount.fetch(configAccount)).config.totalBurned) - Number(burnedBefore)
+    assert.equal(burned, Math.floor((Math.floor(total) * 150) / 1000))
+    const supplyAfter = (await program.provider.connection.getTokenSupply(tokenMint)).value.amount
+    assert.equal(Number(supplyBefore) - Number(supplyAfter), burned)
+  })
+
+  it("withdraw vested marketing", async () => {
+    // the wallet shares are locked in the escrows, not in the wallets
+    let balance = (await program.provider.connection.getTokenAccountBalance(marketingWallet1Ata.address))
+    assert.equal(balance.value.amount, "0");
+    const escrow = (await program.provider.connection.getTokenAccountBalance(marketingEscrow1))
+    let data = await program.account.marketingVesting.fetch(marketingVesting1)
+    assert.equal(Number(data.totalDeposited), Number(escrow.value.amount))
+
+    await sleep(3000)
+    await program.methods
+      .withdrawVestedMarketingHandler()
+      .accounts({
+        configAccount: configAccount,
+        marketingVesting: marketingVesting1,
+        marketingEscrow: marketingEscrow1,
+        walletTokenAccount: marketingWallet1Ata.address,
+        marketingWallet: marketingWallet1.publicKey,
+        mint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([marketingWallet1])
+      .rpc();
+    data = await program.account.marketingVesting.fetch(marketingVesting1)
+    assert.equal(Number(data.locked), 0)
+    assert.equal(Number(data.totalWithdrawn), Number(escrow.value.amount))
+    balance = (await program.provider.connection.getTokenAccountBalance(marketingEscrow1))
+    assert.equal(balance.value.amount, "0");
+
+    // wallet 2 vests over a day, most of its share is still locked
+    data = await program.account.marketingVesting.fetch(marketingVesting2)
+    assert(Number(data.locked) > 0)
+    assert(Number(data.endTime) - Number(data.startTime) > 86000)
+  })
+  
+  it("release Funds", async () => {
+
+
+    let charFundsAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      charFunds.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+    let monthlyTopTierWalletAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      monthlyTopTierWallet.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+    let annualTopTierWalletAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      annualTopTierWallet.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+    let monthlyCharityLotteryWalletAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      monthlyCharityLotteryWallet.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+    let annualCharityLotteryWalletAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      annualCharityLotteryWallet.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+    let monthlyOneTimeCausesWalletAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      monthlyOneTimeCausesWallet.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+    let annualOneTimeCausesWalletAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      annualOneTimeCausesWallet.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+    let monthlyInfiniteImpactCausesWalletAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      monthlyInfiniteImpactCausesWallet.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+    let annualInfiniteImpactCausesWalletAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      annualInfiniteImpactCausesWallet.publicKey,
+      false,
+          null,
+    null,
+    TOKEN_2022_PROGRAM_ID,
+    ASSOCIATED_PROGRAM_ID,
+    );
+
+ 
+    await program.methods
+      .releaseRewardsHandler()
+      .accounts({
+        configAccount: configAccount,
+        treasuryAuthority: treasuryAuthority.publicKey,
+        treasury: treasury,
+        treasuryVault: treasuryVault,
+        monthlyTopTierAta:monthlyTopTierWalletAta.address,
+        annualTopTierAta:annualTopTierWalletAta.address,
+        monthlyCharityLotteryAta:monthlyCharityLotteryWalletAta.address,
+        annualCharityLotteryAta:annualCharityLotteryWalletAta.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        mint:tokenMint,
+        feeAccumulator: feeAccumulator,
+        feeRelease: feeRelease,
+        distributionConfig: distributionConfig,
+        outflowLimiter: outflowLimiter,
+        releaseSchedule: releaseSchedule,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([treasuryAuthority])
+      .rpc();
+
+
+        await program.methods
+      .releaseDonationsHandler()
+      .accounts({
+        configAccount: configAccount,
+        treasuryAuthority: treasuryAuthority.publicKey,
+        treasury: treasury,
+        treasuryVault: treasuryVault,
+  
+        monthlyOneTimeCausesAta:monthlyOneTimeCausesWalletAta.address,
+        annualOneTimeCausesAta:annualOneTimeCausesWalletAta.address,
+        monthlyInfiniteImpactCausesAta:monthlyInfiniteImpactCausesWalletAta.address,
+        annualInfiniteImpactCausesAta:annualInfiniteImpactCausesWalletAta.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        charFundsAta: charFundsAta.address,
+        mint:tokenMint,
+        feeAccumulator: feeAccumulator,
+        feeRelease: feeRelease,
+        distributionConfig: distributionConfig,
+        outflowLimiter: outflowLimiter,
+        releaseSchedule: releaseSchedule,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([treasuryAuthority])
+      .rpc();
+     
+     
+      await program.methods
+      .releaseStakingFundsHandler()
+      .accounts({
+        configAccount: configAccount,
+        treasuryAuthority: treasuryAuthority.publicKey,
+        treasury: treasury,
+        treasuryVault: treasuryVault,
+        stakingRewardAta: stakingRewardAta.address,
+        stakingPool: stakingPool,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        mint:tokenMint,
+        feeAccumulator: feeAccumulator,
+        feeRelease: feeRelease,
+        distributionConfig: distributionConfig,
+        outflowLimiter: outflowLimiter,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([treasuryAuthority])
+      .rpc();
+  })
+it("buyback and burn", async () => {
+    // the marketing distribution burns its share directly, fund the death wallet to burn
+    await mintTo(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      deathWalletAta.address,
+      admin,
+      1_000_000,
+      [],
+      {},
+      TOKEN_PROGRAM_ID,
+    );
+
+    const accounts = {
+      configAccount: configAccount,
+      buybackConfig: buybackConfig,
+      mint: tokenMint,
+      deathWallet: deathWalletPda,
+      burnWalletAta: deathWalletAta.address,
+      burnLedger: burnLedger,
+      keeperTokenAccount: userAta.address,
+      keeper: user.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }
+    // anyone can burn the death wallet, the keeper gets 0.5%
+    const keeperBefore = await program.provider.connection.getTokenAccountBalance(userAta.address)
+    const burnedBefore = (await program.account.configAccount.fetch(configAccount)).config.totalBurned
+    await program.methods
+      .buybackBurnHandler()
+      .accounts(accounts)
+      .remainingAccounts(hookAccounts)
+      .signers([user])
+      .rpc();
+    const keeperAfter = await program.provider.connection.getTokenAccountBalance(userAta.address)
+    // tip minus the 1% transfer fee
+    assert.equal(Number(keeperAfter.value.amount) - Number(keeperBefore.value.amount), 5000 - 50)
+    const burned = Number((await program.account.configAccount.fetch(configAccount)).config.totalBurned) - Number(burnedBefore)
+    assert.equal(burned, 1_000_000 - 5000)
+    const balance = await program.provider.connection.getTokenAccountBalance(deathWalletAta.address)
+    assert.equal(balance.value.amount, "0")
+
+    // below the minimum balance, the next burn waits for the interval
+    await mintTo(program.provider.connection, admin, tokenMint, deathWalletAta.address, admin, 1000, [], {}, TOKEN_PROGRAM_ID);
+    try {
+      await program.methods
+        .buybackBurnHandler()
+        .accounts(accounts)
+        .remainingAccounts(hookAccounts)
+        .signers([user])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("BuybackNotDue"))
+    }
+  })
+
+  it("buyback swap", async () => {
+    // quote asset in a classic SPL token, like USDC
+    const quoteMint = await createMint(
+      program.provider.connection,
+      admin,
+      admin.publicKey,
+      null,
+      6,
+      anchor.web3.Keypair.generate(),
+      {},
+      SPL_TOKEN_PROGRAM_ID,
+    );
+    const [pool] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('pool'), tokenMint.toBuffer(), quoteMint.toBuffer()],
+      ammProgram.programId
+    );
+    const [poolCharVault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_vault'), pool.toBuffer(), tokenMint.toBuffer()],
+      ammProgram.programId
+    );
+    const [poolQuoteVault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('pool_vault'), pool.toBuffer(), quoteMint.toBuffer()],
+      ammProgram.programId
+    );
+    const [ammConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('amm_config')],
+      program.programId
+    );
+    const [quoteVault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('treasury_vault'), quoteMint.toBuffer()],
+      program.programId
+    );
+    // the AMM only gets the swap accounts of the buyback authority
+    const [buybackAuthority] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('buyback_authority')],
+      program.programId
+    );
+    const [swapQuoteAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('buyback_swap'), quoteMint.toBuffer()],
+      program.programId
+    );
+    const [swapCharAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('buyback_swap'), tokenMint.toBuffer()],
+      program.programId
+    );
+
+    await ammProgram.methods
+      .initializePool()
+      .accounts({
+        pool: pool,
+        baseVault: poolCharVault,
+        quoteVault: poolQuoteVault,
+        baseMint: tokenMint,
+        quoteMint: quoteMint,
+        payer: admin.publicKey,
+        baseTokenProgram: TOKEN_PROGRAM_ID,
+        quoteTokenProgram: SPL_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    // 1 CHAR = 1 quote token
+    await mintTo(program.provider.connection, admin, tokenMint, poolCharVault, admin, 1_000_000e6, [], {}, TOKEN_PROGRAM_ID);
+    await mintTo(program.provider.connection, admin, quoteMint, poolQuoteVault, admin, 1_000_000e6, [], {}, SPL_TOKEN_PROGRAM_ID);
+
+    await program.methods
+      .initializeAmmConfigHandler(ammProgram.programId, pool)
+      .accounts({
+        configAccount: configAccount,
+        ammConfig: ammConfig,
+        treasury: treasury,
+        quoteVault: quoteVault,
+        quoteMint: quoteMint,
+        buybackAuthority: buybackAuthority,
+        swapQuoteAccount: swapQuoteAccount,
+        swapCharAccount: swapCharAccount,
+        mint: tokenMint,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        quoteTokenProgram: SPL_TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([admin])
+      .rpc();
+
+    // another AMM program waits for the same timelock as raised outflow limits
+    await program.methods
+      .proposeAmmConfigHandler(anchor.web3.Keypair.generate().publicKey, pool)
+      .accounts({
+        configAccount: configAccount,
+        ammConfig: ammConfig,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    try {
+      await program.methods
+        .applyAmmConfigHandler()
+        .accounts({
+          configAccount: configAccount,
+          ammConfig: ammConfig,
+          admin: admin.publicKey,
+        })
+        .signers([admin])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("AmmConfigTimelocked"))
+    }
+    assert.equal((await program.account.ammConfig.fetch(ammConfig)).ammProgram.toBase58(), ammProgram.programId.toBase58())
+
+    // the quote asset has its own outflow limits, in units of the quote mint
+    const [quoteOutflowLimiter] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('outflow_limiter'), quoteMint.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .initializeQuoteOutflowLimiterHandler(new anchor.BN(500e6), new anchor.BN(5000e6))
+      .accounts({
+        configAccount: configAccount,
+        ammConfig: ammConfig,
+        quoteOutflowLimiter: quoteOutflowLimiter,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    await mintTo(program.provider.connection, admin, quoteMint, quoteVault, admin, 1000e6, [], {}, SPL_TOKEN_PROGRAM_ID);
+
+    const accounts = {
+      configAccount: configAccount,
+      ammConfig: ammConfig,
+      treasury: treasury,
+      quoteVault: quoteVault,
+      buybackAuthority: buybackAuthority,
+      swapQuoteAccount: swapQuoteAccount,
+      swapCharAccount: swapCharAccount,
+      burnLedger: burnLedger,
+      quoteOutflowLimiter: quoteOutflowLimiter,
+      pool: pool,
+      poolQuoteVault: poolQuoteVault,
+      poolCharVault: poolCharVault,
+      ammProgram: ammProgram.programId,
+      quoteMint: quoteMint,
+      mint: tokenMint,
+      treasuryAuthority: treasuryAuthority.publicKey,
+      quoteTokenProgram: SPL_TOKEN_PROGRAM_ID,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }
+
+    // slippage guard
+    try {
+      await program.methods
+        .buybackSwapHandler(new anchor.BN(100e6), new anchor.BN(100e6))
+        .accounts(accounts)
+        .remainingAccounts(hookAccounts)
+        .signers([treasuryAuthority])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("SlippageExceeded"))
+    }
+
+    const burnedBefore = (await program.account.configAccount.fetch(configAccount)).config.totalBurned
+    const supplyBefore = (await program.provider.connection.getTokenSupply(tokenMint)).value.amount
+    const vaultBefore = (await program.provider.connection.getTokenAccountBalance(treasuryVault)).value.amount
+    const outflowBefore = (await program.account.outflowLimiter.fetch(outflowLimiter)).hourlyOutflow.reduce((x, y) => x + Number(y), 0)
+    const quoteOutflowBefore = (await program.account.outflowLimiter.fetch(quoteOutflowLimiter)).hourlyOutflow.reduce((x, y) => x + Number(y), 0)
+    await program.methods
+      .buybackSwapHandler(new anchor.BN(100e6), new anchor.BN(90e6))
+      .accounts(accounts)
+      .remainingAccounts(hookAccounts)
+      .signers([treasuryAuthority])
+      .rpc();
+
+    // the quote asset was spent and everything bought was burned
+    const quote = await program.provider.connection.getTokenAccountBalance(quoteVault)
+    assert.equal(quote.value.amount, (900e6).toString())
+    const burned = Number((await program.account.configAccount.fetch(configAccount)).config.totalBurned) - Number(burnedBefore)
+    assert(burned >= 90e6)
+    const supplyAfter = (await program.provider.connection.getTokenSupply(tokenMint)).value.amount
+    assert.equal(Number(supplyBefore) - Number(supplyAfter), burned)
+    const vaultAfter = (await program.provider.connection.getTokenAccountBalance(treasuryVault)).value.amount
+    assert.equal(vaultAfter, vaultBefore)
+    // nothing is left in the swap accounts and the quote spent counts as a quote outflow only
+    assert.equal((await program.provider.connection.getTokenAccountBalance(swapQuoteAccount)).value.amount, "0")
+    assert.equal((await program.provider.connection.getTokenAccountBalance(swapCharAccount)).value.amount, "0")
+    const outflowAfter = (await program.account.outflowLimiter.fetch(outflowLimiter)).hourlyOutflow.reduce((x, y) => x + Number(y), 0)
+    assert.equal(outflowAfter, outflowBefore)
+    const quoteOutflowAfter = (await program.account.outflowLimiter.fetch(quoteOutflowLimiter)).hourlyOutflow.reduce((x, y) => x + Number(y), 0)
+    assert.equal(quoteOutflowAfter - quoteOutflowBefore, 100e6)
+
+    // over the daily quote limit
+    await mintTo(program.provider.connection, admin, quoteMint, quoteVault, admin, 1000e6, [], {}, SPL_TOKEN_PROGRAM_ID);
+    try {
+      await program.methods
+        .buybackSwapHandler(new anchor.BN(450e6), new anchor.BN(1))
+        .accounts(accounts)
+        .remainingAccounts(hookAccounts)
+        .signers([treasuryAuthority])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("OutflowLimitExceeded"))
+    }
+  })
+
+  it("burn voluntary", async () => {
+    const [burnerStats] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('burner_stats'), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const before = await program.account.burnLedger.fetch(burnLedger)
+    for (let i = 0; i < 2; i++) {
+      await program.methods
+        .burnVoluntaryHandler(new anchor.BN(5e6))
+        .accounts({
+          configAccount: configAccount,
+          burnLedger: burnLedger,
+          burnerStats: burnerStats,
+          burnerTokenAccount: userAta.address,
+          mint: tokenMint,
+          burner: user.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+    }
+    const stats = await program.account.burnerStats.fetch(burnerStats)
+    assert.equal(Number(stats.totalBurned), 10e6)
+    assert.equal(Number(stats.burnCount), 2)
+
+    // each burn is in the history with its source
+    const ledger = await program.account.burnLedger.fetch(burnLedger)
+    assert.equal(Number(ledger.burnCount), Number(before.burnCount) + 2)
+    assert.equal(Number(ledger.totalBySource[2]) - Number(before.totalBySource[2]), 10e6)
+    const last = ledger.entries[(Number(ledger.burnCount) - 1) % ledger.entries.length]
+    assert.equal(Number(last.amount), 5e6)
+    assert.deepEqual(last.source, { voluntary: {} })
+    // marketing share and buyback burns were recorded before
+    assert(Number(ledger.totalBySource[0]) > 0)
+    assert(Number(ledger.totalBySource[1]) > 0)
+  })
+
+  it("supply stats", async () => {
+    const [supplyConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('supply_config')],
+      program.programId
+    );
+    await program.methods
+      .initializeSupplyConfigHandler()
+      .accounts({
+        configAccount: configAccount,
+        supplyConfig: supplyConfig,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+
+    const accounts = {
+      configAccount: configAccount,
+      supplyConfig: supplyConfig,
+      mint: tokenMint,
+      stakingPool: stakingPool,
+      treasury: treasury,
+      treasuryVault: treasuryVault,
+      stakingRewardAta: stakingRewardAta.address,
+    }
+    // the CHAR account of every excluded wallet, in the order of the supply config
+    const supplyData = await program.account.supplyConfig.fetch(supplyConfig)
+    const excluded = supplyData.excludedWallets.slice(0, supplyData.excludedCount)
+      .map((wallet) => getAssociatedTokenAddressSync(tokenMint, wallet, true, TOKEN_2022_PROGRAM_ID))
+      .map((pubkey) => ({ pubkey, isSigner: false, isWritable: false }))
+    assert.isTrue(excluded.some((x) => x.pubkey.equals(deathWalletAta.address)))
+    const stats = await program.methods
+      .getSupplyStatsHandler()
+      .accounts(accounts)
+      .remainingAccounts(excluded)
+      .view();
+
+    const balance = async (account: anchor.web3.PublicKey) =>
+      Number((await program.provider.connection.getTokenAccountBalance(account)).value.amount)
+    const supply = Number((await program.provider.connection.getTokenSupply(tokenMint)).value.amount)
+    let excludedBalance = 0
+    for (const account of excluded) {
+      // wallets without a CHAR account hold nothing
+      if (await program.provider.connection.getAccountInfo(account.pubkey)) {
+        excludedBalance += await balance(account.pubkey)
+      }
+    }
+    assert.equal(Number(stats.totalSupply), supply)
+    assert.equal(Number(stats.excludedBalance), excludedBalance)
+    assert.equal(
+      Number(stats.circulatingSupply),
+      supply - await balance(treasuryVault) - await balance(stakingRewardAta.address) - excludedBalance
+    )
+    const data = await program.account.configAccount.fetch(configAccount)
+    assert.equal(Number(stats.totalBurned), Number(data.config.totalBurned))
+
+    // holder accounts can't be excluded and no excluded wallet can be left out
+    for (const remaining of [
+      [...excluded.slice(0, -1), { pubkey: userAta.address, isSigner: false, isWritable: false }],
+      excluded.slice(0, -1),
+    ]) {
+      try {
+        await program.methods
+          .getSupplyStatsHandler()
+          .accounts(accounts)
+          .remainingAccounts(remaining)
+          .view();
+        assert(false);
+      } catch (e) {
+        assert(e.message.includes("InvalidSupplyExclusion"))
+      }
+    }
+  })
+
+
+
+
+  
+
+
+  it("register Charity", async () => {
+    let data = await program.account.configAccount.fetch(configAccount)
+
+    let name = "Water for All";
+    const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('charity'), data.config.nextCharityId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    let startTime = Math.floor(Date.now() / 1000) -1;
+    let endTime = Math.floor(Date.now() / 1000) + 19;
+    const charityWallet = anchor.web3.Keypair.generate()
+    const tx = await program.methods
+      .registerCharityHandler(
+        name,
+        charityWallet.publicKey,
+        { monthlyInfiniteImpactCauses: {} },
+        new anchor.BN(startTime),
+        new anchor.BN(endTime)
+      )
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        registrar: admin.publicKey,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+
+    // the charity admin fills in the profile, the account grows with it
+    const profile = {
+      title: name,
+      descriptionUri: "https://example.org/water-for-all/about",
+      websiteUri: "https://example.org/water-for-all",
+      imageUri: "https://example.org/water-for-all/logo.png",
+      registrationHash: Array(32).fill(4),
+      countryCode: Array.from(Buffer.from("KE")),
+      contentHash: Array(32).fill(5),
+    }
+    const updateProfile = (profile) => program.methods
+      .updateCharityProfileHandler(data.config.nextCharityId, profile)
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        charityAdmin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    await updateProfile(profile);
+    const charity = await program.account.charity.fetch(charityAccount)
+    assert.equal(charity.websiteUri, profile.websiteUri)
+    assert.equal(Buffer.from(charity.countryCode).toString(), "KE")
+
+    try {
+      await updateProfile({ ...profile, imageUri: "https://example.org/" + "a".repeat(200) });
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityUriTooLong"))
+    }
+
+    // only accounts still in the legacy layout can be migrated
+    try {
+      await program.methods
+        .migrateCharityHandler(data.config.nextCharityId, { monthlyInfiniteImpactCauses: {} })
+        .accounts({
+          configAccount: configAccount,
+          charity: charityAccount,
+          admin: admin.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([admin])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityNotLegacy"))
+    }
+  })
+  it("voting round", async () => {
+    await program.methods
+      .createVotingRoundHandler(
+        new anchor.BN(Math.floor(Date.now() / 1000) - 1),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 17),
+        new anchor.BN(1),
+        2,
+        { monthlyInfiniteImpactCauses: {} },
+        false
+      )
+      .accounts({
+        configAccount: configAccount,
+        roundRegistry: roundRegistry,
+        votingRound: votingRound,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+
+    // a second charity competing with the one registered above
+    const data = await program.account.configAccount.fetch(configAccount)
+    const [secondCharity] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('charity'), data.config.nextCharityId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .registerCharityHandler(
+        "Food for All",
+        anchor.web3.Keypair.generate().publicKey,
+        { monthlyInfiniteImpactCauses: {} },
+        new anchor.BN(Math.floor(Date.now() / 1000)),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60)
+      )
+      .accounts({
+        configAccount: configAccount,
+        charity: secondCharity,
+        registrar: admin.publicKey,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+
+    for (const charityId of [0, Number(data.config.nextCharityId)]) {
+      const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('charity'), new anchor.BN(charityId).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      await program.methods
+        .addCharityToRoundHandler(new anchor.BN(0), new anchor.BN(charityId))
+        .accounts({
+          configAccount: configAccount,
+          votingRound: votingRound,
+          charity: charityAccount,
+          attestation: null,
+          admin: admin.publicKey,
+        })
+        .signers([admin])
+        .rpc();
+    }
+    const round = await program.account.votingRound.fetch(votingRound)
+    assert.equal(round.charityCount, 2)
+
+    // the winners share 1000 CHAR of the donation vault of the category
+    const [donationVault, vault] = donationVaultOf(1)
+    await program.methods
+      .initializeDonationVaultHandler({ monthlyInfiniteImpactCauses: {} })
+      .accounts({
+        configAccount: configAccount,
+        donationVault: donationVault,
+        vault: vault,
+        mint: tokenMint,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([admin])
+      .rpc();
+    await mintTo(program.provider.connection, admin, tokenMint, vault, admin, 1000e6, [], {}, TOKEN_PROGRAM_ID);
+    const setAllocation = (allocation: number) => program.methods
+      .setRoundAllocationHandler(new anchor.BN(0), new anchor.BN(allocation))
+      .accounts({
+        configAccount: configAccount,
+        votingRound: votingRound,
+        donationVault: donationVault,
+        vault: vault,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    // the allocation is committed and cannot exceed the vault balance
+    try {
+      await setAllocation(1000e6 + 1);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("AllocationExceedsVault"))
+    }
+    await setAllocation(400e6);
+    await setAllocation(1000e6);
+    assert.equal(Number((await program.account.donationVault.fetch(donationVault)).committed), 1000e6)
+    // the charities take the voting window of the round
+    const charity = await program.account.charity.fetch(secondCharity)
+    assert.equal(charity.endTime.toString(), round.endTime.toString())
+    assert.equal(charity.round.toBase58(), votingRound.toBase58())
+  })
+
+  it("castVote", async () => {
+        await sleep(10000); // Wait for proposal duration to pass
+
+      const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('charity'), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [voteRecord] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('vote'), charityAccount.toBuffer(), user.publicKey.toBuffer()],
+        program.programId
+      );
+
+      const tx = await program.methods
+        .castVoteHandler(
+          new anchor.BN(0),
+        )
+        .accounts({
+          voteRecord: voteRecord,
+          voter: user.publicKey,
+          configAccount: configAccount,
+          charity: charityAccount,
+          user: userStakePDA,
+          stakingPool: stakingPool,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+  
+  })
+
+
+
+
+  it("finalize Charity", async () => {
+    await sleep(11000); // Wait for charity voting duration to pass
+    const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('charity'), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    const tx = await program.methods
+      .finalizeCharityVoteHandler(new anchor.BN(0))
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+  })
+
+  it("finalize round", async () => {
+    const round = await program.account.votingRound.fetch(votingRound)
+    const charities = round.charities.slice(0, round.charityCount)
+    await program.methods
+      .finalizeRoundHandler(new anchor.BN(0))
+      .accounts({
+        configAccount: configAccount,
+        votingRound: votingRound,
+        donationVault: donationVaultOf(1)[0],
+        admin: admin.publicKey,
+      })
+      .remainingAccounts(charities.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false })))
+      .signers([admin])
+      .rpc();
+    // only the first charity received votes
+    const data = await program.account.votingRound.fetch(votingRound)
+    assert.deepEqual(data.status, { finalized: {} })
+    assert.equal(data.winnersSelected, 1)
+    assert.equal(data.winners[0].toBase58(), charities[0].toBase58())
+    assert.equal(data.winnerShares[0], 1000)
+
+    // the winner is paid its share of the allocation once
+    const charity = await program.account.charity.fetch(charities[0])
+    const charityAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      charity.wallet,
+      false,
+      null,
+      null,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID,
+    );
+    const [donationVault, vault] = donationVaultOf(1)
+    const pay = () => program.methods
+      .payCharityHandler(new anchor.BN(0), charity.id)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: votingRound,
+        charity: charities[0],
+        attestation: null,
+        donationVault: donationVault,
+        vault: vault,
+        charityTokenAccount: charityAta.address,
+        mint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .rpc();
+    await pay();
+    const paid = await program.account.votingRound.fetch(votingRound)
+    assert.equal(Number(paid.paidTotal), 1000e6)
+    assert.equal((await program.account.charity.fetch(charities[0])).paidOut, true)
+    const vaultBalance = await program.provider.connection.getTokenAccountBalance(vault)
+    assert.equal(vaultBalance.value.amount, "0")
+    assert.equal(Number((await program.account.donationVault.fetch(donationVault)).committed), 0)
+    try {
+      await pay();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityAlreadyPaid"))
+    }
+  })
+
+  it("milestones", async () => {
+    await program.methods
+      .initializeGovernanceConfigHandler(new anchor.BN(2), new anchor.BN(1), new anchor.BN(1), new anchor.BN(1), new anchor.BN(3600), new anchor.BN(3600))
+      .accounts({
+        configAccount: configAccount,
+        governanceConfig: governanceConfig,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+
+    // a one time cause wins round 1 alone
+    const roundId = new anchor.BN(1)
+    const [round] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('voting_round'), roundId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .createVotingRoundHandler(
+        new anchor.BN(Math.floor(Date.now() / 1000) - 1),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 6),
+        new anchor.BN(1),
+        1,
+        { annualOneTimeCauses: {} },
+        false
+      )
+      .accounts({
+        configAccount: configAccount,
+        roundRegistry: roundRegistry,
+        votingRound: round,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    const charityId = (await program.account.configAccount.fetch(configAccount)).config.nextCharityId
+    const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('charity'), charityId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const charityWallet = anchor.web3.Keypair.generate()
+    await program.methods
+      .registerCharityHandler(
+        "Schools for All",
+        charityWallet.publicKey,
+        { annualOneTimeCauses: {} },
+        new anchor.BN(Math.floor(Date.now() / 1000)),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60)
+      )
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        registrar: admin.publicKey,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    await program.methods
+      .addCharityToRoundHandler(roundId, charityId)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        charity: charityAccount,
+        attestation: null,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    const [donationVault, vault] = donationVaultOf(2)
+    await program.methods
+      .initializeDonationVaultHandler({ annualOneTimeCauses: {} })
+      .accounts({
+        configAccount: configAccount,
+        donationVault: donationVault,
+        vault: vault,
+        mint: tokenMint,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([admin])
+      .rpc();
+    // the vault replaces the donation wallet of the category
+    const config = (await program.account.configAccount.fetch(configAccount)).config
+    assert.equal(config.annualOneTimeCausesWallet.toBase58(), donationVault.toBase58())
+    await mintTo(program.provider.connection, admin, tokenMint, vault, admin, 100e6, [], {}, TOKEN_PROGRAM_ID);
+    await program.methods
+      .setRoundAllocationHandler(roundId, new anchor.BN(100e6))
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        donationVault: donationVault,
+        vault: vault,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+
+    // staking again restores the voting power spent in "castVote"
+    const stakeCount = (await program.account.userStakeInfo.fetch(userStakePDA)).stakeCount
+    const [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user_stake'), user.publicKey.toBuffer(), stakeCount.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .stakeTokensHandler(new anchor.BN(10e6), new anchor.BN(1), null)
+      .accounts({
+        configAccount: configAccount,
+        mint: tokenMint,
+        stakingPool: stakingPool,
+        user: userStakePDA,
+        userStake: userStake,
+        userAuthority: user.publicKey,
+        userTokenAccount: userAta.address,
+        poolTokenAccount: stakingPoolAta.address,
+        referralConfig: null,
+        referrerUser: null,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([user])
+      .rpc();
+    const [voteRecord] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('vote'), charityAccount.toBuffer(), user.publicKey.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .castVoteHandler(charityId)
+      .accounts({
+        voteRecord: voteRecord,
+        voter: user.publicKey,
+        configAccount: configAccount,
+        charity: charityAccount,
+        user: userStakePDA,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    await sleep(7000); // Wait for the round to end
+    await program.methods
+      .finalizeRoundHandler(roundId)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        donationVault: donationVault,
+        admin: admin.publicKey,
+      })
+      .remainingAccounts([{ pubkey: charityAccount, isWritable: false, isSigner: false }])
+      .signers([admin])
+      .rpc();
+
+    // a one time cause is not paid at once, only through its milestones
+    const charityAta = await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      charityWallet.publicKey,
+      false,
+      null,
+      null,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID,
+    );
+    try {
+      await program.methods
+        .payCharityHandler(roundId, charityId)
+        .accounts({
+          configAccount: configAccount,
+          votingRound: round,
+          charity: charityAccount,
+          attestation: null,
+          donationVault: donationVault,
+          vault: vault,
+          charityTokenAccount: charityAta.address,
+          mint: tokenMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(hookAccounts)
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("MilestonePlanRequired"))
+    }
+
+    // 60 CHAR once the school is built, 20 CHAR for a report due in 3 seconds, 20 CHAR for a later one
+    const [milestonePlan] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('milestone_plan'), charityAccount.toBuffer()],
+      program.programId
+    );
+    const now = Math.floor(Date.now() / 1000)
+    const registerMilestones = (lastDeadline: number) => program.methods
+      .registerMilestonesHandler(roundId, charityId, [
+        { amount: new anchor.BN(60e6), evidenceHash: Array(32).fill(1), deadline: new anchor.BN(lastDeadline) },
+        { amount: new anchor.BN(20e6), evidenceHash: Array(32).fill(2), deadline: new anchor.BN(now + 3) },
+        { amount: new anchor.BN(20e6), evidenceHash: Array(32).fill(6), deadline: new anchor.BN(now + 60) },
+      ])
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        charity: charityAccount,
+        milestonePlan: milestonePlan,
+        governanceConfig: governanceConfig,
+        charityAdmin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    // deadlines are bounded by the max milestone duration of the governance config
+    try {
+      await registerMilestones(now + 7200);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("InvalidMilestones"))
+    }
+    await registerMilestones(now + 60);
+    // the payout is committed to the milestones
+    assert.equal((await program.account.charity.fetch(charityAccount)).paidOut, true)
+
+    await program.methods
+      .submitMilestoneProofHandler(charityId, 0, Array(32).fill(3))
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        milestonePlan: milestonePlan,
+        charityAdmin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    const [milestoneVote] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('milestone_vote'), milestonePlan.toBuffer(), Buffer.from([0]), user.publicKey.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .voteMilestoneHandler(charityId, 0, true)
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        milestonePlan: milestonePlan,
+        governanceConfig: governanceConfig,
+        milestoneVote: milestoneVote,
+        user: userStakePDA,
+        voter: user.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+    // the stake that voted cannot be withdrawn before the votes on the proof close
+    const lockedUntil = (await program.account.userStakeInfo.fetch(userStakePDA)).governanceLockedUntil
+    assert.equal(Number(lockedUntil), now + 60)
+
+    // approved, released before the end of the objection window
+    await program.methods
+      .releaseMilestoneHandler(charityId, 0)
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        milestonePlan: milestonePlan,
+        votingRound: round,
+        attestation: null,
+        governanceConfig: governanceConfig,
+        donationVault: donationVault,
+        vault: vault,
+        charityTokenAccount: charityAta.address,
+        mint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .rpc();
+
+    // the report is not submitted in time and goes back to the donation pool
+    await sleep(4000);
+    const reclaim = (index: number) => program.methods
+      .reclaimMilestoneHandler(charityId, index)
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        milestonePlan: milestonePlan,
+        governanceConfig: governanceConfig,
+        votingRound: round,
+        donationVault: donationVault,
+        attestation: null,
+        admin: null,
+      })
+      .rpc();
+    await reclaim(1);
+    try {
+      await reclaim(2);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("MilestoneNotReclaimable"))
+    }
+
+    // the charity is cancelled with a milestone left, which goes back at once
+    const cancel = (plan: anchor.web3.PublicKey | null) => program.methods
+      .cancelCharityHandler(charityId, 3)
+      .accounts({
+        configAccount: configAccount,
+        charity: charityAccount,
+        milestonePlan: plan,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    try {
+      await cancel(null);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityNotRemovable"))
+    }
+    await cancel(milestonePlan);
+    assert.deepEqual((await program.account.charity.fetch(charityAccount)).status, { cancelled: {} })
+    await reclaim(2);
+
+    const plan = await program.account.milestonePlan.fetch(milestonePlan)
+    assert.equal(Number(plan.released), 60e6)
+    assert.equal(Number(plan.reclaimed), 40e6)
+    assert.deepEqual(plan.milestones[0].status, { released: {} })
+    assert.deepEqual(plan.milestones[1].status, { reclaimed: {} })
+    assert.deepEqual(plan.milestones[2].status, { reclaimed: {} })
+    const vaultBalance = await program.provider.connection.getTokenAccountBalance(vault)
+    assert.equal(vaultBalance.value.amount, (40e6).toString())
+    // the reclaimed milestone is no longer committed
+    assert.equal(Number((await program.account.donationVault.fetch(donationVault)).committed), 0)
+  })
+
+  it("nominations", async () => {
+    const [nominationConfig] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('nomination_config')],
+      program.programId
+    );
+    const [bondVault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('nomination_bond_vault'), tokenMint.toBuffer()],
+      program.programId
+    );
+    // 5 CHAR bond, any staker endorsing is enough, 2 seconds to endorse and 5 more to accept
+    await program.methods
+      .initializeNominationConfigHandler(new anchor.BN(5e6), new anchor.BN(1), new anchor.BN(2), new anchor.BN(5))
+      .accounts({
+        configAccount: configAccount,
+        nominationConfig: nominationConfig,
+        bondVault: bondVault,
+        mint: tokenMint,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([admin])
+      .rpc();
+
+    const roundId = new anchor.BN(2)
+    const [round] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('voting_round'), roundId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .createVotingRoundHandler(
+        new anchor.BN(Math.floor(Date.now() / 1000) - 1),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60),
+        new anchor.BN(1),
+        1,
+        { annualOneTimeCauses: {} },
+        false
+      )
+      .accounts({
+        configAccount: configAccount,
+        roundRegistry: roundRegistry,
+        votingRound: round,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+
+    const nominationOf = (id: number) => anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('nomination'), new anchor.BN(id).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+    const nominate = (id: number, title: string, category) => program.methods
+      .nominateCharityHandler(title, anchor.web3.Keypair.generate().publicKey, category)
+      .accounts({
+        configAccount: configAccount,
+        nominationConfig: nominationConfig,
+        nomination: nominationOf(id),
+        user: userStakePDA,
+        bondVault: bondVault,
+        nominatorTokenAccount: userAta.address,
+        nominator: user.publicKey,
+        mint: tokenMint,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([user])
+      .rpc();
+    await nominate(0, "Trees for All", { annualOneTimeCauses: {} });
+    await nominate(1, "Books for All", { monthlyInfiniteImpactCauses: {} });
+    await nominate(2, "Wells for All", { annualOneTimeCauses: {} });
+    await nominate(3, "Farms for All", { annualOneTimeCauses: {} });
+    const nomination = await program.account.nomination.fetch(nominationOf(0))
+    const bond = Number(nomination.bond)
+    assert.isAbove(bond, 0)
+    assert.deepEqual(nomination.status, { open: {} })
+
+    const [endorsement] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('endorsement'), nominationOf(0).toBuffer(), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const endorse = (id: number, endorsement: anchor.web3.PublicKey) => program.methods
+      .endorseNominationHandler(new anchor.BN(id))
+      .accounts({
+        configAccount: configAccount,
+        nomination: nominationOf(id),
+        endorsement: endorsement,
+        user: userStakePDA,
+        endorser: user.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+    await endorse(0, endorsement);
+    await endorse(2, anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('endorsement'), nominationOf(2).toBuffer(), user.publicKey.toBuffer()],
+      program.programId
+    )[0]);
+    await endorse(3, anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('endorsement'), nominationOf(3).toBuffer(), user.publicKey.toBuffer()],
+      program.programId
+    )[0]);
+
+    const charityId = (await program.account.configAccount.fetch(configAccount)).config.nextCharityId
+    const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('charity'), charityId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const acceptAs = (signer: anchor.web3.Keypair, id: number, charity: anchor.web3.PublicKey, votingRound: anchor.web3.PublicKey | null) => program.methods
+      .acceptNominationHandler(new anchor.BN(id), roundId)
+      .accounts({
+        configAccount: configAccount,
+        nominationConfig: nominationConfig,
+        nomination: nominationOf(id),
+        charity: charity,
+        votingRound: votingRound,
+        attestation: null,
+        bondVault: bondVault,
+        nominatorTokenAccount: userAta.address,
+        admin: signer.publicKey,
+        mint: tokenMint,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([signer])
+      .rpc();
+    const accept = () => acceptAs(admin, 0, charityAccount, round);
+    try {
+      await accept();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("NominationPhaseNotEnded"))
+    }
+
+    await sleep(3000); // Wait for the nomination phase to end
+    // the bond of an endorsed nomination waits for the admin during the acceptance period
+    const refund = (id: number) => program.methods
+      .refundNominationBondHandler(new anchor.BN(id))
+      .accounts({
+        configAccount: configAccount,
+        nominationConfig: nominationConfig,
+        nomination: nominationOf(id),
+        bondVault: bondVault,
+        nominatorTokenAccount: userAta.address,
+        mint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .rpc();
+    try {
+      await refund(3);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("NominationAcceptancePending"))
+    }
+    // the endorsed nomination joins the round and its bond is refunded
+    await accept();
+    const charity = await program.account.charity.fetch(charityAccount)
+    assert.equal(charity.title, "Trees for All")
+    assert.equal(charity.admin.toBase58(), user.publicKey.toBase58())
+    assert.equal(charity.round.toBase58(), round.toBase58())
+    assert.deepEqual((await program.account.nomination.fetch(nominationOf(0))).status, { accepted: {} })
+
+    // only the admin accepts nominations, without a round the charity waits for one
+    const [waitingCharity] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('charity'), charityId.addn(1).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    try {
+      await acceptAs(user, 2, waitingCharity, null);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("ConstraintRaw"))
+    }
+    await acceptAs(admin, 2, waitingCharity, null);
+    const waiting = await program.account.charity.fetch(waitingCharity)
+    assert.equal(waiting.title, "Wells for All")
+    assert.equal(waiting.round.toBase58(), anchor.web3.PublicKey.default.toBase58())
+    assert.deepEqual((await program.account.nomination.fetch(nominationOf(2))).status, { accepted: {} })
+
+    // nobody endorsed the second one, its bond goes to the donation pool
+    const [donationVault, vault] = donationVaultOf(1)
+    const before = Number((await program.provider.connection.getTokenAccountBalance(vault)).value.amount)
+    await program.methods
+      .rejectNominationHandler(new anchor.BN(1))
+      .accounts({
+        configAccount: configAccount,
+        nominationConfig: nominationConfig,
+        nomination: nominationOf(1),
+        bondVault: bondVault,
+        donationVault: donationVault,
+        vault: vault,
+        mint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .rpc();
+    const after = Number((await program.provider.connection.getTokenAccountBalance(vault)).value.amount)
+    assert.isAbove(after, before)
+    assert.deepEqual((await program.account.nomination.fetch(nominationOf(1))).status, { rejected: {} })
+
+    // the admin never accepted the last one, anyone refunds its bond after the acceptance period
+    await sleep(5000);
+    const nominatorBefore = Number((await program.provider.connection.getTokenAccountBalance(userAta.address)).value.amount)
+    await refund(3);
+    const nominatorAfter = Number((await program.provider.connection.getTokenAccountBalance(userAta.address)).value.amount)
+    assert.isAbove(nominatorAfter, nominatorBefore)
+    assert.deepEqual((await program.account.nomination.fetch(nominationOf(3))).status, { lapsed: {} })
+    try {
+      await acceptAs(admin, 3, anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('charity'), charityId.addn(2).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0], null);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("NominationAlreadyResolved"))
+    }
+    const bondBalance = await program.provider.connection.getTokenAccountBalance(bondVault)
+    assert.equal(bondBalance.value.amount, "0")
+  })
+
+  it("attestation", async () => {
+    // the admin key acts as verifier
+    const [verifier] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('verifier'), admin.publicKey.toBuffer()],
+      program.programId
     );
-    let monthlyCharityLotteryWalletAta = await getOrCreateAssociatedTokenAccount(
-      program.provider.connection,
-      admin,
-      tokenMint,
-      monthlyCharityLotteryWallet.publicKey,
-      false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+    await program.methods
+      .setVerifierHandler(admin.publicKey, true)
+      .accounts({
+        configAccount: configAccount,
+        verifier: verifier,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    const charityWallet = anchor.web3.Keypair.generate()
+    const [attestation] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('attestation'), charityWallet.publicKey.toBuffer()],
+      program.programId
     );
-    let annualCharityLotteryWalletAta = await getOrCreateAssociatedTokenAccount(
-      program.provider.connection,
-      admin,
-      tokenMint,
-      annualCharityLotteryWallet.publicKey,
-      false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+    const attest = () => program.methods
+      .issueAttestationHandler(
+        charityWallet.publicKey,
+        Array(32).fill(6),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 3600)
+      )
+      .accounts({
+        configAccount: configAccount,
+        verifier: verifier,
+        attestation: attestation,
+        authority: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    await attest();
+
+    // round 3 only accepts attested charities
+    const roundId = new anchor.BN(3)
+    const [round] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('voting_round'), roundId.toArrayLike(Buffer, "le", 8)],
+      program.programId
     );
-    let monthlyOneTimeCausesWalletAta = await getOrCreateAssociatedTokenAccount(
-      program.provider.connection,
-      admin,
-      tokenMint,
-      monthlyOneTimeCausesWallet.publicKey,
-      false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+    await program.methods
+      .createVotingRoundHandler(
+        new anchor.BN(Math.floor(Date.now() / 1000) - 1),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 6),
+        new anchor.BN(1),
+        1,
+        { monthlyInfiniteImpactCauses: {} },
+        true
+      )
+      .accounts({
+        configAccount: configAccount,
+        roundRegistry: roundRegistry,
+        votingRound: round,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    const register = async (title: string, wallet: anchor.web3.PublicKey) => {
+      const charityId = (await program.account.configAccount.fetch(configAccount)).config.nextCharityId
+      const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('charity'), charityId.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      await program.methods
+        .registerCharityHandler(
+          title,
+          wallet,
+          { monthlyInfiniteImpactCauses: {} },
+          new anchor.BN(Math.floor(Date.now() / 1000)),
+          new anchor.BN(Math.floor(Date.now() / 1000) + 60)
+        )
+        .accounts({
+          configAccount: configAccount,
+          charity: charityAccount,
+          registrar: admin.publicKey,
+          admin: admin.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([admin])
+        .rpc();
+      return [charityId, charityAccount] as const
+    }
+    const addToRound = (charityId: anchor.BN, charityAccount: anchor.web3.PublicKey, attestation) => program.methods
+      .addCharityToRoundHandler(roundId, charityId)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        charity: charityAccount,
+        attestation: attestation,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    const [unverifiedId, unverifiedCharity] = await register("Unverified", anchor.web3.Keypair.generate().publicKey)
+    try {
+      await addToRound(unverifiedId, unverifiedCharity, null);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityNotAttested"))
+    }
+    const [charityId, charityAccount] = await register("Verified", charityWallet.publicKey)
+    await addToRound(charityId, charityAccount, attestation);
+    await program.methods
+      .setRoundAllocationHandler(roundId, new anchor.BN(1e6))
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        donationVault: donationVaultOf(1)[0],
+        vault: donationVaultOf(1)[1],
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+
+    // staking again restores the voting power spent in "milestones"
+    const stakeCount = (await program.account.userStakeInfo.fetch(userStakePDA)).stakeCount
+    const [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user_stake'), user.publicKey.toBuffer(), stakeCount.toArrayLike(Buffer, "le", 8)],
+      program.programId
     );
-    let annualOneTimeCausesWalletAta = await getOrCreateAssociatedTokenAccount(
-      program.provider.connection,
-      admin,
-      tokenMint,
-      annualOneTimeCausesWallet.publicKey,
-      false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+    await program.methods
+      .stakeTokensHandler(new anchor.BN(10e6), new anchor.BN(1), null)
+      .accounts({
+        configAccount: configAccount,
+        mint: tokenMint,
+        stakingPool: stakingPool,
+        user: userStakePDA,
+        userStake: userStake,
+        userAuthority: user.publicKey,
+        userTokenAccount: userAta.address,
+        poolTokenAccount: stakingPoolAta.address,
+        referralConfig: null,
+        referrerUser: null,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([user])
+      .rpc();
+    const [voteRecord] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('vote'), charityAccount.toBuffer(), user.publicKey.toBuffer()],
+      program.programId
     );
-    let monthlyInfiniteImpactCausesWalletAta = await getOrCreateAssociatedTokenAccount(
+    await program.methods
+      .castVoteHandler(charityId)
+      .accounts({
+        voteRecord: voteRecord,
+        voter: user.publicKey,
+        configAccount: configAccount,
+        charity: charityAccount,
+        user: userStakePDA,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    await sleep(7000); // Wait for the round to end
+    await program.methods
+      .finalizeRoundHandler(roundId)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        donationVault: donationVaultOf(1)[0],
+        admin: admin.publicKey,
+      })
+      .remainingAccounts([{ pubkey: charityAccount, isWritable: false, isSigner: false }])
+      .signers([admin])
+      .rpc();
+
+    // a revoked attestation blocks the payout until it is issued again
+    await program.methods
+      .revokeAttestationHandler(charityWallet.publicKey)
+      .accounts({
+        configAccount: configAccount,
+        verifier: verifier,
+        attestation: attestation,
+        authority: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    const charityAta = await getOrCreateAssociatedTokenAccount(
       program.provider.connection,
       admin,
       tokenMint,
-      monthlyInfiniteImpactCausesWallet.publicKey,
+      charityWallet.publicKey,
       false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+      null,
+      null,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID,
     );
-    let annualInfiniteImpactCausesWalletAta = await getOrCreateAssociatedTokenAccount(
-      program.provider.connection,
-      admin,
-      tokenMint,
-      annualInfiniteImpactCausesWallet.publicKey,
-      false,
-          null,
-    null,
-    TOKEN_2022_PROGRAM_ID,
-    ASSOCIATED_PROGRAM_ID,
+    const [donationVault, vault] = donationVaultOf(1)
+    const pay = () => program.methods
+      .payCharityHandler(roundId, charityId)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        charity: charityAccount,
+        attestation: attestation,
+        donationVault: donationVault,
+        vault: vault,
+        charityTokenAccount: charityAta.address,
+        mint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .rpc();
+    try {
+      await pay();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityNotAttested"))
+    }
+    // only the admin can send the share of the unattested winner back to the pool
+    try {
+      await program.methods
+        .reclaimCharityPayoutHandler(roundId, charityId)
+        .accounts({
+          configAccount: configAccount,
+          votingRound: round,
+          charity: charityAccount,
+          donationVault: donationVault,
+          attestation: attestation,
+          admin: null,
+        })
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityPayoutNotReclaimable"))
+    }
+    // another verifier cannot renew the attestation
+    const otherVerifier = anchor.web3.Keypair.generate()
+    const [otherVerifierAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('verifier'), otherVerifier.publicKey.toBuffer()],
+      program.programId
     );
+    await program.methods
+      .setVerifierHandler(otherVerifier.publicKey, true)
+      .accounts({
+        configAccount: configAccount,
+        verifier: otherVerifierAccount,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    try {
+      await program.methods
+        .issueAttestationHandler(
+          charityWallet.publicKey,
+          Array(32).fill(7),
+          new anchor.BN(Math.floor(Date.now() / 1000) + 3600)
+        )
+        .accounts({
+          configAccount: configAccount,
+          verifier: otherVerifierAccount,
+          attestation: attestation,
+          authority: otherVerifier.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([otherVerifier])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("AttestationVerifierMismatch"))
+    }
+    await attest();
+    await pay();
+    assert.equal((await program.account.charity.fetch(charityAccount)).paidOut, true)
+  })
 
- 
-    let total = 1000e6; // 1000 tokens
+  it("cancel and veto", async () => {
+    const roundId = new anchor.BN(4)
+    const [round] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('voting_round'), roundId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .createVotingRoundHandler(
+        new anchor.BN(Math.floor(Date.now() / 1000) - 1),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 6),
+        new anchor.BN(1),
+        2,
+        { monthlyInfiniteImpactCauses: {} },
+        false
+      )
+      .accounts({
+        configAccount: configAccount,
+        roundRegistry: roundRegistry,
+        votingRound: round,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    const charities = []
+    for (const title of ["Fraud for All", "Scam for All"]) {
+      const charityId = (await program.account.configAccount.fetch(configAccount)).config.nextCharityId
+      const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from('charity'), charityId.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      await program.methods
+        .registerCharityHandler(
+          title,
+          anchor.web3.Keypair.generate().publicKey,
+          { monthlyInfiniteImpactCauses: {} },
+          new anchor.BN(Math.floor(Date.now() / 1000)),
+          new anchor.BN(Math.floor(Date.now() / 1000) + 60)
+        )
+        .accounts({
+          configAccount: configAccount,
+          charity: charityAccount,
+          registrar: admin.publicKey,
+          admin: admin.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([admin])
+        .rpc();
+      await program.methods
+        .addCharityToRoundHandler(roundId, charityId)
+        .accounts({
+          configAccount: configAccount,
+          votingRound: round,
+          charity: charityAccount,
+          attestation: null,
+          admin: admin.publicKey,
+        })
+        .signers([admin])
+        .rpc();
+      charities.push([charityId, charityAccount])
+    }
+    const [[cancelledId, cancelledCharity], [vetoedId, vetoedCharity]] = charities
 
+    // staking again restores the voting power spent in "attestation"
+    const stakeCount = (await program.account.userStakeInfo.fetch(userStakePDA)).stakeCount
+    const [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user_stake'), user.publicKey.toBuffer(), stakeCount.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
     await program.methods
-      .releaseRewardsHandler(new anchor.BN(total))
+      .stakeTokensHandler(new anchor.BN(10e6), new anchor.BN(1), null)
       .accounts({
         configAccount: configAccount,
-        treasuryAuthority: treasuryAuthority.publicKey,
-        treasuryAta: treasuryAuthorityAta.address,
-        monthlyTopTierAta:monthlyTopTierWalletAta.address,
-        annualTopTierAta:annualTopTierWalletAta.address,
-        monthlyCharityLotteryAta:monthlyCharityLotteryWalletAta.address,
-        annualCharityLotteryAta:annualCharityLotteryWalletAta.address,
+        mint: tokenMint,
+        stakingPool: stakingPool,
+        user: userStakePDA,
+        userStake: userStake,
+        userAuthority: user.publicKey,
+        userTokenAccount: userAta.address,
+        poolTokenAccount: stakingPoolAta.address,
+        referralConfig: null,
+        referrerUser: null,
+        systemProgram: anchor.web3.SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
-        mint:tokenMint,
-
       })
-      .signers([treasuryAuthority])
+      .remainingAccounts(hookAccounts)
+      .signers([user])
+      .rpc();
+    const [voteRecord] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('vote'), cancelledCharity.toBuffer(), user.publicKey.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .castVoteHandler(cancelledId)
+      .accounts({
+        voteRecord: voteRecord,
+        voter: user.publicKey,
+        configAccount: configAccount,
+        charity: cancelledCharity,
+        user: userStakePDA,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
       .rpc();
 
-
-        await program.methods
-      .releaseDonationsHandler(new anchor.BN(total))
+    // the admin cancels the charity that received the votes
+    const cancel = () => program.methods
+      .cancelCharityHandler(cancelledId, 3)
       .accounts({
         configAccount: configAccount,
-        treasuryAuthority: treasuryAuthority.publicKey,
-        treasuryAta: treasuryAuthorityAta.address,
-  
-        monthlyOneTimeCausesAta:monthlyOneTimeCausesWalletAta.address,
-        annualOneTimeCausesAta:annualOneTimeCausesWalletAta.address,
-        monthlyInfiniteImpactCausesAta:monthlyInfiniteImpactCausesWalletAta.address,
-        annualInfiniteImpactCausesAta:annualInfiniteImpactCausesWalletAta.address,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        charFundsAta: charFundsAta.address,
-        mint:tokenMint,
-
+        charity: cancelledCharity,
+        milestonePlan: null,
+        admin: admin.publicKey,
       })
-      .signers([treasuryAuthority])
+      .signers([admin])
       .rpc();
-     
-     
+    await cancel();
+    const cancelled = await program.account.charity.fetch(cancelledCharity)
+    assert.deepEqual(cancelled.status, { cancelled: {} })
+    assert.equal(cancelled.removalReason, 3)
+    try {
+      await cancel();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityNotRemovable"))
+    }
+
+    // the stakers veto the other one, a proposal takes its id from the governance config
+    const vetoOf = (id: anchor.BN) => anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('charity_veto'), id.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+    const propose = async (reason: number) => {
+      const vetoId = (await program.account.governanceConfig.fetch(governanceConfig)).nextVetoId
       await program.methods
-      .releaseStakingFundsHandler(new anchor.BN(total))
+        .proposeCharityVetoHandler(vetoedId, reason)
+        .accounts({
+          configAccount: configAccount,
+          charity: vetoedCharity,
+          milestonePlan: null,
+          governanceConfig: governanceConfig,
+          charityVeto: vetoOf(vetoId),
+          user: userStakePDA,
+          proposer: user.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      return vetoId
+    }
+    const vote = (vetoId: anchor.BN) => program.methods
+      .voteCharityVetoHandler(vetoedId, vetoId)
       .accounts({
         configAccount: configAccount,
-        treasuryAuthority: treasuryAuthority.publicKey,
-        treasuryAta: treasuryAuthorityAta.address,
-        stakingRewardAta: stakingRewardAta.address,
-        stakingPool: stakingPool,
-        tokenProgram: TOKEN_PROGRAM_ID,
-        mint:tokenMint,
-
+        charity: vetoedCharity,
+        milestonePlan: null,
+        charityVeto: vetoOf(vetoId),
+        governanceConfig: governanceConfig,
+        vetoVote: anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from('veto_vote'), vetoOf(vetoId).toBuffer(), user.publicKey.toBuffer()],
+          program.programId
+        )[0],
+        user: userStakePDA,
+        voter: user.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
       })
-      .signers([treasuryAuthority])
+      .signers([user])
+      .rpc();
+    const setVetoPeriod = (period: number) => program.methods
+      .updateGovernanceConfigHandler(new anchor.BN(2), new anchor.BN(1), new anchor.BN(1), new anchor.BN(1), new anchor.BN(3600), new anchor.BN(period))
+      .accounts({
+        configAccount: configAccount,
+        governanceConfig: governanceConfig,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
       .rpc();
-  })
-it("buyback and burn", async () => {
 
+    // a proposal is only open to votes during the veto period
+    await setVetoPeriod(1);
+    const staleVeto = await propose(5);
+    await sleep(2000);
+    try {
+      await vote(staleVeto);
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("VetoVotingClosed"))
+    }
+    await setVetoPeriod(3600);
+    // the charity is proposed again
+    const vetoId = await propose(7);
+    await vote(vetoId);
+    // the stake of the voter stays locked until the veto period ends
+    const endsAt = (await program.account.charityVeto.fetch(vetoOf(vetoId))).endsAt
+    assert.isAtLeast(Number((await program.account.userStakeInfo.fetch(userStakePDA)).governanceLockedUntil), Number(endsAt))
+    const vetoed = await program.account.charity.fetch(vetoedCharity)
+    assert.deepEqual(vetoed.status, { vetoed: {} })
+    assert.equal(vetoed.removalReason, 7)
+
+    // the votes of the cancelled charity are left out of the tally
+    await sleep(7000); // Wait for the round to end
     await program.methods
-      .buybackBurnHandler()
+      .finalizeRoundHandler(roundId)
       .accounts({
         configAccount: configAccount,
-        mint: tokenMint,
-        burnWalletAta: deathWalletAta.address,
-        burnAuthority: deathWallet.publicKey,
-        tokenProgram: TOKEN_PROGRAM_ID,
+        votingRound: round,
+        donationVault: donationVaultOf(1)[0],
+        admin: admin.publicKey,
       })
-      .signers([deathWallet])
+      .remainingAccounts(charities.map(([, pubkey]) => ({ pubkey, isWritable: false, isSigner: false })))
+      .signers([admin])
       .rpc();
+    const data = await program.account.votingRound.fetch(round)
+    assert.equal(Number(data.totalVotes), 0)
+    assert.deepEqual(data.status, { quorumNotReached: {} })
   })
 
-
-
-
-  
-
-
-  it("register Charity", async () => {
-    let data = await program.account.configAccount.fetch(configAccount)
-
-    let name = "Water for All";
+  it("cancel a round winner", async () => {
+    const roundId = new anchor.BN(5)
+    const [round] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('voting_round'), roundId.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .createVotingRoundHandler(
+        new anchor.BN(Math.floor(Date.now() / 1000) - 1),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 6),
+        new anchor.BN(1),
+        1,
+        { monthlyInfiniteImpactCauses: {} },
+        false
+      )
+      .accounts({
+        configAccount: configAccount,
+        roundRegistry: roundRegistry,
+        votingRound: round,
+        admin: admin.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([admin])
+      .rpc();
+    const charityId = (await program.account.configAccount.fetch(configAccount)).config.nextCharityId
     const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from('charity'), data.config.nextCharityId.toArrayLike(Buffer, "le", 8)],
+      [Buffer.from('charity'), charityId.toArrayLike(Buffer, "le", 8)],
       program.programId
     );
-
-    let startTime = Math.floor(Date.now() / 1000) -1;
-    let endTime = Math.floor(Date.now() / 1000) + 19;
-    const charityWallet = anchor.web3.Keypair.generate()
-    const tx = await program.methods
+    await program.methods
       .registerCharityHandler(
-        name,
-        charityWallet.publicKey,
-        new anchor.BN(startTime),
-        new anchor.BN(endTime)
+        "Winner for All",
+        anchor.web3.Keypair.generate().publicKey,
+        { monthlyInfiniteImpactCauses: {} },
+        new anchor.BN(Math.floor(Date.now() / 1000)),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 60)
       )
       .accounts({
         configAccount: configAccount,
@@ -715,56 +3410,116 @@ it("buyback and burn", async () => {
       })
       .signers([admin])
       .rpc();
-  })
-  it("castVote", async () => {
-        await sleep(10000); // Wait for proposal duration to pass
-
-      const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('charity'), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
-        program.programId
-      );
-      const [voteRecord] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from('vote'), charityAccount.toBuffer(), user.publicKey.toBuffer()],
-        program.programId
-      );
-
-      const tx = await program.methods
-        .castVoteHandler(
-          new anchor.BN(0),
-        )
-        .accounts({
-          voteRecord: voteRecord,
-          voter: user.publicKey,
-          configAccount: configAccount,
-          charity: charityAccount,
-          user: userStakePDA,
-          stakingPool: stakingPool,
-          systemProgram: anchor.web3.SystemProgram.programId,
-        })
-        .signers([user])
-        .rpc();
-  
-  })
-
-
-
+    await program.methods
+      .addCharityToRoundHandler(roundId, charityId)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        charity: charityAccount,
+        attestation: null,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+    const [donationVault, vault] = donationVaultOf(1)
+    const committedBefore = Number((await program.account.donationVault.fetch(donationVault)).committed)
+    await program.methods
+      .setRoundAllocationHandler(roundId, new anchor.BN(1e6))
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        donationVault: donationVault,
+        vault: vault,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
 
-  it("finalize Charity", async () => {
-    await sleep(11000); // Wait for charity voting duration to pass
-    const [charityAccount] = anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from('charity'), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+    // staking again restores the voting power spent in "cancel and veto"
+    const stakeCount = (await program.account.userStakeInfo.fetch(userStakePDA)).stakeCount
+    const [userStake] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('user_stake'), user.publicKey.toBuffer(), stakeCount.toArrayLike(Buffer, "le", 8)],
       program.programId
     );
+    await program.methods
+      .stakeTokensHandler(new anchor.BN(10e6), new anchor.BN(1), null)
+      .accounts({
+        configAccount: configAccount,
+        mint: tokenMint,
+        stakingPool: stakingPool,
+        user: userStakePDA,
+        userStake: userStake,
+        userAuthority: user.publicKey,
+        userTokenAccount: userAta.address,
+        poolTokenAccount: stakingPoolAta.address,
+        referralConfig: null,
+        referrerUser: null,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([user])
+      .rpc();
+    await program.methods
+      .castVoteHandler(charityId)
+      .accounts({
+        voteRecord: anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from('vote'), charityAccount.toBuffer(), user.publicKey.toBuffer()],
+          program.programId
+        )[0],
+        voter: user.publicKey,
+        configAccount: configAccount,
+        charity: charityAccount,
+        user: userStakePDA,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
 
-    const tx = await program.methods
-      .finalizeCharityVoteHandler(new anchor.BN(0))
+    await sleep(7000); // Wait for the round to end
+    await program.methods
+      .finalizeRoundHandler(roundId)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        donationVault: donationVault,
+        admin: admin.publicKey,
+      })
+      .remainingAccounts([{ pubkey: charityAccount, isWritable: false, isSigner: false }])
+      .signers([admin])
+      .rpc();
+    assert.equal((await program.account.votingRound.fetch(round)).winners[0].toBase58(), charityAccount.toBase58())
+
+    // the unpaid winner is cancelled, its share goes back to the donation pool once
+    await program.methods
+      .cancelCharityHandler(charityId, 3)
       .accounts({
         configAccount: configAccount,
         charity: charityAccount,
+        milestonePlan: null,
         admin: admin.publicKey,
       })
       .signers([admin])
       .rpc();
+    const reclaim = () => program.methods
+      .reclaimCharityPayoutHandler(roundId, charityId)
+      .accounts({
+        configAccount: configAccount,
+        votingRound: round,
+        charity: charityAccount,
+        donationVault: donationVault,
+        attestation: null,
+        admin: null,
+      })
+      .rpc();
+    await reclaim();
+    assert.equal(Number((await program.account.donationVault.fetch(donationVault)).committed), committedBefore)
+    try {
+      await reclaim();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("CharityAlreadyPaid"))
+    }
   })
 
    it("request unstake", async () => {
@@ -816,6 +3571,7 @@ it("unstake", async () => {
           systemProgram: anchor.web3.SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
         })
+        .remainingAccounts(hookAccounts)
         .signers([user])
 
         .rpc();
@@ -825,6 +3581,160 @@ it("unstake", async () => {
  
 
 
+  it("settle fee epoch", async () => {
+    const ata = async (owner: anchor.web3.PublicKey) => (await getOrCreateAssociatedTokenAccount(
+      program.provider.connection,
+      admin,
+      tokenMint,
+      owner,
+      false,
+      null,
+      null,
+      TOKEN_2022_PROGRAM_ID,
+      ASSOCIATED_PROGRAM_ID,
+    )).address;
+    const [feeEpochState] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('fee_epoch_state')],
+      program.programId
+    );
+    const [feeEpoch] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from('fee_epoch'), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const accumulator = await hookProgram.account.feeAccumulator.fetch(feeAccumulator)
+    const released = (await program.account.feeRelease.fetch(feeRelease)).rewardsReleased
+
+    // the monthly releases of this period were done by "release Funds", start a new period
+    // by ending the monthly period right after the last release
+    const schedule = await program.account.releaseSchedule.fetch(releaseSchedule)
+    const lastRelease = Math.max(...schedule.lastReleaseAt.map(Number))
+    // the anchor cannot move past a release
+    try {
+      await program.methods
+        .updateReleaseScheduleHandler(
+          new anchor.BN(lastRelease + 1),
+          new anchor.BN(30 * 86400),
+          new anchor.BN(365 * 86400),
+        )
+        .accounts({
+          configAccount: configAccount,
+          releaseSchedule: releaseSchedule,
+          admin: admin.publicKey,
+        })
+        .signers([admin])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("ReleaseAnchorAfterLastRelease"))
+    }
+    await sleep(1000)
+    await program.methods
+      .updateReleaseScheduleHandler(
+        schedule.periodAnchor,
+        new anchor.BN(lastRelease - Number(schedule.periodAnchor) + 1),
+        new anchor.BN(365 * 86400),
+      )
+      .accounts({
+        configAccount: configAccount,
+        releaseSchedule: releaseSchedule,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+
+    // supply is far above the target, the maximum burn share applies
+    await program.methods
+      .setBurnPolicyHandler(true, 100, 600, 500, 50, [
+        { timestamp: new anchor.BN(0), targetSupply: new anchor.BN(1_000_000) },
+        { timestamp: new anchor.BN(Math.floor(Date.now() / 1000) + 365 * 86400), targetSupply: new anchor.BN(1) },
+      ])
+      .accounts({
+        configAccount: configAccount,
+        burnPolicy: burnPolicy,
+        admin: admin.publicKey,
+      })
+      .signers([admin])
+      .rpc();
+
+    await program.methods
+      .settleFeeEpochHandler()
+      .accounts({
+        configAccount: configAccount,
+        feeEpochState: feeEpochState,
+        feeEpoch: feeEpoch,
+        feeAccumulator: feeAccumulator,
+        feeRelease: feeRelease,
+        distributionConfig: distributionConfig,
+        outflowLimiter: outflowLimiter,
+        burnLedger: burnLedger,
+        burnPolicy: burnPolicy,
+        releaseSchedule: releaseSchedule,
+        treasury: treasury,
+        treasuryVault: treasuryVault,
+        stakingPool: stakingPool,
+        stakingRewardAta: stakingRewardAta.address,
+        monthlyTopTierAta: await ata(monthlyTopTierWallet.publicKey),
+        monthlyCharityLotteryAta: await ata(monthlyCharityLotteryWallet.publicKey),
+        annualTopTierAta: await ata(annualTopTierWallet.publicKey),
+        annualCharityLotteryAta: await ata(annualCharityLotteryWallet.publicKey),
+        monthlyOneTimeCausesAta: await ata(monthlyOneTimeCausesWallet.publicKey),
+        // the donation vaults of the categories with rounds
+        monthlyInfiniteImpactCausesAta: donationVaultOf(1)[1],
+        annualOneTimeCausesAta: donationVaultOf(2)[1],
+        annualInfiniteImpactCausesAta: await ata(annualInfiniteImpactCausesWallet.publicKey),
+        charFundsAta: await ata(charFunds.publicKey),
+        marketingVesting1: marketingVesting1,
+        marketingEscrow1: marketingEscrow1,
+        marketingVesting2: marketingVesting2,
+        marketingEscrow2: marketingEscrow2,
+        treasuryAuthority: treasuryAuthority.publicKey,
+        mint: tokenMint,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts(hookAccounts)
+      .signers([treasuryAuthority])
+      .rpc();
+
+    const epoch = await program.account.feeEpoch.fetch(feeEpoch)
+    assert.equal(Number(epoch.collected), Number(accumulator.totalFees) - Number(released))
+    // everything collected is paid out, kept in the annual reserves or carried into the next epoch
+    assert.equal(
+      Number(epoch.distributed) + Number(epoch.burned) + Number(epoch.reserved) + Number(epoch.carriedOut),
+      Number(epoch.collected) + Number(epoch.carriedIn) + Number(epoch.reservePaid)
+    )
+    const marketing = Math.floor(((Number(epoch.collected) + Number(epoch.carriedIn)) * 100) / 1000)
+    assert.equal(Number(epoch.burned), Math.floor((marketing * 600) / 1000))
+    const state = await program.account.feeEpochState.fetch(feeEpochState)
+    assert.equal(Number(state.epochCount), 1)
+
+    // fees are now only settled by epoch, the per stream releases are closed
+    try {
+      await program.methods
+        .releaseStakingFundsHandler()
+        .accounts({
+          configAccount: configAccount,
+          treasuryAuthority: treasuryAuthority.publicKey,
+          treasury: treasury,
+          treasuryVault: treasuryVault,
+          stakingRewardAta: stakingRewardAta.address,
+          stakingPool: stakingPool,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          mint: tokenMint,
+          feeAccumulator: feeAccumulator,
+          feeRelease: feeRelease,
+          distributionConfig: distributionConfig,
+          outflowLimiter: outflowLimiter,
+        })
+        .remainingAccounts(hookAccounts)
+        .signers([treasuryAuthority])
+        .rpc();
+      assert(false);
+    } catch (e) {
+      assert(e.message.includes("FeesSettledByEpoch"))
+    }
+  });
+
 });
 
 